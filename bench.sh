@@ -2,6 +2,7 @@
 # Benchmark widget startup time.
 # Measures wall time from launch until the process enters sleep state
 # (i.e. hits the event loop's epoll_wait, meaning init + first frame is done).
+# For where the time goes, run the widget once with --trace-startup.
 #
 # Usage: ./bench.sh <widget-binary> [args...]
 # Examples:
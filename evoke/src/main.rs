@@ -1,43 +1,36 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Child, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
-use std::time::Instant;
-use libc;
-use serde::Deserialize;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
 use smithay_client_toolkit as sctk;
+use sctk::output::OutputInfo;
 use sctk::reexports::calloop::generic::Generic;
 use sctk::reexports::calloop::timer::{TimeoutAction, Timer};
 use sctk::reexports::calloop::{EventLoop, Interest, Mode, PostAction};
-use sctk::reexports::calloop_wayland_source::WaylandSource;
-use sctk::compositor::{CompositorHandler, CompositorState};
-use sctk::output::{OutputHandler, OutputState};
-use sctk::registry::{ProvidesRegistryState, RegistryState};
-use sctk::registry_handlers;
-use sctk::seat::keyboard::{KeyEvent, KeyboardHandler, Keysym};
-use sctk::seat::pointer::{PointerEvent, PointerEventKind, PointerHandler};
-use sctk::seat::pointer::cursor_shape::CursorShapeManager;
-use sctk::seat::{Capability, SeatHandler, SeatState};
-use sctk::reexports::protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
-use sctk::shell::wlr_layer::{
-    Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
-    LayerSurfaceConfigure,
-};
+use sctk::seat::keyboard::{KeyEvent, Keysym, Modifiers};
+use sctk::shell::wlr_layer::{Anchor, KeyboardInteractivity, LayerSurface};
 use sctk::shell::WaylandSurface;
-use sctk::shm::slot::SlotPool;
-use sctk::shm::{Shm, ShmHandler};
-use sctk::{
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
-    delegate_registry, delegate_seat, delegate_shm,
-};
-use wayland_client::globals::registry_queue_init;
-use wayland_client::protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface};
-use wayland_client::{Connection, QueueHandle};
 use tiny_skia::Pixmap;
+use widgets_core::color::{load_colors, Palette};
+use widgets_core::cli::{self, Opt, Parsed, Spec};
+use widgets_core::clipboard::Clip;
+use widgets_core::config::{expand_path, load_config, Documented};
+use widgets_core::dbus::{self, Remote};
+use widgets_core::headless::render_to_png;
+use widgets_core::render::{clear_rect, fill_rect, Rect};
+use widgets_core::replay;
+use widgets_core::style::{Backdrop, Style, STYLE_DOC};
+use widgets_core::trace;
+use widgets_core::watch;
+use widgets_core::window::{Edges, Options, Placement, Update, Widget, Window, PLACEMENT_KEYS};
+use zbus::blocking::connection::Builder;
+use zbus::{fdo, interface};
 
 // --- Config ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(default)]
 struct Config {
     color_file: Option<String>,
@@ -51,7 +44,10 @@ struct Config {
     bar_gap: u32,
     margin: f32,
     scale: f32,
-    border_width: u32,
+    output: Option<String>,
+    style: Style,
+    #[serde(flatten)]
+    placement: Placement,
 }
 
 impl Default for Config {
@@ -68,104 +64,71 @@ impl Default for Config {
             bar_gap: 2,
             margin: 0.25,
             scale: 4.0,
-            border_width: 1,
-        }
-    }
-}
-
-fn load_config() -> Config {
-    let path = config_dir().join("evoke.toml");
-    match std::fs::read_to_string(&path) {
-        Ok(s) => match toml::from_str(&s) {
-            Ok(cfg) => cfg,
-            Err(e) => { eprintln!("evoke: failed to parse {}: {e}", path.display()); Config::default() }
+            output: None,
+            style: Style { border_width: 1.0, ..Style::default() },
+            placement: Placement { anchor: Edges(Anchor::BOTTOM), ..Placement::default() },
         }
-        Err(_) => Config::default(),
     }
 }
 
-fn config_dir() -> PathBuf {
-    let base = std::env::var("XDG_CONFIG_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| home().join(".config"));
-    base.join("widgets")
-}
-
-fn home() -> PathBuf {
-    PathBuf::from(std::env::var("HOME").unwrap())
-}
-
-fn expand_path(p: &str) -> PathBuf {
-    if let Some(rest) = p.strip_prefix("~/") {
-        home().join(rest)
-    } else {
-        PathBuf::from(p)
+impl Documented for Config {
+    type Colors = Colors;
+    const FLATTENED: &'static [(&'static str, &'static str)] = PLACEMENT_KEYS;
+    const KEYS: &'static [(&'static str, &'static str)] = &[
+        ("color_file", "walrs color file"),
+        ("model", "whisper.cpp model name, loaded from <models_dir>/ggml-<model>.bin"),
+        ("models_dir", "Directory holding whisper.cpp models"),
+        ("sounds", "Reserved for start/stop sounds; currently unused"),
+        ("width", "Panel width in logical pixels"),
+        ("height", "Panel height in logical pixels"),
+        ("bar_count", "Number of waveform bars"),
+        ("bar_width", "Bar width in logical pixels"),
+        ("bar_gap", "Gap between bars in logical pixels"),
+        ("margin", "Distance from the bottom edge as a fraction of the output height, used while margin_bottom is 0"),
+        ("scale", "Waveform amplitude gain"),
+        ("output", "Monitor: connector name, \"focused\" or \"cursor\" (both need Hyprland); unset lets the compositor choose"),
+        ("style", STYLE_DOC),
+    ];
+    fn color_file(&self) -> Option<&str> { self.color_file.as_deref() }
+    fn files(&self) -> Vec<(&'static str, String)> {
+        let model = expand_path(&self.models_dir).join(format!("ggml-{}.bin", self.model));
+        vec![("model", model.display().to_string())]
     }
 }
 
 // --- Colors ---
 
 struct Colors {
-    background: [u8; 3],
-    background_alpha: u8,
-    border: [u8; 3],
-    waveform: [u8; 3],
+    background: [u8; 4],
+    border: [u8; 4],
+    waveform: [u8; 4],
 }
 
 impl Default for Colors {
     fn default() -> Self {
         Self {
-            background: [0x1e, 0x1e, 0x2e],
-            background_alpha: 0xd9, // ~0.85
-            border: [0xcd, 0xd6, 0xf4],
-            waveform: [0x89, 0xb4, 0xfa],
+            background: [0x1e, 0x1e, 0x2e, 0xd9], // ~0.85
+            border: [0xcd, 0xd6, 0xf4, 0xff],
+            waveform: [0x89, 0xb4, 0xfa, 0xff],
         }
     }
 }
 
-fn parse_hex(s: &str) -> Option<[u8; 3]> {
-    let s = s.strip_prefix('#').unwrap_or(s);
-    if s.len() != 6 { return None; }
-    Some([u8::from_str_radix(&s[0..2], 16).ok()?,
-          u8::from_str_radix(&s[2..4], 16).ok()?,
-          u8::from_str_radix(&s[4..6], 16).ok()?])
-}
-
-fn load_colors(path: Option<&str>) -> Colors {
-    let mut colors = Colors::default();
-    let content = match path {
-        Some(p) => std::fs::read_to_string(expand_path(p)).unwrap_or_default(),
-        None => return colors,
-    };
-    for line in content.lines() {
-        if let Some((key, val)) = line.split_once('=') {
-            let (key, val) = (key.trim(), val.trim());
-            match key {
-                "background_opacity" => {
-                    if let Ok(f) = val.parse::<f32>() {
-                        colors.background_alpha = (f.clamp(0.0, 1.0) * 255.0) as u8;
-                    }
-                }
-                _ => {
-                    if let Some(c) = parse_hex(val) {
-                        match key {
-                            "background" => colors.background = c,
-                            "border" => colors.border = c,
-                            "waveform" => colors.waveform = c,
-                            _ => {}
-                        }
-                    }
-                }
-            }
-        }
+impl Palette for Colors {
+    fn color(&mut self, key: &str) -> Option<&mut [u8; 4]> {
+        Some(match key {
+            "background" => &mut self.background,
+            "border" => &mut self.border,
+            "waveform" => &mut self.waveform,
+            _ => return None,
+        })
     }
-    colors
 }
 
 // --- Constants ---
 
 const CHUNK_SAMPLES: usize = 320; // 20ms at 16kHz
-const TICK_MS: u64 = 33; // ~30fps
+const POLL_MS: u64 = 33; // SIGUSR1 and transcription result
 
 // --- Phase ---
 
@@ -183,20 +146,9 @@ extern "C" fn signal_handler(_: libc::c_int) {
 // --- App ---
 
 struct App {
-    registry_state: RegistryState,
-    seat_state: SeatState,
-    output_state: OutputState,
-    shm: Shm,
-    layer: LayerSurface,
-    pointer: Option<wl_pointer::WlPointer>,
-    keyboard: Option<wl_keyboard::WlKeyboard>,
-    cursor_shape_manager: CursorShapeManager,
-    loop_handle: sctk::reexports::calloop::LoopHandle<'static, App>,
-    pool: SlotPool,
     width: u32,
     height: u32,
-    exit: bool,
-    configured: bool,
+    scale: f32,
     colors: Colors,
     config: Config,
     // Recording state
@@ -211,11 +163,24 @@ struct App {
     pending_byte: Option<u8>,
     // Transcription result from background thread
     transcription_rx: Option<mpsc::Receiver<String>>,
+    // Transcript for the window to copy, then paste once it has
+    clip: Option<Clip>,
+    paste_pending: bool,
     // Screen height for margin calculation
-    margin_set: bool,
+    output_height: Option<i32>,
 }
 
 impl App {
+    /// Configured placement, with an unset bottom margin derived from
+    /// `margin` once the output height is known.
+    fn placement(&self) -> Placement {
+        let mut p = self.config.placement.clone();
+        if p.margin_bottom == 0 && let Some(h) = self.output_height {
+            p.margin_bottom = (h as f32 * self.config.margin) as i32;
+        }
+        p
+    }
+
     fn process_audio_chunk(&mut self, buf: &[u8]) {
         let mut start = 0;
         // Handle leftover byte from previous chunk
@@ -236,7 +201,7 @@ impl App {
             let sample = i16::from_le_bytes([chunk[0], chunk[1]]);
             self.audio_samples.push(sample);
         }
-        if remaining.len() % 2 != 0 {
+        if !remaining.len().is_multiple_of(2) {
             self.pending_byte = Some(remaining[remaining.len() - 1]);
         }
 
@@ -254,7 +219,7 @@ impl App {
         }
     }
 
-    fn stop_and_transcribe(&mut self) {
+    fn stop_and_transcribe(&mut self) -> Update {
         // Kill recorder
         if let Some(mut child) = self.recorder.take() {
             unsafe { libc::kill(child.id() as i32, libc::SIGTERM); }
@@ -271,8 +236,7 @@ impl App {
 
         if samples_f32.is_empty() {
             eprintln!("evoke: no audio captured");
-            self.exit = true;
-            return;
+            return Update::Exit;
         }
 
         eprintln!("evoke: transcribing {} samples ({:.1}s)...",
@@ -288,50 +252,85 @@ impl App {
             let text = transcribe(&model_path, &samples_f32);
             tx.send(text).ok();
         });
+        Update::Redraw
     }
 
-    fn draw(&mut self) {
-        if !self.configured { return; }
-        let c = &self.colors;
+    /// Stop recording and transcribe, as SIGUSR1 does; nothing once transcribing.
+    fn stop(&mut self) -> Update {
+        if self.phase != Phase::Recording { return Update::None; }
+        self.stop_and_transcribe()
+    }
+
+    fn tick(&mut self) -> Update {
+        // Check for SIGUSR1
+        if GOT_SIGNAL.swap(false, Ordering::AcqRel) && self.stop() == Update::Exit {
+            return Update::Exit;
+        }
+        // The window copied the transcript after the last tick
+        if self.paste_pending {
+            // Ticks keep coming during the close fade; paste only once
+            self.paste_pending = false;
+            paste();
+            return Update::Exit;
+        }
+        // Check for transcription result
+        if let Some(rx) = &self.transcription_rx && let Ok(text) = rx.try_recv() {
+            let text = text.trim().to_string();
+            if !text.is_empty() {
+                eprintln!("evoke: transcribed: {text}");
+                self.clip = Some(Clip::text(&text));
+                self.paste_pending = true;
+                return Update::None;
+            } else {
+                eprintln!("evoke: no speech detected");
+            }
+            return Update::Exit;
+        }
+        Update::None
+    }
 
-        let stride = self.width as i32 * 4;
-        let (wl_buf, canvas) = self.pool
-            .create_buffer(self.width as i32, self.height as i32, stride, wl_shm::Format::Argb8888)
-            .unwrap();
+    fn backdrop(&self) -> Backdrop {
+        let c = &self.colors;
+        Backdrop { background: c.background, border: c.border }
+    }
 
-        let mut pixmap = Pixmap::new(self.width, self.height).unwrap();
-        pixmap.fill(tiny_skia::Color::TRANSPARENT);
+    /// Logical size to buffer pixels.
+    fn px(&self, v: u32) -> u32 { (v as f32 * self.scale).round() as u32 }
 
+    fn draw(&mut self, pixmap: &mut Pixmap) {
         let pw = pixmap.width();
         let ph = pixmap.height();
 
-        // Background
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, 0, 0, self.width, self.height, c.background, c.background_alpha);
+        // The panel shows through
+        clear_rect(pixmap.data_mut(), pw, ph, Rect { x: 0, y: 0, w: pw, h: ph }, [0; 3], 0);
+        self.draw_waveform(pixmap);
+    }
+
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Vec<Rect> {
+        self.draw(pixmap);
+        vec![Rect { x: 0, y: 0, w: self.width, h: self.height }]
+    }
 
-        // Border
-        let bw = self.config.border_width;
-        if bw > 0 {
-            fill_rect(pixmap.data_mut(), pw, ph, 0, 0, self.width, bw, c.border);
-            fill_rect(pixmap.data_mut(), pw, ph, 0, self.height - bw, self.width, bw, c.border);
-            fill_rect(pixmap.data_mut(), pw, ph, 0, 0, bw, self.height, c.border);
-            fill_rect(pixmap.data_mut(), pw, ph, self.width - bw, 0, bw, self.height, c.border);
-        }
+    fn draw_waveform(&self, pixmap: &mut Pixmap) {
+        let c = &self.colors;
+        let pw = pixmap.width();
+        let ph = pixmap.height();
 
         match self.phase {
             Phase::Recording => {
                 let bar_count = self.config.bar_count;
-                let bar_w = self.config.bar_width;
-                let gap = self.config.bar_gap;
+                let bar_w = self.px(self.config.bar_width);
+                let gap = self.px(self.config.bar_gap);
                 let scale = self.config.scale;
                 let total_w = bar_count as u32 * (bar_w + gap) - gap;
                 let x_start = (self.width - total_w) / 2;
-                let max_bar_h = self.height - 20;
-                let padding_y = 10;
+                let padding_y = self.px(10);
+                let max_bar_h = self.height - 2 * padding_y;
 
                 for i in 0..bar_count {
                     let idx = (self.ring_pos + i) % bar_count;
                     let amplitude = (self.ring_buf[idx] * scale).min(1.0);
-                    let bar_h = ((amplitude * max_bar_h as f32) as u32).max(2);
+                    let bar_h = ((amplitude * max_bar_h as f32) as u32).max(self.px(2));
                     let x = x_start + i as u32 * (bar_w + gap);
                     let y = padding_y + (max_bar_h - bar_h) / 2;
                     fill_rect(pixmap.data_mut(), pw, ph, x, y, bar_w, bar_h, c.waveform);
@@ -339,36 +338,49 @@ impl App {
             }
             Phase::Transcribing => {
                 let bar_count = self.config.bar_count;
-                let bar_w = self.config.bar_width;
-                let gap = self.config.bar_gap;
+                let bar_w = self.px(self.config.bar_width);
+                let gap = self.px(self.config.bar_gap);
                 let total_w = bar_count as u32 * (bar_w + gap) - gap;
                 let x_start = (self.width - total_w) / 2;
-                let max_bar_h = self.height - 20;
-                let padding_y = 10;
+                let padding_y = self.px(10);
+                let max_bar_h = self.height - 2 * padding_y;
 
                 let t = self.started_at.elapsed().as_secs_f32();
                 for i in 0..bar_count {
                     let frac = i as f32 / bar_count as f32;
                     let amplitude = ((frac * std::f32::consts::TAU * 2.0 + t * 12.0).sin() * 0.5 + 0.5) * 0.25;
-                    let bar_h = ((amplitude * max_bar_h as f32) as u32).max(2);
+                    let bar_h = ((amplitude * max_bar_h as f32) as u32).max(self.px(2));
                     let x = x_start + i as u32 * (bar_w + gap);
                     let y = padding_y + (max_bar_h - bar_h) / 2;
                     fill_rect(pixmap.data_mut(), pw, ph, x, y, bar_w, bar_h, c.waveform);
                 }
             }
         }
+    }
+}
 
-        // Copy RGBA premul -> BGRA
-        for (dst, src) in canvas.chunks_exact_mut(4).zip(pixmap.data().chunks_exact(4)) {
-            dst[0] = src[2];
-            dst[1] = src[1];
-            dst[2] = src[0];
-            dst[3] = src[3];
-        }
-
-        wl_buf.attach_to(self.layer.wl_surface()).unwrap();
-        self.layer.wl_surface().damage_buffer(0, 0, self.width as i32, self.height as i32);
-        self.layer.wl_surface().commit();
+impl Widget for App {
+    fn draw(&mut self, pixmap: &mut Pixmap) { self.draw(pixmap); }
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Option<Vec<Rect>> { Some(self.draw_dirty(pixmap)) }
+    fn backdrop(&self) -> Backdrop { self.backdrop() }
+    fn resize(&mut self, width: u32, height: u32, scale: f32) {
+        self.width = width;
+        self.height = height;
+        self.scale = scale;
+    }
+    /// The transcribing wave moves on its own; the recording one follows the audio source.
+    fn animating(&self) -> bool { self.phase == Phase::Transcribing }
+    fn animate(&mut self, _: Instant) -> Update { Update::Redraw }
+    fn key(&mut self, event: &KeyEvent, _: &Modifiers) -> Update {
+        if event.keysym == Keysym::Escape { Update::Exit } else { Update::None }
+    }
+    fn take_clip(&mut self) -> Option<Clip> { self.clip.take() }
+    fn output_entered(&mut self, layer: &LayerSurface, info: &OutputInfo) {
+        if self.output_height.is_some() { return; }
+        self.output_height = info.logical_size.map(|s| s.1);
+        let p = self.placement();
+        layer.set_margin(p.margin_top, p.margin_right, p.margin_bottom, p.margin_left);
+        layer.wl_surface().commit();
     }
 }
 
@@ -412,10 +424,8 @@ fn transcribe(model_path: &std::path::Path, samples: &[f32]) -> String {
     let n = state.full_n_segments();
     let mut text = String::new();
     for i in 0..n {
-        if let Some(seg) = state.get_segment(i) {
-            if let Ok(s) = seg.to_str() {
-                text.push_str(s);
-            }
+        if let Some(seg) = state.get_segment(i) && let Ok(s) = seg.to_str() {
+            text.push_str(s);
         }
     }
     text
@@ -423,157 +433,95 @@ fn transcribe(model_path: &std::path::Path, samples: &[f32]) -> String {
 
 // --- Output ---
 
-fn output_text(text: &str) {
-    // Copy to clipboard
-    match Command::new("wl-copy").arg("--").stdin(Stdio::piped()).spawn() {
-        Ok(mut proc) => {
-            if let Some(mut stdin) = proc.stdin.take() {
-                use std::io::Write;
-                stdin.write_all(text.as_bytes()).ok();
-            }
-            proc.wait().ok();
-        }
-        Err(e) => eprintln!("evoke: wl-copy failed: {e}"),
-    }
-
-    // Paste via Ctrl+V
+/// Paste the clipboard into the focused window with Ctrl+V.
+fn paste() {
     Command::new("ydotool").args(["key", "29:1", "47:1", "47:0", "29:0"]).status().ok();
 }
 
-// --- Rendering helpers ---
+// --- D-Bus ---
 
-fn fill_rect(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3]) {
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i] = c[0]; data[i + 1] = c[1]; data[i + 2] = c[2]; data[i + 3] = 0xff;
-        }
-    }
-}
+struct Control(Remote<App>);
 
-fn fill_rect_alpha(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3], a: u8) {
-    if a == 0xff { return fill_rect(data, pw, ph, x, y, w, h, c); }
-    if a == 0 { return; }
-    let a32 = a as u32;
-    let inv = 255 - a32;
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i]     = ((c[0] as u32 * a32 + data[i] as u32 * inv) / 255) as u8;
-            data[i + 1] = ((c[1] as u32 * a32 + data[i + 1] as u32 * inv) / 255) as u8;
-            data[i + 2] = ((c[2] as u32 * a32 + data[i + 2] as u32 * inv) / 255) as u8;
-            data[i + 3] = ((a32 + data[i + 3] as u32 * inv / 255)) as u8;
-        }
+#[interface(name = "org.widgets.Evoke")]
+impl Control {
+    /// As SIGUSR1.
+    async fn stop(&self) -> fdo::Result<()> {
+        self.0.call(|win| {
+            let update = win.widget().stop();
+            win.apply(update);
+        }).await
     }
-}
 
-
-// --- Wayland handler boilerplate ---
-
-impl CompositorHandler for App {
-    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
-    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
-    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
-    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
-        if !self.margin_set {
-            // Set bottom margin to 25% of screen height
-            if let Some(info) = self.output_state.info(output) {
-                if let Some(size) = info.logical_size {
-                    let margin = (size.1 as f32 * self.config.margin) as u32;
-                    self.layer.set_margin(0, 0, margin as i32, 0);
-                    self.layer.wl_surface().commit();
-                    self.margin_set = true;
-                }
-            }
-        }
-    }
-    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+    #[zbus(property)]
+    async fn recording(&self) -> fdo::Result<bool> { self.0.call(|win| win.widget().phase == Phase::Recording).await }
 }
 
-impl OutputHandler for App {
-    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
-    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-}
+// --- Main ---
 
-impl SeatHandler for App {
-    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, capability: Capability) {
-        if capability == Capability::Keyboard && self.keyboard.is_none() {
-            self.keyboard = Some(self.seat_state.get_keyboard_with_repeat(
-                qh, &seat, None,
-                self.loop_handle.clone(),
-                Box::new(|_state, _wl_kbd, _event| {}),
-            ).unwrap());
-        }
-        if capability == Capability::Pointer && self.pointer.is_none() {
-            self.pointer = Some(self.seat_state.get_pointer(qh, &seat).unwrap());
-        }
-    }
-    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, _: Capability) {}
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-}
+const CLI: Spec = Spec {
+    name: "evoke",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Push-to-talk dictation: records until SIGUSR1, then types the transcript.",
+    usage: &["[OPTIONS]"],
+    opts: &[
+        Opt { name: "--replay", value: Some("SCRIPT"), help: "Feed scripted input without a compositor or recording, then exit" },
+    ],
+};
 
-impl PointerHandler for App {
-    fn pointer_frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, pointer: &wl_pointer::WlPointer, events: &[PointerEvent]) {
-        for event in events {
-            if let PointerEventKind::Enter { serial } = event.kind {
-                let device = self.cursor_shape_manager.get_shape_device(pointer, qh);
-                device.set_shape(serial, Shape::Default);
-                device.destroy();
-            }
-        }
-    }
+/// Per-run options from the command line.
+struct Invocation {
+    render_to: Option<String>,
+    replay: Option<String>,
+    scale: f32,
+    dbus: bool,
 }
 
-impl KeyboardHandler for App {
-    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
-    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
-    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
-        if event.keysym == Keysym::Escape { self.exit = true; }
+fn parse_args(opts: &Parsed, cfg: &mut Config) -> Result<Invocation, String> {
+    let mut inv = Invocation { render_to: None, replay: None, scale: 1.0, dbus: false };
+    for (name, value) in opts {
+        let v = value.clone().unwrap_or_default();
+        match *name {
+            "--render-to" => inv.render_to = Some(v),
+            "--replay" => inv.replay = Some(v),
+            "--scale" => inv.scale = cli::value(name, &v)?,
+            "--dbus" => inv.dbus = true,
+            "--output" => cfg.output = Some(v),
+            "--set" => cli::set(cfg, &v)?,
+            _ => {}
+        }
     }
-    fn repeat_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
-    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
-    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: sctk::seat::keyboard::Modifiers, _: sctk::seat::keyboard::RawModifiers, _: u32) {}
-}
-
-impl ShmHandler for App {
-    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
+    Ok(inv)
 }
 
-impl LayerShellHandler for App {
-    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
-        self.exit = true;
-    }
-    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
-        if configure.new_size.0 > 0 { self.width = configure.new_size.0; }
-        if configure.new_size.1 > 0 { self.height = configure.new_size.1; }
-        self.configured = true;
-        self.draw();
+/// Reload config and colors from disk, keeping command-line overrides;
+/// returns the files to keep watching.
+fn reload(win: &mut Window<App>, opts: &Parsed) -> Vec<PathBuf> {
+    let mut cfg: Config = load_config("evoke");
+    parse_args(opts, &mut cfg).ok();
+    win.opts.style = cfg.style.clone();
+    let app = &mut win.widget;
+    app.colors = load_colors(cfg.color_file.as_deref());
+    if cfg.bar_count != app.ring_buf.len() {
+        app.ring_buf.resize(cfg.bar_count, 0.0);
+        app.ring_pos %= cfg.bar_count.max(1);
     }
+    (win.opts.width, win.opts.height) = (cfg.width, cfg.height);
+    let watched = watch::config_files("evoke", cfg.color_file.as_deref());
+    app.config = cfg;
+    win.opts.placement = app.placement();
+    win.reload();
+    watched
 }
 
-impl ProvidesRegistryState for App {
-    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
-    registry_handlers![OutputState, SeatState];
-}
-
-delegate_compositor!(App);
-delegate_output!(App);
-delegate_seat!(App);
-delegate_keyboard!(App);
-delegate_pointer!(App);
-delegate_shm!(App);
-delegate_layer!(App);
-delegate_registry!(App);
-
-// --- Main ---
-
 fn main() {
-    let cfg = load_config();
-    let colors = load_colors(cfg.color_file.as_deref());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = CLI.parse_or_exit(&args);
+    trace::start("evoke", &opts);
+    cli::config_commands::<Config>("evoke", &opts);
+
+    let mut cfg: Config = load_config("evoke");
+    let inv = parse_args(&opts, &mut cfg).unwrap_or_else(|e| CLI.fail(&e));
+    let colors: Colors = load_colors(cfg.color_file.as_deref());
 
     // Set up SIGUSR1 handler
     unsafe {
@@ -584,52 +532,15 @@ fn main() {
         libc::sigaction(libc::SIGUSR1, &sa, std::ptr::null_mut());
     }
 
-    // Start recording: pw-record to stdout with raw PCM
-    let recorder = Command::new("pw-record")
-        .args(["--format=s16", "--rate=16000", "--channels=1", "-"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("failed to start pw-record");
-
-    let conn = Connection::connect_to_env().unwrap();
-    let (globals, event_queue) = registry_queue_init::<App>(&conn).unwrap();
-    let qh = event_queue.handle();
-
-    let mut event_loop: EventLoop<App> = EventLoop::try_new().unwrap();
-    let loop_handle = event_loop.handle();
-    WaylandSource::new(conn.clone(), event_queue).insert(loop_handle.clone()).unwrap();
-
-    let compositor = CompositorState::bind(&globals, &qh).unwrap();
-    let layer_shell = LayerShell::bind(&globals, &qh).unwrap();
-    let shm = Shm::bind(&globals, &qh).unwrap();
-    let cursor_shape_manager = CursorShapeManager::bind(&globals, &qh).unwrap();
-
-    let surface = compositor.create_surface(&qh);
-    let layer = layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("evoke"), None);
-    layer.set_size(cfg.width, cfg.height);
-    layer.set_anchor(Anchor::BOTTOM);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
-    layer.wl_surface().commit();
-
-    let pool = SlotPool::new((cfg.width * cfg.height * 4) as usize, &shm).unwrap();
-
     let ring_buf = vec![0.0; cfg.bar_count];
+    let (width, height) = (cfg.width, cfg.height);
+    let output = cfg.output.clone();
+    let placement = cfg.placement.clone();
+    let style = cfg.style.clone();
     let mut app = App {
-        registry_state: RegistryState::new(&globals),
-        seat_state: SeatState::new(&globals, &qh),
-        output_state: OutputState::new(&globals, &qh),
-        shm,
-        layer,
-        pointer: None,
-        keyboard: None,
-        cursor_shape_manager,
-        loop_handle: loop_handle.clone(),
-        pool,
-        width: cfg.width,
-        height: cfg.height,
-        exit: false,
-        configured: false,
+        width,
+        height,
+        scale: 1.0,
         colors,
         phase: Phase::Recording,
         started_at: Instant::now(),
@@ -639,10 +550,57 @@ fn main() {
         recorder: None,
         pending_byte: None,
         transcription_rx: None,
-        margin_set: false,
+        clip: None,
+        paste_pending: false,
+        output_height: None,
         config: cfg,
     };
 
+    if let Some(path) = inv.render_to {
+        // Fake a few seconds of speech instead of recording
+        for (i, level) in app.ring_buf.iter_mut().enumerate() {
+            *level = 0.02 + 0.15 * (i as f32 * 0.45).sin().abs() * (i as f32 * 0.11).cos().abs();
+        }
+        if let Err(e) = render_to_png(&mut app, width, height, inv.scale, &style, Path::new(&path)) {
+            eprintln!("evoke: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let options = Options {
+        namespace: "evoke",
+        width,
+        height,
+        placement,
+        style,
+        keyboard: KeyboardInteractivity::Exclusive,
+        output,
+        resident: false,
+    };
+    let bus = if inv.dbus {
+        dbus::connect(Builder::session(), Control).inspect_err(|e| eprintln!("evoke: D-Bus: {e}")).ok()
+    } else {
+        None
+    };
+
+    if let Some(path) = &inv.replay {
+        // Nothing is recorded: stopping finds no audio and exits
+        if let Err(e) = replay::run(app, options, inv.scale, Path::new(path), bus) {
+            eprintln!("evoke: replay: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Start recording: pw-record to stdout with raw PCM
+    let recorder = Command::new("pw-record")
+        .args(["--format=s16", "--rate=16000", "--channels=1", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start pw-record");
+
     // Set up pw-record stdout as calloop source
     let mut recorder = recorder;
     let stdout = recorder.stdout.take().unwrap();
@@ -656,47 +614,37 @@ fn main() {
         libc::fcntl(stdout_fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
     }
 
+    let mut event_loop: EventLoop<Window<App>> = EventLoop::try_new().unwrap();
+    let loop_handle = event_loop.handle();
+    let mut win = Window::new(&event_loop, options, app);
+
     let generic_source = Generic::new(stdout, Interest::READ, Mode::Level);
-    loop_handle.insert_source(generic_source, |_, stdout_wrapper, app: &mut App| {
+    loop_handle.insert_source(generic_source, |_, stdout_wrapper, win: &mut Window<App>| {
         let fd = stdout_wrapper.as_ref().as_raw_fd();
         let mut buf = [0u8; 8192];
         loop {
             let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
             if n <= 0 { break; }
-            app.process_audio_chunk(&buf[..n as usize]);
+            win.widget.process_audio_chunk(&buf[..n as usize]);
         }
+        win.draw();
         Ok(PostAction::Continue)
     }).unwrap();
 
-    // Redraw timer (~30fps)
-    let timer = Timer::from_duration(std::time::Duration::from_millis(TICK_MS));
-    loop_handle.insert_source(timer, |_, _, app| {
-        // Check for SIGUSR1
-        if GOT_SIGNAL.load(Ordering::Acquire) && app.phase == Phase::Recording {
-            GOT_SIGNAL.store(false, Ordering::Release);
-            app.stop_and_transcribe();
-        }
-        // Check for transcription result
-        if let Some(rx) = &app.transcription_rx {
-            if let Ok(text) = rx.try_recv() {
-                let text = text.trim().to_string();
-                if !text.is_empty() {
-                    eprintln!("evoke: transcribed: {text}");
-                    output_text(&text);
-                } else {
-                    eprintln!("evoke: no speech detected");
-                }
-                app.exit = true;
-            }
-        }
-        if !app.exit {
-            app.draw();
-        }
-        TimeoutAction::ToDuration(std::time::Duration::from_millis(TICK_MS))
+    // Signal / result poll; drawing is paced by frame callbacks
+    let timer = Timer::from_duration(Duration::from_millis(POLL_MS));
+    loop_handle.insert_source(timer, |_, _, win| {
+        let update = win.widget.tick();
+        win.apply(update);
+        TimeoutAction::ToDuration(Duration::from_millis(POLL_MS))
     }).unwrap();
 
-    loop {
-        event_loop.dispatch(std::time::Duration::from_millis(TICK_MS), &mut app).unwrap();
-        if app.exit { break; }
+    if let Some(bus) = bus && let Err(e) = bus.attach(&loop_handle) {
+        eprintln!("evoke: D-Bus: {e}");
+    }
+    let watched = watch::config_files("evoke", win.widget.config.color_file.as_deref());
+    if let Err(e) = watch::watch(&loop_handle, watched, move |win| reload(win, &opts)) {
+        eprintln!("evoke: cannot watch config: {e}");
     }
+    win.run(&mut event_loop);
 }
@@ -0,0 +1,21 @@
+//! `org.widgets.Evoke` on a private bus, with evoke under `--replay`, where
+//! nothing is recorded.
+
+use widgets_core::replay::testing::{self, Bus};
+
+#[test]
+fn stop_ends_the_recording() {
+    let home = testing::scratch("evoke-dbus-stop");
+    let bus = Bus::start();
+    let script = r#"steps = [{ serve_until = "exit" }]"#;
+    let mut child = testing::serve(env!("CARGO_BIN_EXE_evoke"), &home, "", script, &[], &bus);
+    let evoke = bus.proxy("org.widgets.Evoke", &mut child);
+    assert!(evoke.get_property::<bool>("Recording").unwrap());
+    // With no audio it exits before it could answer
+    evoke.call_noreply("Stop", &()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&home).ok();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{stderr}");
+    assert_eq!(stderr, "evoke: no audio captured\n");
+}
@@ -1,47 +1,47 @@
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::io::BufRead;
+use std::io::{BufRead, Write};
+use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use cosmic_text::{Attrs, Buffer, FontSystem, Metrics, Shaping, SwashCache, SwashContent};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use cosmic_text::Weight;
 use serde::{Deserialize, Serialize};
 use smithay_client_toolkit as sctk;
-use sctk::compositor::{CompositorHandler, CompositorState};
-use sctk::output::{OutputHandler, OutputState};
-use sctk::reexports::calloop::{EventLoop, LoopHandle};
-use sctk::reexports::calloop_wayland_source::WaylandSource;
-use sctk::registry::{ProvidesRegistryState, RegistryState};
-use sctk::registry_handlers;
-use sctk::seat::keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers};
-use sctk::seat::pointer::cursor_shape::CursorShapeManager;
-use sctk::seat::pointer::{PointerEvent, PointerEventKind, PointerHandler};
-use sctk::seat::{Capability, SeatHandler, SeatState};
+use sctk::reexports::calloop::EventLoop;
 use sctk::reexports::protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
-use sctk::shell::wlr_layer::{
-    KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
-    LayerSurfaceConfigure,
-};
-use sctk::shell::WaylandSurface;
-use sctk::shm::slot::SlotPool;
-use sctk::shm::{Shm, ShmHandler};
-use sctk::{
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
-    delegate_registry, delegate_seat, delegate_shm,
-};
+use sctk::seat::keyboard::{KeyEvent, Modifiers};
+use sctk::seat::pointer::{PointerEvent, PointerEventKind};
+use sctk::shell::wlr_layer::KeyboardInteractivity;
 use tiny_skia::Pixmap;
-use wayland_client::globals::registry_queue_init;
-use wayland_client::protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface};
-use wayland_client::{Connection, QueueHandle};
+use widgets_core::anim::Tween;
+use widgets_core::color::{load_colors, Palette};
+use widgets_core::cli::{self, Opt, Parsed, Spec};
+use widgets_core::clipboard::Clip;
+use widgets_core::config::{cache_dir, home, load_config, state_dir, Documented};
+use widgets_core::dbus::{self, Remote};
+use widgets_core::edit::{Change, LineEdit};
+use widgets_core::headless::render_to_png;
+use widgets_core::ipc;
+use widgets_core::keys::{self, Action, Keys, Spawn};
+use widgets_core::replay;
+use widgets_core::watch;
+use widgets_core::render::{blit_rgba, clear_rect, faded, fill_rect, fill_rect_alpha, Rect};
+use widgets_core::style::{fill_rounded, Backdrop, Style, STYLE_DOC};
+use widgets_core::text::{font_db, load_font, Fonts, LINE_HEIGHT};
+use widgets_core::trace;
+use widgets_core::window::{Host, Options, Placement, TextInput, Update, Widget, Window, PLACEMENT_KEYS};
+use zbus::blocking::connection::Builder;
+use zbus::{fdo, interface};
 
 // --- Config ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 #[serde(default)]
 struct Config {
     color_file: Option<String>,
     font: String,
+    font_fallback: Vec<String>,
     font_size: f32,
     comment_font_size: f32,
     icon_size: u32,
@@ -52,126 +52,101 @@ struct Config {
     show_comments: bool,
     search_comments: bool,
     center_items: bool,
+    output: Option<String>,
+    keys: Keys,
+    style: Style,
+    #[serde(flatten)]
+    placement: Placement,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            color_file: None, font: "~/.local/share/fonts/GoogleSansCode-Regular.ttf".into(),
+            color_file: None, font: "~/.local/share/fonts/GoogleSansCode-Regular.ttf".into(), font_fallback: Vec::new(),
             font_size: 18.0, comment_font_size: 14.0, icon_size: 32,
             window_width: 600, window_height: 400,
             terminal: "ghostty -e".into(),
             columns: 1, show_comments: true, search_comments: false, center_items: false,
+            output: None, keys: Keys::default(), style: Style::default(), placement: Placement::default(),
         }
     }
 }
 
-fn load_config() -> Config {
-    let base = std::env::var("XDG_CONFIG_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from(std::env::var("HOME").unwrap()).join(".config"));
-    let path = base.join("widgets/grimoire.toml");
-    let content = match std::fs::read_to_string(&path) {
-        Ok(s) => s,
-        Err(_) => return Config::default(),
-    };
-    match toml::from_str(&content) {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            eprintln!("grimoire: failed to parse {}: {e}", path.display());
-            Config::default()
-        }
+impl Documented for Config {
+    type Colors = Colors;
+    const FLATTENED: &'static [(&'static str, &'static str)] = PLACEMENT_KEYS;
+    const KEYS: &'static [(&'static str, &'static str)] = &[
+        ("color_file", "walrs color file; unset uses the built-in palette"),
+        ("font", "Font file or family name for all text"),
+        ("font_fallback", "Fonts (files or family names) for characters `font` lacks, before the system's fallbacks"),
+        ("font_size", "Entry name size in logical pixels"),
+        ("comment_font_size", "Entry comment size in logical pixels"),
+        ("icon_size", "Icon size in logical pixels; also sets the row height"),
+        ("window_width", "Panel width in logical pixels"),
+        ("window_height", "Panel height in logical pixels"),
+        ("terminal", "Command prefix for Terminal=true entries"),
+        ("columns", "Grid columns"),
+        ("show_comments", "Show each entry's Comment= line"),
+        ("search_comments", "Match the search against comments as well as names"),
+        ("center_items", "Center entries within their cells"),
+        ("output", "Monitor: connector name, \"focused\" or \"cursor\" (both need Hyprland); unset lets the compositor choose"),
+        ("keys", "Key bindings, combo = action, layered over these defaults; \"none\" unbinds"),
+        ("style", STYLE_DOC),
+    ];
+    fn color_file(&self) -> Option<&str> { self.color_file.as_deref() }
+    fn fonts(&self) -> Vec<(&'static str, String)> {
+        let fallback = self.font_fallback.iter().map(|f| ("font_fallback", f.clone()));
+        std::iter::once(("font", self.font.clone())).chain(fallback).collect()
     }
 }
 
 // --- Colors ---
 
 struct Colors {
-    background: [u8; 3],
-    background_alpha: u8,
-    border: [u8; 3],
-    bar_bg: [u8; 3],
-    bar_border: [u8; 3],
-    text: [u8; 3],
-    text_comment: [u8; 3],
-    text_placeholder: [u8; 3],
-    selection: [u8; 3],
-    selection_alpha: u8,
+    background: [u8; 4],
+    border: [u8; 4],
+    bar_bg: [u8; 4],
+    bar_border: [u8; 4],
+    text: [u8; 4],
+    text_comment: [u8; 4],
+    text_placeholder: [u8; 4],
+    selection: [u8; 4],
 }
 
 impl Default for Colors {
     fn default() -> Self {
         Self {
-            background: [0x1a, 0x1a, 0x2e], background_alpha: 0xff,
-            border: [0x4a, 0x4a, 0x6e],
-            bar_bg: [0x2a, 0x2a, 0x4e], bar_border: [0x4a, 0x4a, 0x6e],
-            text: [0xe0, 0xe0, 0xe0], text_comment: [0x80, 0x80, 0x90],
-            text_placeholder: [0x60, 0x60, 0x70],
-            selection: [0x40, 0x40, 0x90], selection_alpha: 0xcc,
+            background: [0x1a, 0x1a, 0x2e, 0xff],
+            border: [0x4a, 0x4a, 0x6e, 0xff],
+            bar_bg: [0x2a, 0x2a, 0x4e, 0xff], bar_border: [0x4a, 0x4a, 0x6e, 0xff],
+            text: [0xe0, 0xe0, 0xe0, 0xff], text_comment: [0x80, 0x80, 0x90, 0xff],
+            text_placeholder: [0x60, 0x60, 0x70, 0xff],
+            selection: [0x40, 0x40, 0x90, 0xcc],
         }
     }
 }
 
-fn parse_hex(s: &str) -> Option<[u8; 3]> {
-    let s = s.strip_prefix('#').unwrap_or(s);
-    if s.len() != 6 { return None; }
-    Some([u8::from_str_radix(&s[0..2], 16).ok()?,
-          u8::from_str_radix(&s[2..4], 16).ok()?,
-          u8::from_str_radix(&s[4..6], 16).ok()?])
-}
-
-fn expand_path(p: &str) -> PathBuf {
-    if let Some(rest) = p.strip_prefix("~/") {
-        PathBuf::from(std::env::var("HOME").unwrap()).join(rest)
-    } else { PathBuf::from(p) }
-}
-
-fn load_colors(path: Option<&str>) -> Colors {
-    let mut colors = Colors::default();
-    let content = match path {
-        Some(p) => std::fs::read_to_string(expand_path(p)).unwrap_or_default(),
-        None => return colors,
-    };
-    for line in content.lines() {
-        if let Some((key, val)) = line.split_once('=') {
-            let (key, val) = (key.trim(), val.trim());
-            match key {
-                "background_opacity" | "selection_opacity" => {
-                    if let Ok(f) = val.parse::<f32>() {
-                        let a = (f.clamp(0.0, 1.0) * 255.0) as u8;
-                        match key {
-                            "background_opacity" => colors.background_alpha = a,
-                            _ => colors.selection_alpha = a,
-                        }
-                    }
-                }
-                _ => {
-                    if let Some(c) = parse_hex(val) {
-                        match key {
-                            "background" => colors.background = c,
-                            "border" => colors.border = c,
-                            "bar_bg" => colors.bar_bg = c,
-                            "bar_border" => colors.bar_border = c,
-                            "text" => colors.text = c,
-                            "text_comment" => colors.text_comment = c,
-                            "text_placeholder" => colors.text_placeholder = c,
-                            "selection" => colors.selection = c,
-                            _ => {}
-                        }
-                    }
-                }
-            }
-        }
+impl Palette for Colors {
+    fn color(&mut self, key: &str) -> Option<&mut [u8; 4]> {
+        Some(match key {
+            "background" => &mut self.background,
+            "border" => &mut self.border,
+            "bar_bg" => &mut self.bar_bg,
+            "bar_border" => &mut self.bar_border,
+            "text" => &mut self.text,
+            "text_comment" => &mut self.text_comment,
+            "text_placeholder" => &mut self.text_placeholder,
+            "selection" => &mut self.selection,
+            _ => return None,
+        })
     }
-    colors
 }
 
 // --- Desktop entry parsing ---
 
 fn desktop_dirs() -> Vec<PathBuf> {
-    let home = std::env::var("HOME").unwrap();
     vec![
-        PathBuf::from(&home).join(".local/share/applications"),
+        home().join(".local/share/applications"),
         PathBuf::from("/usr/local/share/applications"),
         PathBuf::from("/usr/share/applications"),
     ]
@@ -181,13 +156,9 @@ fn strip_field_codes(exec: &str) -> String {
     let mut result = String::with_capacity(exec.len());
     let mut chars = exec.chars().peekable();
     while let Some(c) = chars.next() {
-        if c == '%' {
-            if let Some(&next) = chars.peek() {
-                if "fFuUdDnNickvm".contains(next) {
-                    chars.next();
-                    continue;
-                }
-            }
+        if c == '%' && let Some(&next) = chars.peek() && "fFuUdDnNickvm".contains(next) {
+            chars.next();
+            continue;
         }
         result.push(c);
     }
@@ -224,7 +195,7 @@ fn parse_desktop_file(path: &Path) -> Option<(String, String, String, String, bo
                 "Terminal" => terminal = val.eq_ignore_ascii_case("true"),
                 "NoDisplay" => no_display = val.eq_ignore_ascii_case("true"),
                 "Hidden" => hidden = val.eq_ignore_ascii_case("true"),
-                "Type" => { if val != "Application" { return None; } }
+                "Type" if val != "Application" => return None,
                 _ => {}
             }
         }
@@ -236,10 +207,7 @@ fn parse_desktop_file(path: &Path) -> Option<(String, String, String, String, bo
 // --- Icon resolution ---
 
 fn icon_cache_dir() -> PathBuf {
-    let base = std::env::var("XDG_CACHE_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from(std::env::var("HOME").unwrap()).join(".cache"));
-    base.join("thumbnails/grimoire")
+    cache_dir().join("thumbnails/grimoire")
 }
 
 fn find_icon_path(name: &str) -> Option<PathBuf> {
@@ -248,9 +216,9 @@ fn find_icon_path(name: &str) -> Option<PathBuf> {
         if p.exists() { return Some(p); }
         return None;
     }
-    let home = std::env::var("HOME").unwrap();
+    let home = home();
     let bases = [
-        format!("{home}/.local/share/icons/hicolor"),
+        format!("{}/.local/share/icons/hicolor", home.display()),
         "/usr/share/icons/hicolor".into(),
     ];
     let sizes = ["48x48", "64x64", "32x32", "128x128", "256x256", "512x512"];
@@ -308,12 +276,10 @@ fn resolve_icon(name: &str, size: u32) -> Option<(Vec<u8>, u32, u32)> {
     let key = icon_cache_key(name, size);
     let cached = cd.join(format!("{key}.png"));
 
-    if cached.exists() {
-        if let Ok(img) = image::open(&cached) {
-            let rgba = img.to_rgba8();
-            let (w, h) = rgba.dimensions();
-            return Some((rgba.into_raw(), w, h));
-        }
+    if cached.exists() && let Ok(img) = image::open(&cached) {
+        let rgba = img.to_rgba8();
+        let (w, h) = rgba.dimensions();
+        return Some((rgba.into_raw(), w, h));
     }
 
     let path = find_icon_path(name)?;
@@ -334,6 +300,7 @@ struct Item {
     name: String,
     exec: String,
     comment: String,
+    icon: String,
     icon_data: Option<Vec<u8>>,
     icon_w: u32,
     icon_h: u32,
@@ -350,10 +317,7 @@ struct FrecencyEntry {
 }
 
 fn frecency_state_path() -> PathBuf {
-    let base = std::env::var("XDG_STATE_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from(std::env::var("HOME").unwrap()).join(".local/state"));
-    base.join("widgets/grimoire.toml")
+    state_dir().join("grimoire.toml")
 }
 
 fn load_frecency() -> HashMap<String, FrecencyEntry> {
@@ -399,12 +363,12 @@ fn load_desktop_entries(icon_size: u32, frecency: &HashMap<String, FrecencyEntry
             let filename = path.file_name().unwrap().to_string_lossy().to_string();
             let desktop_id = path.file_stem().unwrap().to_string_lossy().to_string();
 
-            if let Some((name, exec, comment, icon_name, terminal)) = parse_desktop_file(&path) {
-                let (icon_data, icon_w, icon_h) = match resolve_icon(&icon_name, icon_size) {
+            if let Some((name, exec, comment, icon_name, terminal)) = trace::span("desktop_entries", || parse_desktop_file(&path)) {
+                let (icon_data, icon_w, icon_h) = match trace::span("icons", || resolve_icon(&icon_name, icon_size)) {
                     Some((d, w, h)) => (Some(d), w, h),
                     None => (None, 0, 0),
                 };
-                let item = Item { name, exec, comment, icon_data, icon_w, icon_h, terminal, desktop_id };
+                let item = Item { name, exec, comment, icon: icon_name, icon_data, icon_w, icon_h, terminal, desktop_id };
 
                 if let Some(&idx) = seen.get(&filename) {
                     items[idx] = item; // local overrides system
@@ -415,6 +379,11 @@ fn load_desktop_entries(icon_size: u32, frecency: &HashMap<String, FrecencyEntry
             }
         }
     }
+    sort_by_frecency(&mut items, frecency);
+    items
+}
+
+fn sort_by_frecency(items: &mut [Item], frecency: &HashMap<String, FrecencyEntry>) {
     let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
     items.sort_by(|a, b| {
         let sa = frecency.get(&a.desktop_id).map_or(0.0, |e| frecency_score(e, now));
@@ -422,53 +391,47 @@ fn load_desktop_entries(icon_size: u32, frecency: &HashMap<String, FrecencyEntry
         sb.partial_cmp(&sa).unwrap_or(std::cmp::Ordering::Equal)
             .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
     });
-    items
 }
 
 fn load_stdin_items(strip_prefix: Option<&str>) -> Vec<Item> {
     let stdin = std::io::stdin();
-    stdin.lock().lines().flatten().map(|line| {
-        let name = match strip_prefix {
-            Some(pfx) => line.strip_prefix(pfx).unwrap_or(&line).trim_start_matches('/').to_string(),
-            None => line.clone(),
-        };
-        Item {
-            name, exec: line, comment: String::new(),
-            icon_data: None, icon_w: 0, icon_h: 0, terminal: false, desktop_id: String::new(),
-        }
-    }).collect()
+    stdin.lock().lines().map_while(Result::ok).map(|line| dmenu_item(line, strip_prefix)).collect()
+}
+
+fn dmenu_item(line: String, strip_prefix: Option<&str>) -> Item {
+    let name = match strip_prefix {
+        Some(pfx) => line.strip_prefix(pfx).unwrap_or(&line).trim_start_matches('/').to_string(),
+        None => line.clone(),
+    };
+    Item {
+        name, exec: line, comment: String::new(), icon: String::new(),
+        icon_data: None, icon_w: 0, icon_h: 0, terminal: false, desktop_id: String::new(),
+    }
 }
 
 // --- Mode ---
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 enum Mode { Drun, Dmenu }
 
 // --- App ---
 
 struct App {
-    registry_state: RegistryState,
-    seat_state: SeatState,
-    output_state: OutputState,
-    shm: Shm,
-    layer: LayerSurface,
-    keyboard: Option<wl_keyboard::WlKeyboard>,
-    pointer: Option<wl_pointer::WlPointer>,
-    cursor_shape_manager: CursorShapeManager,
-    pool: SlotPool,
     width: u32,
     height: u32,
-    exit: bool,
-    font_system: FontSystem,
-    swash_cache: SwashCache,
-    loop_handle: LoopHandle<'static, App>,
+    scale: f32,
+    fonts: Fonts,
     mode: Mode,
     items: Vec<Item>,
     filtered: Vec<usize>,
     selected: usize,
     scroll_offset: usize,
-    input: String,
+    input: LineEdit,
+    /// Where the search caret was last drawn, for the input method.
+    caret: Rect,
     colors: Colors,
+    /// Corner radius of the highlights, in logical pixels.
+    selection_radius: f32,
     font_size: f32,
     comment_font_size: f32,
     icon_size: u32,
@@ -476,23 +439,106 @@ struct App {
     font_family: String,
     hover_index: Option<usize>,
     hover_alpha: f32,
+    hover_fade: Tween,
     fade_out_index: Option<usize>,
     fade_out_alpha: f32,
+    fade_out: Tween,
     cols: usize,
     show_comments: bool,
     search_comments: bool,
     center_items: bool,
     frecency: HashMap<String, FrecencyEntry>,
-    modifiers: Modifiers,
+    /// Where frecency is saved; nowhere under `--replay`.
+    save_frecency: fn(&HashMap<String, FrecencyEntry>),
+    /// Starts launched entries and `run:` commands.
+    spawn: Spawn,
+    keys: Keys,
+    /// Marked items (indices into `items`), in the order they were marked.
+    marked: Vec<usize>,
+    shown: Option<Shown>,
+    /// Desktop entries kept warm while a daemon shows dmenu items.
+    drun_items: Vec<Item>,
+    /// `msg` client to answer instead of stdout (daemon mode).
+    client: Option<UnixStream>,
+    /// A `copy` for the window to take.
+    clip: Option<Clip>,
+    /// Config as read from disk, and the arguments layered over it for the
+    /// current run or `msg show`; kept to re-apply on reload.
+    config: Config,
+    opts: Parsed,
+}
+
+/// What the last frame showed, to work out which cells need repainting.
+struct Shown {
+    input: LineEdit,
+    start: usize,
+    items: Vec<usize>,
+    highlight: Vec<u8>,
+    marked: Vec<usize>,
 }
 
 const BAR_H: f32 = 50.0;
 const PAD: f32 = 8.0;
 const ROW_PAD: f32 = 8.0;
+const HOVER_FADE: Duration = Duration::from_millis(110);
 
 impl App {
-    fn row_height(&self) -> f32 { self.icon_size as f32 + ROW_PAD }
-    fn visible_rows(&self) -> usize { ((self.height as f32 - BAR_H) / self.row_height()).max(0.0) as usize }
+    /// Print a result: to the `msg` client in daemon mode, stdout otherwise.
+    fn emit(&mut self, line: &str) {
+        match &mut self.client {
+            Some(stream) => { writeln!(stream, "{line}").ok(); }
+            None => println!("{line}"),
+        }
+    }
+
+    /// Apply one invocation's options and items before showing.
+    fn open(&mut self, cfg: &Config, inv: &Invocation, stdin: Option<&str>) {
+        if self.mode == Mode::Drun && !self.items.is_empty() { self.drun_items = std::mem::take(&mut self.items); }
+        self.mode = inv.mode;
+        self.items = match (inv.mode, stdin) {
+            (Mode::Drun, _) => {
+                let mut items = std::mem::take(&mut self.drun_items);
+                sort_by_frecency(&mut items, &self.frecency);
+                items
+            }
+            (Mode::Dmenu, Some(text)) => text.lines().map(|l| dmenu_item(l.to_string(), inv.relative_paths.as_deref())).collect(),
+            (Mode::Dmenu, None) => load_stdin_items(inv.relative_paths.as_deref()),
+        };
+        self.cols = cfg.columns.max(1);
+        self.center_items = cfg.center_items;
+        self.input.set(inv.input.clone().unwrap_or_default());
+        self.marked.clear();
+        self.hover_index = None;
+        self.fade_out_index = None;
+        self.refilter();
+    }
+
+    /// Re-apply colors, fonts and layout after the config or color file changed.
+    fn apply_config(&mut self, cfg: &Config, colors: Colors) {
+        self.colors = colors;
+        let mut db = font_db();
+        match load_font(&mut db, &cfg.font) {
+            Some(family) => { self.fonts = Fonts::new(db, &cfg.font_fallback); self.font_family = family; }
+            None => eprintln!("grimoire: failed to load font {}", cfg.font),
+        }
+        self.font_size = cfg.font_size;
+        self.comment_font_size = cfg.comment_font_size;
+        self.terminal_cmd = cfg.terminal.clone();
+        self.cols = cfg.columns.max(1);
+        self.show_comments = cfg.show_comments;
+        self.search_comments = cfg.search_comments;
+        self.center_items = cfg.center_items;
+        self.keys = cfg.keys.clone();
+        self.selection_radius = cfg.style.selection_radius;
+        if cfg.icon_size != self.icon_size {
+            self.icon_size = cfg.icon_size;
+            self.reload_icons();
+        }
+        self.ensure_visible();
+    }
+
+    fn row_height(&self) -> f32 { (self.icon_size as f32 + ROW_PAD) * self.scale }
+    fn visible_rows(&self) -> usize { ((self.height as f32 - BAR_H * self.scale) / self.row_height()).max(0.0) as usize }
 
     fn effective_cols(&self) -> usize {
         let n = self.filtered.len();
@@ -509,14 +555,15 @@ impl App {
     }
 
     fn item_at_pos(&self, x: f32, y: f32) -> Option<usize> {
-        if y < BAR_H { return None; }
+        let bar_h = BAR_H * self.scale;
+        if y < bar_h { return None; }
         let ecols = self.effective_cols();
         let col_w = self.col_width();
         let x_off = self.grid_x_offset();
         if x < x_off { return None; }
         let col = ((x - x_off) / col_w) as usize;
         if col >= ecols { return None; }
-        let row = ((y - BAR_H) / self.row_height()) as usize;
+        let row = ((y - bar_h) / self.row_height()) as usize;
         let idx = self.scroll_offset + row * ecols + col;
         if idx < self.filtered.len() { Some(idx) } else { None }
     }
@@ -539,8 +586,8 @@ impl App {
         } else {
             let mut scored: Vec<(usize, u32)> = (0..self.items.len())
                 .filter_map(|i| {
-                    let name_score = fuzzy_score(&self.items[i].name, &self.input);
-                    let comment_score = if self.search_comments { fuzzy_score(&self.items[i].comment, &self.input) } else { None };
+                    let name_score = fuzzy_score(&self.items[i].name, self.input.text());
+                    let comment_score = if self.search_comments { fuzzy_score(&self.items[i].comment, self.input.text()) } else { None };
                     match (name_score, comment_score) {
                         (Some(a), Some(b)) => Some((i, a.min(b))),
                         (Some(a), None) => Some((i, a)),
@@ -556,223 +603,407 @@ impl App {
         self.scroll_offset = 0;
     }
 
-    fn select_item(&mut self) {
-        if self.filtered.is_empty() { return; }
-        let item = &self.items[self.filtered[self.selected]];
+    /// Items to act on: the marked ones, or else the selected one.
+    fn chosen(&self) -> Vec<usize> {
+        if !self.marked.is_empty() { return self.marked.clone(); }
+        self.filtered.get(self.selected).copied().into_iter().collect()
+    }
+
+    fn select_item(&mut self) -> Update {
+        let chosen = self.chosen();
+        if chosen.is_empty() { return Update::None; }
 
         if self.mode == Mode::Dmenu {
-            println!("{}", item.exec);
-            self.exit = true;
-            return;
+            for i in chosen {
+                let line = self.items[i].exec.clone();
+                self.emit(&line);
+            }
+            return Update::Exit;
         }
 
         // Update frecency
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        let entry = self.frecency.entry(item.desktop_id.clone()).or_default();
-        entry.count += 1;
-        entry.last = now;
-        save_frecency(&self.frecency);
+        for &i in &chosen {
+            let entry = self.frecency.entry(self.items[i].desktop_id.clone()).or_default();
+            entry.count += 1;
+            entry.last = now;
+        }
+        (self.save_frecency)(&self.frecency);
 
         // drun: fork+exec
-        let exec_cmd = if item.terminal {
-            format!("{} {}", self.terminal_cmd, item.exec)
-        } else {
-            item.exec.clone()
-        };
-        Command::new("sh")
-            .arg("-c")
-            .arg(&exec_cmd)
-            .stdin(Stdio::null())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .ok();
-        self.exit = true;
+        for i in chosen {
+            let item = &self.items[i];
+            let exec_cmd = if item.terminal {
+                format!("{} {}", self.terminal_cmd, item.exec)
+            } else {
+                item.exec.clone()
+            };
+            (self.spawn)(&exec_cmd, &[]);
+        }
+        Update::Exit
     }
 
-    fn handle_key(&mut self, event: &KeyEvent) {
-        if event.keysym == Keysym::Escape {
-            self.exit = true;
-            return;
-        }
-        if event.keysym == Keysym::Return {
-            self.select_item();
-            return;
-        }
+    /// What `run:` and `copy` bindings act on: the chosen dmenu lines, or
+    /// desktop entry IDs in drun mode.
+    fn chosen_values(&self) -> Vec<String> {
+        self.chosen().into_iter().map(|i| match self.mode {
+            Mode::Dmenu => self.items[i].exec.clone(),
+            Mode::Drun => self.items[i].desktop_id.clone(),
+        }).collect()
+    }
+
+    fn run(&mut self, cmd: &str) -> Update {
+        let args = self.chosen_values();
+        if args.is_empty() { return Update::None; }
+        (self.spawn)(cmd, &args);
+        Update::Exit
+    }
+
+    /// Copy the chosen values, one per line, instead of printing them.
+    fn copy(&mut self) -> Update {
+        let values = self.chosen_values();
+        if values.is_empty() { return Update::None; }
+        self.clip = Some(Clip::text(&values.join("\n")));
+        Update::Exit
+    }
+
+    fn handle_key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> Update {
         let n = self.filtered.len();
         let ecols = self.effective_cols();
-        let changed = match event.keysym {
-            Keysym::BackSpace => {
-                if self.modifiers.ctrl {
-                    if !self.input.is_empty() { self.input.clear(); self.refilter(); true } else { false }
-                } else {
-                    if self.input.pop().is_some() { self.refilter(); true } else { false }
+        let page = (self.visible_rows() * ecols).max(1);
+        let last = n.saturating_sub(1);
+        let changed = match self.keys.action(event, modifiers).cloned() {
+            Some(Action::Cancel) => return Update::Exit,
+            Some(Action::Select) => return self.select_item(),
+            Some(Action::Run(cmd)) => return self.run(&cmd),
+            Some(Action::Copy) => return self.copy(),
+            Some(Action::Paste) => return Update::Paste,
+            // A single-column list has no use for Left/Right, so they edit
+            Some(Action::Left) if self.cols == 1 => self.edit(&Action::CaretLeft, modifiers.shift),
+            Some(Action::Right) if self.cols == 1 => self.edit(&Action::CaretRight, modifiers.shift),
+            Some(Action::Left | Action::Prev) if self.selected > 0 => { self.selected -= 1; true }
+            Some(Action::Right | Action::Next) if self.selected + 1 < n => { self.selected += 1; true }
+            Some(Action::Up) if self.selected >= ecols => { self.selected -= ecols; true }
+            Some(Action::Down) if self.selected + ecols < n => { self.selected += ecols; true }
+            Some(Action::PageUp) if self.selected > 0 => { self.selected = self.selected.saturating_sub(page); true }
+            Some(Action::PageDown) if self.selected < last => { self.selected = (self.selected + page).min(last); true }
+            Some(Action::First) if self.selected > 0 => { self.selected = 0; true }
+            Some(Action::Last) if self.selected < last => { self.selected = last; true }
+            Some(Action::ToggleMark) if n > 0 => {
+                let item = self.filtered[self.selected];
+                match self.marked.iter().position(|&m| m == item) {
+                    Some(pos) => { self.marked.remove(pos); }
+                    None => self.marked.push(item),
                 }
+                if self.selected < last { self.selected += 1; }
+                true
             }
-            Keysym::Left if self.selected > 0 => { self.selected -= 1; true }
-            Keysym::Right if self.selected + 1 < n => { self.selected += 1; true }
-            Keysym::Up if self.selected >= ecols => { self.selected -= ecols; true }
-            Keysym::Down if self.selected + ecols < n => { self.selected += ecols; true }
-            _ => match event.utf8 {
-                Some(ref text) if !text.is_empty() && text.chars().all(|c| !c.is_control()) => {
-                    self.input.push_str(text);
-                    self.refilter();
-                    true
-                }
-                _ => false,
+            Some(action) => self.edit(&action, modifiers.shift),
+            None => match keys::typed(event) {
+                Some(text) => { self.input.insert(text); self.refilter(); true }
+                None => false,
             },
         };
-        if changed {
-            self.ensure_visible();
-            self.draw();
+        if !changed { return Update::None; }
+        self.ensure_visible();
+        Update::Redraw
+    }
+
+    /// Apply a search-bar action; returns whether anything changed.
+    fn edit(&mut self, action: &Action, extend: bool) -> bool {
+        match self.input.apply(action, extend) {
+            Change::None => false,
+            Change::Caret => true,
+            Change::Text => { self.refilter(); true }
         }
     }
 
-    fn draw(&mut self) {
-        let bg = self.colors.background;
-        let bg_alpha = self.colors.background_alpha;
-        let bar_bg = self.colors.bar_bg;
-        let border = self.colors.border;
+    /// Input method text goes into the search like typed text.
+    fn text_input(&mut self, event: &TextInput) -> Update {
+        match self.input.input_method(event) {
+            Change::None => return Update::None,
+            Change::Caret => {}
+            Change::Text => self.refilter(),
+        }
+        Update::Redraw
+    }
+
+    /// Pasted text goes into the search, up to the first line break.
+    fn paste(&mut self, text: &str) -> Update {
+        if self.input.insert(text.lines().next().unwrap_or_default()) == Change::None { return Update::None; }
+        self.refilter();
+        Update::Redraw
+    }
+
+    /// Re-rasterize icons at the current device scale.
+    fn reload_icons(&mut self) {
+        let size = (self.icon_size as f32 * self.scale).round() as u32;
+        let shown: &mut [Item] = if self.mode == Mode::Drun { &mut self.items } else { &mut [] };
+        for item in shown.iter_mut().chain(&mut self.drun_items) {
+            (item.icon_data, item.icon_w, item.icon_h) = match resolve_icon(&item.icon, size) {
+                Some((d, w, h)) => (Some(d), w, h),
+                None => (None, 0, 0),
+            };
+        }
+    }
+
+    fn backdrop(&self) -> Backdrop {
+        let c = &self.colors;
+        Backdrop { background: c.background, border: c.border }
+    }
+
+    /// Cell `vi` of the visible grid, clipped to the window.
+    fn cell_rect(&self, vi: usize) -> Rect {
+        let s = self.scale;
+        let ecols = self.effective_cols();
+        let (col_w, row_h) = (self.col_width(), self.row_height());
+        let x = (self.grid_x_offset() + (vi % ecols) as f32 * col_w) as u32;
+        let y = (BAR_H * s + (vi / ecols) as f32 * row_h) as u32;
+        let (x0, y0) = (x, y);
+        let x1 = (x + col_w as u32).min(self.width);
+        let y1 = (y + row_h as u32).min(self.height);
+        Rect { x: x0, y: y0, w: x1.saturating_sub(x0), h: y1.saturating_sub(y0) }
+    }
+
+    /// Selection/hover highlight opacity of filtered item `i`.
+    fn highlight(&self, i: usize) -> u8 {
+        let sel_alpha = self.colors.selection[3];
+        if i == self.selected {
+            sel_alpha
+        } else if self.hover_index == Some(i) {
+            (sel_alpha as f32 / 2.0 * self.hover_alpha) as u8
+        } else if self.fade_out_index == Some(i) {
+            (sel_alpha as f32 / 2.0 * self.fade_out_alpha) as u8
+        } else {
+            0
+        }
+    }
+
+    fn visible_range(&self) -> std::ops::Range<usize> {
+        let start = self.scroll_offset;
+        start..(start + self.visible_rows() * self.effective_cols()).min(self.filtered.len())
+    }
+
+    fn snapshot(&self) -> Shown {
+        let range = self.visible_range();
+        Shown {
+            input: self.input.clone(),
+            items: self.filtered[range.clone()].to_vec(),
+            start: range.start,
+            highlight: range.map(|i| self.highlight(i)).collect(),
+            marked: self.marked.clone(),
+        }
+    }
+
+    fn draw(&mut self, pixmap: &mut Pixmap) {
+        let s = self.scale;
+        let bar_h = BAR_H * s;
+        let bar_bg = faded(self.colors.bar_bg, self.colors.background[3]);
         let text_color = self.colors.text;
-        let comment_color = self.colors.text_comment;
-        let sel_color = self.colors.selection;
-        let sel_alpha = self.colors.selection_alpha;
+        let font_size = self.font_size * s;
+        let width = self.width;
+
+        let pw = pixmap.width();
+        let ph = pixmap.height();
+        // The panel shows through
+        clear_rect(pixmap.data_mut(), pw, ph, Rect { x: 0, y: 0, w: pw, h: ph }, [0; 3], 0);
+
+        // Search bar background
+        fill_rect(pixmap.data_mut(), pw, ph, 0, 0, width, bar_h as u32, bar_bg);
+
+        // Search text
+        let ty = (bar_h - font_size * LINE_HEIGHT) / 2.0;
+        self.caret = self.input.draw(pixmap, &mut self.fonts, width as f32 / 2.0, ty, font_size, &self.font_family,
+            text_color, self.colors.selection, s);
+
+        // Grid items
+        for i in self.visible_range() {
+            self.draw_cell(pixmap, i);
+        }
+        self.shown = Some(self.snapshot());
+    }
+
+    /// Repaint just the cells whose highlight changed (hover fades, selection
+    /// moves); anything else changes most of the window and gets a full draw.
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Option<Vec<Rect>> {
+        let now = self.snapshot();
+        let shown = self.shown.take()?;
+        if now.input != shown.input || now.start != shown.start || now.items != shown.items
+            || now.marked != shown.marked { return None; }
+        let (pw, ph) = (pixmap.width(), pixmap.height());
+        let mut damage = Vec::new();
+        for (vi, (a, b)) in now.highlight.iter().zip(&shown.highlight).enumerate() {
+            if a == b { continue; }
+            let rect = self.cell_rect(vi);
+            clear_rect(pixmap.data_mut(), pw, ph, rect, [0; 3], 0);
+            self.draw_cell(pixmap, now.start + vi);
+            damage.push(rect);
+        }
+        self.shown = Some(now);
+        Some(damage)
+    }
+
+    /// Highlight, icon, name and comment of filtered item `i` (must be visible).
+    fn draw_cell(&mut self, pixmap: &mut Pixmap, i: usize) {
+        let s = self.scale;
+        let bar_h = BAR_H * s;
+        let pad = PAD * s;
         let row_h = self.row_height();
         let ecols = self.effective_cols();
         let col_w = self.col_width();
         let x_off = self.grid_x_offset();
-        let visible = self.visible_rows() * ecols;
-        let icon_sz = self.icon_size;
+        let icon_sz = self.icon_size as f32 * s;
         let has_icons = self.mode == Mode::Drun;
-        let icon_pad = if has_icons { PAD + icon_sz as f32 + PAD } else { PAD };
-        let font_size = self.font_size;
-        let comment_font_size = self.comment_font_size;
-        let show_comments = self.show_comments;
-        let width = self.width;
-        let height = self.height;
-        let start = self.scroll_offset;
-        let end = (start + visible).min(self.filtered.len());
-        let selected = self.selected;
-        let hover = self.hover_index;
-        let hover_alpha = self.hover_alpha;
-        let fade_out = self.fade_out_index;
-        let fade_out_alpha = self.fade_out_alpha;
-        let filtered: Vec<usize> = self.filtered[start..end].to_vec();
-
-        let stride = width as i32 * 4;
-        let (wl_buf, canvas) = self.pool
-            .create_buffer(width as i32, height as i32, stride, wl_shm::Format::Argb8888)
-            .unwrap();
-
-        let mut pixmap = Pixmap::new(width, height).unwrap();
-        pixmap.fill(tiny_skia::Color::from_rgba8(bg[0], bg[1], bg[2], bg_alpha));
-
+        let icon_pad = if has_icons { pad + icon_sz + pad } else { pad };
+        let font_size = self.font_size * s;
+        let comment_font_size = self.comment_font_size * s;
+        let text_color = self.colors.text;
+        let comment_color = self.colors.text_comment;
         let pw = pixmap.width();
         let ph = pixmap.height();
 
-        // Search bar background
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, 0, 0, width, BAR_H as u32, bar_bg, bg_alpha);
+        let vi = i - self.scroll_offset;
+        let item_idx = self.filtered[i];
+        let col = vi % ecols;
+        let row = vi / ecols;
+        let cell_x = x_off + col as f32 * col_w;
+        let cell_y = bar_h + row as f32 * row_h;
+        let text_x = cell_x + icon_pad;
+
+        // Selection / hover highlight
+        let a = self.highlight(i);
+        if a > 0 {
+            let [r, g, b, _] = self.colors.selection;
+            fill_rounded(pixmap, self.cell_rect(vi), self.selection_radius * s, [r, g, b], a);
+        }
 
-        // Window border
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, 0, 0, width, 2, border, bg_alpha);
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, 0, height - 2, width, 2, border, bg_alpha);
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, 0, 0, 2, height, border, bg_alpha);
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, width - 2, 0, 2, height, border, bg_alpha);
+        // Mark: a bar down the left edge of the cell
+        if self.marked.contains(&item_idx) {
+            let r = self.cell_rect(vi);
+            let [cr, cg, cb, _] = self.colors.selection;
+            fill_rect_alpha(pixmap.data_mut(), pw, ph, r.x, r.y, (4.0 * s).round() as u32, r.h, [cr, cg, cb], 0xff);
+        }
 
-        // Search text
-        if !self.input.is_empty() {
-            let tw = measure_text(&mut self.font_system, &self.input, font_size, &self.font_family);
-            let tx = (width as f32 - tw) / 2.0;
-            let ty = (BAR_H + font_size) / 2.0;
-            render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                &self.input, tx, ty, font_size, width as f32, BAR_H, text_color,
-                &self.font_family);
+        // Measure content width for centering
+        let name_w = self.fonts.measure(&self.items[item_idx].name, font_size, &self.font_family, Weight::NORMAL);
+        let has_comment = self.show_comments && !self.items[item_idx].comment.is_empty();
+        let comment_w = if has_comment {
+            self.fonts.measure(&self.items[item_idx].comment, comment_font_size, &self.font_family, Weight::NORMAL)
+        } else { 0.0 };
+        let content_w = icon_pad + name_w + if has_comment { 12.0 * s + comment_w } else { 0.0 };
+        let cx = if self.center_items { ((col_w - content_w) / 2.0).max(0.0) } else { 0.0 };
+
+        // Icon
+        if has_icons && let Some(ref data) = self.items[item_idx].icon_data {
+            let iw = self.items[item_idx].icon_w;
+            let ih = self.items[item_idx].icon_h;
+            let ix = (cell_x + cx + pad) as i32;
+            let iy = cell_y as i32 + (row_h as i32 - ih as i32) / 2;
+            blit_rgba(pixmap.data_mut(), pw as i32, ph as i32,
+                ix, iy, iw as i32, ih as i32, data);
         }
 
-        // Grid items
-        for (vi, &item_idx) in filtered.iter().enumerate() {
-            let i = start + vi;
-            let col = vi % ecols;
-            let row = vi / ecols;
-            let cell_x = x_off + col as f32 * col_w;
-            let cell_y = BAR_H + row as f32 * row_h;
-            let text_x = cell_x + icon_pad;
-
-            // Selection highlight
-            if i == selected {
-                fill_rect_alpha(pixmap.data_mut(), pw, ph,
-                    cell_x as u32, cell_y as u32, col_w as u32, row_h as u32, sel_color, sel_alpha);
-            } else if hover == Some(i) {
-                let a = (sel_alpha as f32 / 2.0 * hover_alpha) as u8;
-                if a > 0 {
-                    fill_rect_alpha(pixmap.data_mut(), pw, ph,
-                        cell_x as u32, cell_y as u32, col_w as u32, row_h as u32, sel_color, a);
-                }
-            } else if fade_out == Some(i) {
-                let a = (sel_alpha as f32 / 2.0 * fade_out_alpha) as u8;
-                if a > 0 {
-                    fill_rect_alpha(pixmap.data_mut(), pw, ph,
-                        cell_x as u32, cell_y as u32, col_w as u32, row_h as u32, sel_color, a);
-                }
+        // Name
+        let name_y = cell_y + (row_h - font_size * LINE_HEIGHT) / 2.0;
+        let max_name_w = (col_w - icon_pad - cx).max(0.0);
+        self.fonts.draw(pixmap, &self.items[item_idx].name, text_x + cx, name_y, font_size,
+            max_name_w, text_color, &self.font_family, Weight::NORMAL);
+
+        // Comment
+        if has_comment {
+            let comment_x = text_x + cx + name_w.min(max_name_w) + 12.0 * s;
+            let comment_y = cell_y + (row_h - comment_font_size * LINE_HEIGHT) / 2.0;
+            let comment_max_w = (cell_x + col_w - comment_x - pad).max(0.0);
+            if comment_max_w > 20.0 * s {
+                self.fonts.draw(pixmap, &self.items[item_idx].comment, comment_x, comment_y,
+                    comment_font_size, comment_max_w, comment_color,
+                    &self.font_family, Weight::NORMAL);
             }
+        }
+    }
 
-            // Measure content width for centering
-            let name_w = measure_text(&mut self.font_system, &self.items[item_idx].name, font_size, &self.font_family);
-            let has_comment = show_comments && !self.items[item_idx].comment.is_empty();
-            let comment_w = if has_comment {
-                measure_text(&mut self.font_system, &self.items[item_idx].comment, comment_font_size, &self.font_family)
-            } else { 0.0 };
-            let content_w = icon_pad + name_w + if has_comment { 12.0 + comment_w } else { 0.0 };
-            let cx = if self.center_items { ((col_w - content_w) / 2.0).max(0.0) } else { 0.0 };
-
-            // Icon
-            if has_icons {
-                if let Some(ref data) = self.items[item_idx].icon_data {
-                    let iw = self.items[item_idx].icon_w;
-                    let ih = self.items[item_idx].icon_h;
-                    let ix = (cell_x + cx) as i32 + PAD as i32;
-                    let iy = cell_y as i32 + (row_h as i32 - ih as i32) / 2;
-                    blit_rgba(pixmap.data_mut(), pw as i32, ph as i32,
-                        ix, iy, iw as i32, ih as i32, data);
+    fn handle_pointer(&mut self, event: &PointerEvent) -> Update {
+        match event.kind {
+            PointerEventKind::Press { button: 0x110, .. } => {
+                if let Some(idx) = self.item_at_pos(event.position.0 as f32, event.position.1 as f32) {
+                    self.selected = idx;
+                    return self.select_item();
                 }
             }
-
-            // Name
-            let name_y = cell_y + (row_h + font_size) / 2.0;
-            let max_name_w = (col_w - icon_pad - cx).max(0.0);
-            render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                &self.items[item_idx].name, text_x + cx, name_y, font_size,
-                max_name_w, row_h, text_color, &self.font_family);
-
-            // Comment
-            if has_comment {
-                let comment_x = text_x + cx + name_w.min(max_name_w) + 12.0;
-                let comment_y = cell_y + (row_h + comment_font_size) / 2.0;
-                let comment_max_w = (cell_x + col_w - comment_x - PAD).max(0.0);
-                if comment_max_w > 20.0 {
-                    render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                        &self.items[item_idx].comment, comment_x, comment_y,
-                        comment_font_size, comment_max_w, row_h, comment_color,
-                        &self.font_family);
+            PointerEventKind::Motion { .. } => {
+                let new_hover = self.item_at_pos(event.position.0 as f32, event.position.1 as f32);
+                if new_hover != self.hover_index {
+                    if self.hover_index.is_some() {
+                        self.fade_out_index = self.hover_index;
+                        self.fade_out_alpha = self.hover_alpha;
+                        self.fade_out = Tween::new(self.hover_alpha, 0.0, HOVER_FADE);
+                    }
+                    self.hover_index = new_hover;
+                    self.hover_alpha = if new_hover.is_some() { 0.0 } else { 1.0 };
+                    self.hover_fade = Tween::new(self.hover_alpha, 1.0, HOVER_FADE);
+                    return Update::Redraw;
                 }
             }
+            PointerEventKind::Axis { ref vertical, .. } => {
+                let ecols = self.effective_cols();
+                let visible = self.visible_rows() * ecols;
+                if vertical.absolute > 0.0 && self.scroll_offset + visible < self.filtered.len() {
+                    self.scroll_offset = (self.scroll_offset + ecols)
+                        .min(self.filtered.len().saturating_sub(visible));
+                    return Update::Redraw;
+                } else if vertical.absolute < 0.0 && self.scroll_offset > 0 {
+                    self.scroll_offset = self.scroll_offset.saturating_sub(ecols);
+                    return Update::Redraw;
+                }
+            }
+            _ => {}
         }
+        Update::None
+    }
 
-        // Copy RGBA -> BGRA
-        for (dst, src) in canvas.chunks_exact_mut(4).zip(pixmap.data().chunks_exact(4)) {
-            dst[0] = src[2];
-            dst[1] = src[1];
-            dst[2] = src[0];
-            dst[3] = src[3];
-        }
+    /// Text cursor over the search bar, a hand over entries.
+    fn cursor(&self, x: f64, y: f64) -> Shape {
+        if (y as f32) < BAR_H * self.scale { return Shape::Text; }
+        if self.item_at_pos(x as f32, y as f32).is_some() { Shape::Pointer } else { Shape::Default }
+    }
+
+    fn animating(&self) -> bool {
+        (self.hover_index.is_some() && self.hover_alpha < 1.0) || self.fade_out_index.is_some()
+    }
 
-        wl_buf.attach_to(self.layer.wl_surface()).unwrap();
-        self.layer.wl_surface().damage_buffer(0, 0, width as i32, height as i32);
-        self.layer.wl_surface().commit();
+    fn animate(&mut self, now: Instant) -> Update {
+        self.hover_alpha = self.hover_fade.value(now);
+        self.fade_out_alpha = self.fade_out.value(now);
+        if self.fade_out.done(now) { self.fade_out_index = None; }
+        Update::Redraw
     }
 }
 
-// --- Rendering helpers ---
+impl Widget for App {
+    fn draw(&mut self, pixmap: &mut Pixmap) { self.draw(pixmap); }
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Option<Vec<Rect>> { self.draw_dirty(pixmap) }
+    fn backdrop(&self) -> Backdrop { self.backdrop() }
+    fn resize(&mut self, width: u32, height: u32, scale: f32) {
+        self.width = width;
+        self.height = height;
+        if scale != self.scale {
+            self.scale = scale;
+            self.reload_icons();
+        }
+    }
+    fn key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> Update { self.handle_key(event, modifiers) }
+    fn pointer(&mut self, event: &PointerEvent) -> Update { self.handle_pointer(event) }
+    fn cursor(&self, x: f64, y: f64) -> Shape { self.cursor(x, y) }
+    fn animating(&self) -> bool { self.animating() }
+    fn hidden(&mut self) { self.client = None; }
+    fn paste(&mut self, text: &str) -> Update { self.paste(text) }
+    fn text_cursor(&self) -> Option<Rect> { Some(self.caret) }
+    fn text_input(&mut self, event: &TextInput) -> Update { self.text_input(event) }
+    fn take_clip(&mut self) -> Option<Clip> { self.clip.take() }
+    fn animate(&mut self, now: Instant) -> Update { self.animate(now) }
+}
+
+// --- Fuzzy matching ---
 
 // Lower score = better match. None = no match.
 fn fuzzy_score(haystack: &str, needle: &str) -> Option<u32> {
@@ -795,391 +1026,261 @@ fn fuzzy_score(haystack: &str, needle: &str) -> Option<u32> {
     Some(score)
 }
 
-fn fill_rect_alpha(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3], a: u8) {
-    if a == 0xff { return fill_rect(data, pw, ph, x, y, w, h, c); }
-    let a = a as u32;
-    let inv = 255 - a;
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i]     = ((c[0] as u32 * a + data[i] as u32 * inv) / 255) as u8;
-            data[i + 1] = ((c[1] as u32 * a + data[i + 1] as u32 * inv) / 255) as u8;
-            data[i + 2] = ((c[2] as u32 * a + data[i + 2] as u32 * inv) / 255) as u8;
-            data[i + 3] = ((a + data[i + 3] as u32 * inv / 255)) as u8;
-        }
-    }
-}
-
-fn fill_rect(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3]) {
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i] = c[0];
-            data[i + 1] = c[1];
-            data[i + 2] = c[2];
-            data[i + 3] = 0xff;
-        }
-    }
-}
-
-fn make_attrs(family: &str) -> Attrs<'_> {
-    Attrs::new().family(cosmic_text::Family::Name(family))
-}
-
-fn measure_text(font_system: &mut FontSystem, text: &str, font_size: f32, family: &str) -> f32 {
-    let mut buf = Buffer::new(font_system, Metrics::new(font_size, font_size * 1.2));
-    buf.set_size(font_system, None, None);
-    buf.set_text(font_system, text, &make_attrs(family), Shaping::Advanced, None);
-    buf.shape_until_scroll(font_system, false);
-    buf.layout_runs().next().map_or(0.0, |r| r.line_w)
-}
-
-fn render_text(
-    pixmap: &mut Pixmap, font_system: &mut FontSystem, swash_cache: &mut SwashCache,
-    text: &str, x: f32, y: f32, font_size: f32, max_w: f32, _max_h: f32, color: [u8; 3],
-    family: &str,
-) {
-    let line_h = font_size * 1.2;
-    let mut buf = Buffer::new(font_system, Metrics::new(font_size, line_h));
-    buf.set_size(font_system, None, None);
-    buf.set_text(font_system, text, &make_attrs(family), Shaping::Advanced, None);
-    buf.shape_until_scroll(font_system, false);
-
-    let pw = pixmap.width() as i32;
-    let ph = pixmap.height() as i32;
-    let x_max = (x + max_w) as i32;
-    if let Some(run) = buf.layout_runs().next() {
-        for glyph in run.glyphs.iter() {
-            let physical = glyph.physical((x, y), 1.0);
-            if physical.x >= x_max { break; }
-            if let Some(image) = swash_cache.get_image_uncached(font_system, physical.cache_key) {
-                let x0 = physical.x + image.placement.left;
-                let y0 = physical.y - image.placement.top;
-                let w = image.placement.width as i32;
-                let h = image.placement.height as i32;
-                match image.content {
-                    SwashContent::Mask => blit_mask(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data, &color),
-                    SwashContent::Color => blit_color(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data),
-                    SwashContent::SubpixelMask => {}
-                }
-            }
-        }
-    }
-}
+// --- Main ---
 
-fn blit_mask(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, mask: &[u8], color: &[u8; 3]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let a = mask[(gy * w + gx) as usize] as u32;
-            if a == 0 { continue; }
-            let i = (py * pw + px) as usize * 4;
-            let inv = 255 - a;
-            data[i]     = ((color[0] as u32 * a + data[i] as u32 * inv) / 255) as u8;
-            data[i + 1] = ((color[1] as u32 * a + data[i + 1] as u32 * inv) / 255) as u8;
-            data[i + 2] = ((color[2] as u32 * a + data[i + 2] as u32 * inv) / 255) as u8;
-            data[i + 3] = ((a + data[i + 3] as u32 * inv / 255)) as u8;
-        }
-    }
+/// Per-invocation options, from the command line or `grimoire msg show`.
+struct Invocation {
+    mode: Mode,
+    relative_paths: Option<String>,
+    render_to: Option<String>,
+    replay: Option<String>,
+    input: Option<String>,
+    scale: f32,
+    daemon: bool,
+    dbus: bool,
 }
 
-fn blit_rgba(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, src: &[u8]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let si = (gy * w + gx) as usize * 4;
-            let di = (py * pw + px) as usize * 4;
-            let a = src[si + 3] as u32;
-            if a == 0 { continue; }
-            if a == 255 {
-                data[di] = src[si];
-                data[di + 1] = src[si + 1];
-                data[di + 2] = src[si + 2];
-                data[di + 3] = 255;
-            } else {
-                let inv = 255 - a;
-                data[di]     = ((src[si] as u32 * a + data[di] as u32 * inv) / 255) as u8;
-                data[di + 1] = ((src[si + 1] as u32 * a + data[di + 1] as u32 * inv) / 255) as u8;
-                data[di + 2] = ((src[si + 2] as u32 * a + data[di + 2] as u32 * inv) / 255) as u8;
-                data[di + 3] = ((a + data[di + 3] as u32 * inv / 255)) as u8;
-            }
-        }
-    }
-}
+const CLI: Spec = Spec {
+    name: "grimoire",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Application launcher and dmenu-style picker.",
+    usage: &["[OPTIONS]", "msg show [OPTIONS]", "msg hide|quit"],
+    opts: &[
+        Opt { name: "--drun", value: None, help: "Launch desktop entries (default)" },
+        Opt { name: "--dmenu", value: None, help: "Pick a line from stdin and print it" },
+        Opt { name: "--relative-paths", value: Some("PREFIX"), help: "Strip PREFIX from displayed dmenu lines" },
+        Opt { name: "--input", value: Some("TEXT"), help: "Pre-fill the search bar" },
+        Opt { name: "--columns", value: Some("N"), help: "Grid columns (same as --set columns=N)" },
+        Opt { name: "--center-items", value: None, help: "Center entries in their cells" },
+        Opt { name: "--daemon", value: None, help: "Stay resident, hidden until `grimoire msg show`" },
+        Opt { name: "--replay", value: Some("SCRIPT"), help: "Feed scripted input without a compositor, then exit" },
+    ],
+};
 
-fn blit_color(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, rgba: &[u8]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let si = (gy * w + gx) as usize * 4;
-            let a = rgba[si + 3] as u32;
-            if a == 0 { continue; }
-            let i = (py * pw + px) as usize * 4;
-            let inv = 255 - a;
-            data[i]     = (rgba[si] as u32 * a / 255 + data[i] as u32 * inv / 255) as u8;
-            data[i + 1] = (rgba[si + 1] as u32 * a / 255 + data[i + 1] as u32 * inv / 255) as u8;
-            data[i + 2] = (rgba[si + 2] as u32 * a / 255 + data[i + 2] as u32 * inv / 255) as u8;
-            data[i + 3] = (a + data[i + 3] as u32 * inv / 255) as u8;
+fn parse_args(opts: &Parsed, cfg: &mut Config) -> Result<Invocation, String> {
+    let mut inv = Invocation { mode: Mode::Drun, relative_paths: None, render_to: None, replay: None, input: None, scale: 1.0, daemon: false, dbus: false };
+    for (name, value) in opts {
+        let v = value.clone().unwrap_or_default();
+        match *name {
+            "--dmenu" => inv.mode = Mode::Dmenu,
+            "--drun" => inv.mode = Mode::Drun,
+            "--columns" => cfg.columns = cli::value(name, &v)?,
+            "--center-items" => cfg.center_items = true,
+            "--relative-paths" => inv.relative_paths = Some(v),
+            "--render-to" => inv.render_to = Some(v),
+            "--replay" => inv.replay = Some(v),
+            "--input" => inv.input = Some(v),
+            "--scale" => inv.scale = cli::value(name, &v)?,
+            "--output" => cfg.output = Some(v),
+            "--set" => cli::set(cfg, &v)?,
+            "--daemon" => inv.daemon = true,
+            "--dbus" => inv.dbus = true,
+            _ => {}
         }
     }
+    Ok(inv)
 }
 
-// --- Wayland handler boilerplate ---
-
-impl CompositorHandler for App {
-    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
-    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
-    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
-    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
-    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+/// Reload config and colors from disk; returns the files to keep watching.
+fn reload(win: &mut Window<App>) -> Vec<PathBuf> {
+    let base: Config = load_config("grimoire");
+    let colors: Colors = load_colors(base.color_file.as_deref());
+    let mut cfg = base.clone();
+    // Arguments were validated when they were first parsed
+    parse_args(&win.widget.opts, &mut cfg).ok();
+    win.widget.apply_config(&cfg, colors);
+    win.widget.config = base;
+    (win.opts.width, win.opts.height) = (cfg.window_width, cfg.window_height);
+    win.opts.placement = cfg.placement.clone();
+    win.opts.style = cfg.style.clone();
+    win.reload();
+    watch::config_files("grimoire", cfg.color_file.as_deref())
 }
 
-impl OutputHandler for App {
-    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
-    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+/// Reopen the resident window for `msg show` (`args` as on the command
+/// line, `stdin` the dmenu lines) or D-Bus `Show`.
+fn show(win: &mut dyn Host<Widget = App>, args: &[String], stdin: Option<&str>) -> Result<(), String> {
+    let mut cfg = win.widget().config.clone();
+    let opts = CLI.parse(args)?;
+    let inv = parse_args(&opts, &mut cfg)?;
+    win.widget().opts = opts;
+    win.hide();
+    win.widget().open(&cfg, &inv, stdin);
+    win.opts().output = cfg.output;
+    win.opts().placement = cfg.placement;
+    win.opts().style = cfg.style;
+    win.show();
+    Ok(())
 }
 
-impl SeatHandler for App {
-    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, capability: Capability) {
-        if capability == Capability::Keyboard && self.keyboard.is_none() {
-            self.keyboard = Some(self.seat_state.get_keyboard_with_repeat(
-                qh, &seat, None,
-                self.loop_handle.clone(),
-                Box::new(|state, _wl_kbd, event| {
-                    state.handle_key(&event);
-                }),
-            ).unwrap());
-        }
-        if capability == Capability::Pointer && self.pointer.is_none() {
-            self.pointer = Some(self.seat_state.get_pointer(qh, &seat).unwrap());
-        }
-    }
-    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, _: Capability) {}
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-}
+struct Control(Remote<App>);
 
-impl KeyboardHandler for App {
-    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
-    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
-    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
-        self.handle_key(&event);
-    }
-    fn repeat_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
-        self.handle_key(&event);
-    }
-    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
-    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, modifiers: Modifiers, _: RawModifiers, _: u32) { self.modifiers = modifiers; }
-}
-
-impl PointerHandler for App {
-    fn pointer_frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, pointer: &wl_pointer::WlPointer, events: &[PointerEvent]) {
-        let mut redraw = false;
-        for event in events {
-            match event.kind {
-                PointerEventKind::Enter { serial, .. } => {
-                    let device = self.cursor_shape_manager.get_shape_device(pointer, qh);
-                    device.set_shape(serial, Shape::Default);
-                    device.destroy();
-                }
-                PointerEventKind::Press { button: 0x110, .. } => {
-                    if let Some(idx) = self.item_at_pos(event.position.0 as f32, event.position.1 as f32) {
-                        self.selected = idx;
-                        self.select_item();
-                        return;
-                    }
-                }
-                PointerEventKind::Motion { .. } => {
-                    let new_hover = self.item_at_pos(event.position.0 as f32, event.position.1 as f32);
-                    if new_hover != self.hover_index {
-                        if self.hover_index.is_some() {
-                            self.fade_out_index = self.hover_index;
-                            self.fade_out_alpha = self.hover_alpha;
-                        }
-                        self.hover_index = new_hover;
-                        self.hover_alpha = if new_hover.is_some() { 0.0 } else { 1.0 };
-                        redraw = true;
-                    }
-                }
-                PointerEventKind::Axis { ref vertical, .. } => {
-                    let ecols = self.effective_cols();
-                    let visible = self.visible_rows() * ecols;
-                    if vertical.absolute > 0.0 && self.scroll_offset + visible < self.filtered.len() {
-                        self.scroll_offset = (self.scroll_offset + ecols)
-                            .min(self.filtered.len().saturating_sub(visible));
-                        redraw = true;
-                    } else if vertical.absolute < 0.0 && self.scroll_offset > 0 {
-                        self.scroll_offset = self.scroll_offset.saturating_sub(ecols);
-                        redraw = true;
-                    }
-                }
-                _ => {}
-            }
-        }
-        if redraw { self.draw(); }
+#[interface(name = "org.widgets.Grimoire")]
+impl Control {
+    /// As `msg show` with no options; only with `--daemon`.
+    async fn show(&self) -> fdo::Result<()> {
+        self.0.call(|win| {
+            if !win.opts().resident { return Err("not running with --daemon".to_string()); }
+            show(win, &[], None)
+        }).await?.map_err(fdo::Error::Failed)
     }
-}
 
-impl ShmHandler for App {
-    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
-}
+    async fn hide(&self) -> fdo::Result<()> { self.0.call(|win| win.dismiss()).await }
 
-impl LayerShellHandler for App {
-    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
-        self.exit = true;
-    }
-    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
-        if configure.new_size.0 > 0 { self.width = configure.new_size.0; }
-        if configure.new_size.1 > 0 { self.height = configure.new_size.1; }
-        self.draw();
-    }
-}
+    async fn quit(&self) -> fdo::Result<()> { self.0.call(|win| win.quit()).await }
 
-impl ProvidesRegistryState for App {
-    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
-    registry_handlers![OutputState, SeatState];
+    #[zbus(property)]
+    async fn visible(&self) -> fdo::Result<bool> { self.0.call(|win| win.shown()).await }
 }
 
-delegate_compositor!(App);
-delegate_output!(App);
-delegate_seat!(App);
-delegate_keyboard!(App);
-delegate_pointer!(App);
-delegate_shm!(App);
-delegate_layer!(App);
-delegate_registry!(App);
-
-// --- Main ---
-
 fn main() {
-    let mut cfg = load_config();
-    let colors = load_colors(cfg.color_file.as_deref());
-
-    let args: Vec<String> = std::env::args().collect();
-    let mut mode = Mode::Drun;
-    let mut relative_paths: Option<String> = None;
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--dmenu" => { mode = Mode::Dmenu; i += 1; }
-            "--drun" => { mode = Mode::Drun; i += 1; }
-            "--columns" => {
-                i += 1;
-                if i < args.len() { if let Ok(v) = args[i].parse() { cfg.columns = v; } }
-                i += 1;
-            }
-            "--center-items" => { cfg.center_items = true; i += 1; }
-            "--relative-paths" => {
-                i += 1;
-                if i < args.len() { relative_paths = Some(args[i].clone()); i += 1; }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "msg") {
+        // Validate here so mistakes are reported by the client, not the daemon
+        match args.get(1).map(String::as_str) {
+            Some("show") => {
+                let opts = CLI.parse_or_exit(&args[2..]);
+                if let Err(e) = parse_args(&opts, &mut Config::default()) { CLI.fail(&e); }
             }
-            _ => { eprintln!("grimoire: unknown arg: {}", args[i]); i += 1; }
+            Some("hide" | "quit") if args.len() == 2 => {}
+            _ => CLI.fail("usage: grimoire msg show [OPTIONS] | msg hide | msg quit"),
         }
+        let forward_stdin = args.iter().any(|a| a == "--dmenu");
+        if let Err(e) = ipc::send("grimoire", &args[1..], forward_stdin) {
+            eprintln!("grimoire: {e}");
+            std::process::exit(1);
+        }
+        return;
     }
-
-    let frecency = load_frecency();
-    let items = match mode {
-        Mode::Drun => load_desktop_entries(cfg.icon_size, &frecency),
-        Mode::Dmenu => load_stdin_items(relative_paths.as_deref()),
+    let opts = CLI.parse_or_exit(&args);
+    trace::start("grimoire", &opts);
+    cli::config_commands::<Config>("grimoire", &opts);
+
+    let base: Config = load_config("grimoire");
+    let colors: Colors = load_colors(base.color_file.as_deref());
+    let mut cfg = base.clone();
+    let inv = parse_args(&opts, &mut cfg).unwrap_or_else(|e| CLI.fail(&e));
+
+    // Headless runs ignore frecency so the same input always gives the same frame
+    let frecency = if inv.render_to.is_some() || inv.replay.is_some() { HashMap::new() } else { load_frecency() };
+    // A daemon keeps desktop entries loaded even if it starts in dmenu mode
+    let drun_items = if inv.mode == Mode::Drun || inv.daemon {
+        load_desktop_entries(cfg.icon_size, &frecency)
+    } else {
+        Vec::new()
     };
 
     let width = cfg.window_width;
     let height = cfg.window_height;
 
-    let conn = Connection::connect_to_env().unwrap();
-    let (globals, event_queue) = registry_queue_init::<App>(&conn).unwrap();
-    let qh = event_queue.handle();
-
-    let mut event_loop: EventLoop<App> = EventLoop::try_new().unwrap();
-    let loop_handle = event_loop.handle();
-    WaylandSource::new(conn.clone(), event_queue).insert(loop_handle).unwrap();
-
-    let compositor = CompositorState::bind(&globals, &qh).unwrap();
-    let layer_shell = LayerShell::bind(&globals, &qh).unwrap();
-    let shm = Shm::bind(&globals, &qh).unwrap();
-    let cursor_shape_manager = CursorShapeManager::bind(&globals, &qh).unwrap();
-
-    let surface = compositor.create_surface(&qh);
-    let layer = layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("grimoire"), None);
-    layer.set_size(width, height);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
-    layer.wl_surface().commit();
-
-    let pool = SlotPool::new((width * height * 4) as usize, &shm).unwrap();
-
-    let filtered: Vec<usize> = (0..items.len()).collect();
-
-    let font_data = std::fs::read(expand_path(&cfg.font)).expect("failed to read font file");
-    let mut db = fontdb::Database::new();
-    db.load_font_data(font_data);
-    let font_family = db.faces().next().expect("font file contains no faces").families[0].0.clone();
-    let font_system = FontSystem::new_with_locale_and_db("en-US".into(), db);
+    let mut db = font_db();
+    let font_family = load_font(&mut db, &cfg.font).unwrap_or_else(|| {
+        eprintln!("grimoire: font not found: {}", cfg.font);
+        std::process::exit(1);
+    });
 
     let mut app = App {
-        registry_state: RegistryState::new(&globals),
-        seat_state: SeatState::new(&globals, &qh),
-        output_state: OutputState::new(&globals, &qh),
-        shm,
-        layer,
-        keyboard: None,
-        pointer: None,
-        cursor_shape_manager,
-        pool,
         width,
         height,
-        exit: false,
-        font_system,
-        swash_cache: SwashCache::new(),
-        loop_handle: event_loop.handle(),
-        mode,
-        filtered,
-        items,
+        scale: 1.0,
+        fonts: Fonts::new(db, &cfg.font_fallback),
+        mode: Mode::Drun,
+        filtered: Vec::new(),
+        items: Vec::new(),
         selected: 0,
         scroll_offset: 0,
-        input: String::new(),
+        input: LineEdit::default(),
+        caret: Rect { x: 0, y: 0, w: 0, h: 0 },
         colors,
+        selection_radius: cfg.style.selection_radius,
         font_size: cfg.font_size,
         comment_font_size: cfg.comment_font_size,
         icon_size: cfg.icon_size,
-        terminal_cmd: cfg.terminal,
+        terminal_cmd: cfg.terminal.clone(),
         font_family,
         hover_index: None,
         hover_alpha: 1.0,
+        hover_fade: Tween::at(1.0),
         fade_out_index: None,
         fade_out_alpha: 0.0,
-        cols: cfg.columns.max(1),
+        fade_out: Tween::at(0.0),
+        cols: 1,
         show_comments: cfg.show_comments,
         search_comments: cfg.search_comments,
-        center_items: cfg.center_items,
+        center_items: false,
         frecency,
-        modifiers: Modifiers::default(),
+        save_frecency: if inv.replay.is_some() { |_| {} } else { save_frecency },
+        spawn: if inv.replay.is_some() { replay::print_run } else { keys::run },
+        keys: cfg.keys.clone(),
+        marked: Vec::new(),
+        shown: None,
+        drun_items,
+        client: None,
+        clip: None,
+        config: base,
+        opts,
     };
+    // The daemon reads dmenu items per request, not from its own stdin
+    if !inv.daemon || inv.mode == Mode::Drun { app.open(&cfg, &inv, None); }
 
-    loop {
-        event_loop.dispatch(Duration::from_millis(16), &mut app).unwrap();
-        if app.exit { break; }
-        let mut anim = false;
-        if app.hover_index.is_some() && app.hover_alpha < 1.0 {
-            app.hover_alpha = (app.hover_alpha + 0.15).min(1.0);
-            anim = true;
+    if let Some(path) = &inv.render_to {
+        if let Err(e) = render_to_png(&mut app, width, height, inv.scale, &cfg.style, Path::new(path)) {
+            eprintln!("grimoire: {e}");
+            std::process::exit(1);
         }
-        if app.fade_out_index.is_some() {
-            app.fade_out_alpha = (app.fade_out_alpha - 0.15).max(0.0);
-            anim = true;
-            if app.fade_out_alpha == 0.0 { app.fade_out_index = None; }
+        return;
+    }
+
+    let options = Options {
+        namespace: "grimoire",
+        width,
+        height,
+        placement: cfg.placement.clone(),
+        style: cfg.style.clone(),
+        keyboard: KeyboardInteractivity::Exclusive,
+        output: cfg.output,
+        resident: inv.daemon,
+    };
+    let bus = if inv.dbus {
+        dbus::connect(Builder::session(), Control).inspect_err(|e| eprintln!("grimoire: D-Bus: {e}")).ok()
+    } else {
+        None
+    };
+
+    if let Some(path) = &inv.replay {
+        if let Err(e) = replay::run(app, options, inv.scale, Path::new(path), bus) {
+            eprintln!("grimoire: replay: {e}");
+            std::process::exit(1);
         }
-        if anim { app.draw(); }
+        return;
+    }
+
+    let mut event_loop: EventLoop<Window<App>> = EventLoop::try_new().unwrap();
+    let mut win = Window::new(&event_loop, options, app);
+
+    if inv.daemon {
+        let served = ipc::serve(&event_loop.handle(), "grimoire", move |req, win: &mut Window<App>| {
+            match req.command.as_str() {
+                "show" => match show(win, &req.args, Some(&req.stdin)) {
+                    Ok(()) => win.widget.client = Some(req.stream),
+                    Err(e) => eprintln!("grimoire: msg show: {e}"),
+                },
+                "hide" => win.dismiss(),
+                "quit" => win.quit(),
+                cmd => eprintln!("grimoire: unknown command: {cmd}"),
+            }
+        });
+        if let Err(e) = served {
+            eprintln!("grimoire: {e}");
+            std::process::exit(1);
+        }
+    }
+    if let Some(bus) = bus && let Err(e) = bus.attach(&event_loop.handle()) {
+        eprintln!("grimoire: D-Bus: {e}");
+    }
+    let watched = watch::config_files("grimoire", cfg.color_file.as_deref());
+    if let Err(e) = watch::watch(&event_loop.handle(), watched, reload) {
+        eprintln!("grimoire: cannot watch config: {e}");
     }
+    win.run(&mut event_loop);
 }
@@ -0,0 +1,160 @@
+//! End-to-end tests: scripted input through `--replay`, in a scratch home so
+//! no real config, desktop entries or frecency get involved; the D-Bus ones
+//! on a private bus.
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use widgets_core::replay::testing::{self, Bus};
+
+fn scratch(test: &str) -> PathBuf {
+    testing::scratch(&format!("grimoire-replay-{test}"))
+}
+
+/// Run grimoire on `script` with `stdin` piped in and `config` as its
+/// config file.
+fn replay(home: &Path, config: &str, script: &str, args: &[&str], stdin: &str) -> Output {
+    testing::replay(env!("CARGO_BIN_EXE_grimoire"), home, config, script, args, stdin)
+}
+
+const FRUIT: &str = "apple\nbanana\ncherry\n";
+
+#[test]
+fn dmenu_enter_prints_selected_line() {
+    let home = scratch("dmenu-enter");
+    let script = r#"steps = [{ key = "Down" }, { key = "Return" }]"#;
+    let out = replay(&home, "", script, &["--dmenu"], FRUIT);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "banana\n");
+}
+
+#[test]
+fn dmenu_filter_then_enter() {
+    let home = scratch("dmenu-filter");
+    let script = r#"steps = [{ type = "cher" }, { key = "Return" }]"#;
+    let out = replay(&home, "", script, &["--dmenu"], FRUIT);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "cherry\n");
+}
+
+#[test]
+fn dmenu_paste_filters() {
+    let home = scratch("dmenu-paste");
+    let script = r#"
+        clipboard = "apple"
+        steps = [{ key = "ctrl+v" }, { key = "Return" }]
+    "#;
+    let out = replay(&home, "", script, &["--dmenu"], FRUIT);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "apple\n");
+}
+
+#[test]
+fn escape_prints_nothing() {
+    let home = scratch("escape");
+    let script = r#"steps = [{ key = "Escape" }, { key = "Return" }]"#;
+    let out = replay(&home, "", script, &["--dmenu"], FRUIT);
+    assert!(out.stdout.is_empty());
+}
+
+#[test]
+fn copy_copies_instead_of_printing() {
+    let home = scratch("copy");
+    let script = r#"steps = [{ key = "Tab" }, { key = "Tab" }, { key = "ctrl+c" }]"#;
+    let out = replay(&home, "", script, &["--dmenu"], FRUIT);
+    assert!(out.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("replay: copy: apple\nbanana\n"));
+}
+
+#[test]
+fn run_binding_is_recorded_not_spawned() {
+    let home = scratch("run-binding");
+    let config = "[keys]\n\"ctrl+o\" = \"run:open-it\"\n";
+    let script = r#"steps = [{ key = "End" }, { key = "ctrl+o" }]"#;
+    let out = replay(&home, config, script, &["--dmenu"], FRUIT);
+    assert!(out.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("replay: run: open-it cherry\n"));
+}
+
+#[test]
+fn drun_launch_is_recorded_not_spawned() {
+    let home = scratch("drun");
+    let apps = home.join(".local/share/applications");
+    std::fs::create_dir_all(&apps).unwrap();
+    std::fs::write(apps.join("replay-probe.desktop"),
+        "[Desktop Entry]\nType=Application\nName=Replay Probe Zq\nExec=replay-probe --flag %U\n").unwrap();
+    let script = r#"steps = [{ type = "Replay Probe Zq" }, { key = "Return" }]"#;
+    let out = replay(&home, "", script, &[], "");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("replay: run: replay-probe --flag"));
+    assert!(!home.join(".local/state/widgets").exists(), "frecency was saved");
+}
+
+#[test]
+fn render_step_writes_frame() {
+    let home = scratch("render");
+    let png = home.join("frame.png");
+    let script = format!(r#"steps = [
+        {{ configure = {{ width = 300, height = 200, scale = 2 }} }},
+        {{ type = "ban" }},
+        {{ render = {:?} }},
+    ]"#, png.display().to_string());
+    let _ = replay(&home, "[style]\nshadow = 0\n", &script, &["--dmenu"], FRUIT);
+    let frame = image::open(&png).unwrap().to_rgba8();
+    assert_eq!(frame.dimensions(), (600, 400));
+    assert!(frame.pixels().any(|p| p.0[3] == 255), "frame is empty");
+}
+
+#[test]
+fn color_alpha_blends_over_the_panel() {
+    let home = scratch("color-alpha");
+    let colors = home.join("colors-grimoire.toml");
+    std::fs::write(&colors, "background = \"#000000\"\nbar_bg = \"#ff000080\"\n").unwrap();
+    let png = home.join("frame.png");
+    let config = format!("color_file = {:?}\n[style]\nshadow = 0\nborder_color = \"#00ff0080\"\n", colors.display().to_string());
+    let script = format!(r#"steps = [
+        {{ configure = {{ width = 300, height = 200, scale = 1 }} }},
+        {{ render = {:?} }},
+    ]"#, png.display().to_string());
+    let out = replay(&home, &config, &script, &["--dmenu"], FRUIT);
+    assert!(out.stderr.is_empty(), "{}", String::from_utf8_lossy(&out.stderr));
+    let frame = image::open(&png).unwrap().to_rgba8();
+    // Half red over black, in the search bar clear of the text, and a half
+    // green border
+    let half = |c: u8| (0x78..=0x88).contains(&c);
+    let [r, g, b, a] = frame.get_pixel(20, 10).0;
+    assert!(half(r) && g == 0 && b == 0 && a == 0xff, "{:?}", [r, g, b, a]);
+    let [r, g, b, a] = frame.get_pixel(0, 0).0;
+    assert!(r == 0 && half(g) && b == 0 && a == 0xff, "{:?}", [r, g, b, a]);
+}
+
+// --- D-Bus ---
+
+const IFACE: &str = "org.widgets.Grimoire";
+
+#[test]
+fn dbus_shows_and_hides_the_daemon() {
+    let home = scratch("dbus-show");
+    let bus = Bus::start();
+    let script = r#"steps = [{ serve_until = "shown" }, { serve_until = "hidden" }, { serve_until = "exit" }]"#;
+    let mut child = testing::serve(env!("CARGO_BIN_EXE_grimoire"), &home, "", script, &["--daemon"], &bus);
+    let grimoire = bus.proxy(IFACE, &mut child);
+    assert!(!grimoire.get_property::<bool>("Visible").unwrap());
+    grimoire.call::<_, _, ()>("Show", &()).unwrap();
+    assert!(grimoire.get_property::<bool>("Visible").unwrap());
+    grimoire.call::<_, _, ()>("Hide", &()).unwrap();
+    assert!(!grimoire.get_property::<bool>("Visible").unwrap());
+    // It exits before it could answer
+    grimoire.call_noreply("Quit", &()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+}
+
+#[test]
+fn dbus_show_needs_daemon() {
+    let home = scratch("dbus-no-daemon");
+    let bus = Bus::start();
+    let script = r#"steps = [{ serve_until = "exit" }]"#;
+    let mut child = testing::serve(env!("CARGO_BIN_EXE_grimoire"), &home, "", script, &["--dmenu"], &bus);
+    let grimoire = bus.proxy(IFACE, &mut child);
+    let err = grimoire.call::<_, _, ()>("Show", &()).unwrap_err();
+    assert!(matches!(err, zbus::Error::MethodError(_, Some(ref msg), _) if msg == "not running with --daemon"), "{err}");
+    assert!(grimoire.get_property::<bool>("Visible").unwrap());
+    grimoire.call_noreply("Quit", &()).unwrap();
+    assert!(child.wait_with_output().unwrap().status.success());
+}
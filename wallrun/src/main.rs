@@ -1,40 +1,39 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
-use cosmic_text::{Attrs, Buffer, FontSystem, Metrics, Shaping, SwashCache, SwashContent};
-use serde::Deserialize;
+use cosmic_text::Weight;
+use serde::{Deserialize, Serialize};
 use smithay_client_toolkit as sctk;
-use sctk::reexports::calloop::{EventLoop, LoopHandle};
-use sctk::reexports::calloop_wayland_source::WaylandSource;
-use sctk::compositor::{CompositorHandler, CompositorState};
-use sctk::output::{OutputHandler, OutputState};
-use sctk::registry::{ProvidesRegistryState, RegistryState};
-use sctk::registry_handlers;
-use sctk::seat::keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers};
-use sctk::seat::pointer::{PointerEvent, PointerEventKind, PointerHandler};
-use sctk::seat::pointer::cursor_shape::CursorShapeManager;
-use sctk::seat::{Capability, SeatHandler, SeatState};
+use sctk::reexports::calloop::EventLoop;
 use sctk::reexports::protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
-use sctk::shell::wlr_layer::{
-    KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
-    LayerSurfaceConfigure,
-};
-use sctk::shell::WaylandSurface;
-use sctk::shm::slot::SlotPool;
-use sctk::shm::{Shm, ShmHandler};
-use sctk::{
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
-    delegate_registry, delegate_seat, delegate_shm,
-};
-use wayland_client::globals::registry_queue_init;
-use wayland_client::protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface};
+use sctk::seat::keyboard::{KeyEvent, Modifiers};
+use sctk::seat::pointer::{PointerEvent, PointerEventKind};
+use sctk::shell::wlr_layer::KeyboardInteractivity;
 use tiny_skia::Pixmap;
-use wayland_client::{Connection, QueueHandle};
+use widgets_core::color::{load_colors, Palette};
+use widgets_core::cli::{self, Opt, Parsed, Spec};
+use widgets_core::clipboard::Clip;
+use widgets_core::config::{cache_dir, load_config, Documented};
+use widgets_core::dbus::{self, Remote};
+use widgets_core::edit::{Change, LineEdit};
+use widgets_core::headless::render_to_png;
+use widgets_core::ipc;
+use widgets_core::keys::{self, Action, Keys, Spawn};
+use widgets_core::replay;
+use widgets_core::watch;
+use widgets_core::render::{blit_rgba, clear_rect, faded, fill_rect, fill_rect_alpha, Rect};
+use widgets_core::style::{fill_rounded, Backdrop, Style, STYLE_DOC};
+use widgets_core::text::{font_db, load_font, Fonts, LINE_HEIGHT};
+use widgets_core::trace;
+use widgets_core::window::{Host, Options, Placement, TextInput, Update, Widget, Window, PLACEMENT_KEYS};
+use zbus::blocking::connection::Builder;
+use zbus::{fdo, interface};
 
 // --- Config ---
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 #[serde(untagged)]
 enum Dimension {
     Fixed(u32),
@@ -45,7 +44,7 @@ impl Default for Dimension {
     fn default() -> Self { Dimension::Fixed(0) }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 #[serde(default)]
 struct Config {
     columns: usize,
@@ -56,111 +55,84 @@ struct Config {
     color_file: Option<String>,
     show_labels: bool,
     font: String,
+    font_fallback: Vec<String>,
+    output: Option<String>,
+    keys: Keys,
+    style: Style,
+    #[serde(flatten)]
+    placement: Placement,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self { columns: 3, window_width: Dimension::Fixed(800), window_height: Dimension::Fixed(600),
                font_size: 20.0, label_font_size: 14.0, color_file: None, show_labels: true,
-               font: "~/.local/share/fonts/GoogleSansCode-Regular.ttf".into() }
+               font: "~/.local/share/fonts/GoogleSansCode-Regular.ttf".into(), font_fallback: Vec::new(), output: None,
+               keys: Keys::default(), style: Style::default(), placement: Placement::default() }
     }
 }
 
-fn load_config() -> Config {
-    let base = std::env::var("XDG_CONFIG_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from(std::env::var("HOME").unwrap()).join(".config"));
-    let path = base.join("widgets/wallrun.toml");
-    let content = match std::fs::read_to_string(&path) {
-        Ok(s) => s,
-        Err(_) => return Config::default(),
-    };
-    match toml::from_str(&content) {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            eprintln!("wallrun: failed to parse {}: {e}", path.display());
-            Config::default()
-        }
+impl Documented for Config {
+    type Colors = Colors;
+    const FLATTENED: &'static [(&'static str, &'static str)] = PLACEMENT_KEYS;
+    const KEYS: &'static [(&'static str, &'static str)] = &[
+        ("color_file", "walrs color file; unset uses the built-in palette"),
+        ("font", "Font file or family name for all text"),
+        ("font_fallback", "Fonts (files or family names) for characters `font` lacks, before the system's fallbacks"),
+        ("font_size", "Search text size in logical pixels"),
+        ("label_font_size", "Thumbnail label size in logical pixels"),
+        ("show_labels", "Show file names under thumbnails"),
+        ("columns", "Grid columns"),
+        ("window_width", "Panel width in logical pixels, or \"fit\" to size from columns"),
+        ("window_height", "Panel height in logical pixels, or \"fit\" to show every wallpaper"),
+        ("output", "Monitor: connector name, \"focused\" or \"cursor\" (both need Hyprland); unset lets the compositor choose"),
+        ("keys", "Key bindings, combo = action, layered over these defaults; \"none\" unbinds"),
+        ("style", STYLE_DOC),
+    ];
+    fn color_file(&self) -> Option<&str> { self.color_file.as_deref() }
+    fn fonts(&self) -> Vec<(&'static str, String)> {
+        let fallback = self.font_fallback.iter().map(|f| ("font_fallback", f.clone()));
+        std::iter::once(("font", self.font.clone())).chain(fallback).collect()
     }
 }
 
 // --- Colors ---
 
 struct Colors {
-    background: [u8; 3],
-    background_alpha: u8,
-    bar_bg: [u8; 3],
-    bar_border: [u8; 3],
-    text: [u8; 3],
-    text_placeholder: [u8; 3],
-    label: [u8; 3],
-    selection: [u8; 3],
-    selection_alpha: u8,
+    background: [u8; 4],
+    bar_bg: [u8; 4],
+    bar_border: [u8; 4],
+    text: [u8; 4],
+    text_placeholder: [u8; 4],
+    label: [u8; 4],
+    selection: [u8; 4],
 }
 
 impl Default for Colors {
     fn default() -> Self {
         Self {
-            background: [0x1a, 0x1a, 0x2e], background_alpha: 0xff,
-            bar_bg: [0x2a, 0x2a, 0x4e],
-            bar_border: [0x4a, 0x4a, 0x6e], text: [0xe0, 0xe0, 0xe0],
-            text_placeholder: [0x80, 0x80, 0x80], label: [0xc0, 0xc0, 0xc0],
-            selection: [0x40, 0x40, 0x90], selection_alpha: 0xff,
+            background: [0x1a, 0x1a, 0x2e, 0xff],
+            bar_bg: [0x2a, 0x2a, 0x4e, 0xff],
+            bar_border: [0x4a, 0x4a, 0x6e, 0xff], text: [0xe0, 0xe0, 0xe0, 0xff],
+            text_placeholder: [0x80, 0x80, 0x80, 0xff], label: [0xc0, 0xc0, 0xc0, 0xff],
+            selection: [0x40, 0x40, 0x90, 0xff],
         }
     }
 }
 
-fn parse_hex(s: &str) -> Option<[u8; 3]> {
-    let s = s.strip_prefix('#').unwrap_or(s);
-    if s.len() != 6 { return None; }
-    Some([u8::from_str_radix(&s[0..2], 16).ok()?,
-          u8::from_str_radix(&s[2..4], 16).ok()?,
-          u8::from_str_radix(&s[4..6], 16).ok()?])
-}
-
-fn expand_path(p: &str) -> PathBuf {
-    if let Some(rest) = p.strip_prefix("~/") {
-        PathBuf::from(std::env::var("HOME").unwrap()).join(rest)
-    } else { PathBuf::from(p) }
-}
-
-fn load_colors(path: Option<&str>) -> Colors {
-    let mut colors = Colors::default();
-    let content = match path {
-        Some(p) => std::fs::read_to_string(expand_path(p)).unwrap_or_default(),
-        None => return colors,
-    };
-    for line in content.lines() {
-        if let Some((key, val)) = line.split_once('=') {
-            let (key, val) = (key.trim(), val.trim());
-            match key {
-                "background_opacity" | "selection_opacity" => {
-                    if let Ok(f) = val.parse::<f32>() {
-                        let a = (f.clamp(0.0, 1.0) * 255.0) as u8;
-                        match key {
-                            "background_opacity" => colors.background_alpha = a,
-                            _ => colors.selection_alpha = a,
-                        }
-                    }
-                }
-                _ => {
-                    if let Some(c) = parse_hex(val) {
-                        match key {
-                            "background" => colors.background = c,
-                            "bar_bg" => colors.bar_bg = c,
-                            "bar_border" => colors.bar_border = c,
-                            "text" => colors.text = c,
-                            "text_placeholder" => colors.text_placeholder = c,
-                            "label" => colors.label = c,
-                            "selection" => colors.selection = c,
-                            _ => {}
-                        }
-                    }
-                }
-            }
-        }
+impl Palette for Colors {
+    fn color(&mut self, key: &str) -> Option<&mut [u8; 4]> {
+        Some(match key {
+            "background" => &mut self.background,
+            "bar_bg" => &mut self.bar_bg,
+            "bar_border" => &mut self.bar_border,
+            "text" => &mut self.text,
+            "text_placeholder" => &mut self.text_placeholder,
+            "label" => &mut self.label,
+            "selection" => &mut self.selection,
+            _ => return None,
+        })
     }
-    colors
 }
 
 // --- App ---
@@ -174,32 +146,51 @@ struct Item {
 }
 
 struct App {
-    registry_state: RegistryState,
-    seat_state: SeatState,
-    output_state: OutputState,
-    shm: Shm,
-    layer: LayerSurface,
-    keyboard: Option<wl_keyboard::WlKeyboard>,
-    pointer: Option<wl_pointer::WlPointer>,
-    cursor_shape_manager: CursorShapeManager,
-    pool: SlotPool,
     width: u32,
     height: u32,
-    exit: bool,
-    input: String,
-    font_system: FontSystem,
-    swash_cache: SwashCache,
-    loop_handle: LoopHandle<'static, App>,
+    scale: f32,
+    input: LineEdit,
+    /// Where the search caret was last drawn, for the input method.
+    caret: Rect,
+    fonts: Fonts,
     items: Vec<Item>,
     filtered: Vec<usize>,
     selected: usize,
     scroll_offset: usize,
     cols: usize,
     colors: Colors,
+    /// Corner radius of the selection outline, in logical pixels.
+    selection_radius: f32,
     font_size: f32,
     label_font_size: f32,
     show_labels: bool,
     font_family: String,
+    keys: Keys,
+    /// Starts `run:` commands.
+    spawn: Spawn,
+    /// Marked items (indices into `items`), in the order they were marked.
+    marked: Vec<usize>,
+    shown: Option<Shown>,
+    /// Directory and extensions `items` was loaded from.
+    source: Option<(String, Vec<String>)>,
+    /// `msg` client to answer instead of stdout (daemon mode).
+    client: Option<UnixStream>,
+    /// A `copy` for the window to take.
+    clip: Option<Clip>,
+    /// Config as read from disk, and the arguments layered over it for the
+    /// current run or `msg show`; kept to re-apply on reload.
+    config: Config,
+    opts: Parsed,
+}
+
+/// What the last frame showed, to work out which cells need repainting.
+#[derive(PartialEq)]
+struct Shown {
+    input: LineEdit,
+    start: usize,
+    items: Vec<usize>,
+    selected: usize,
+    marked: Vec<usize>,
 }
 
 const PAD: f32 = 16.0;
@@ -207,6 +198,84 @@ const CELL_PAD: f32 = 12.0;
 const BAR_H: u32 = 50;
 
 impl App {
+    /// Paths to act on: the marked items, or else the selected one.
+    fn chosen(&self) -> Vec<String> {
+        let items = if self.marked.is_empty() { self.filtered.get(self.selected).copied().into_iter().collect() } else { self.marked.clone() };
+        items.into_iter().map(|i| self.items[i].path.display().to_string()).collect()
+    }
+
+    /// Print the chosen paths: to the `msg` client in daemon mode, stdout otherwise.
+    fn emit_selected(&mut self) {
+        for path in self.chosen() {
+            match &mut self.client {
+                Some(stream) => { writeln!(stream, "{path}").ok(); }
+                None => println!("{path}"),
+            }
+        }
+    }
+
+    /// Copy the selected file itself, as its image type.
+    fn copy_image(&mut self) -> Update {
+        let path = &self.items[self.filtered[self.selected]].path;
+        let mime = image::ImageFormat::from_path(path).map_or("application/octet-stream", |f| f.to_mime_type());
+        match std::fs::read(path) {
+            Ok(data) => { self.clip = Some(Clip::bytes(mime, data)); Update::Exit }
+            Err(e) => { eprintln!("wallrun: cannot read {}: {e}", path.display()); Update::None }
+        }
+    }
+
+    /// Apply one invocation's options, (re)loading items if the directory
+    /// changed; without `--dir` the daemon keeps showing what it has.
+    /// Returns the logical window size.
+    fn open(&mut self, cfg: &Config, inv: &Invocation) -> (u32, u32) {
+        let width = window_width(cfg);
+        self.cols = cfg.columns.max(1);
+        self.width = content_size(cfg, width, self.scale);
+        let source = match &inv.dir {
+            Some(dir) => Some((dir.clone(), inv.exts.clone())),
+            None => self.source.clone(),
+        };
+        if source != self.source {
+            let (_, _, thumb_w, thumb_h, _, _, _) = self.grid_metrics();
+            self.items = match &source {
+                Some((dir, exts)) => load_items(dir, exts, thumb_w, thumb_h),
+                None => Vec::new(),
+            };
+            self.source = source;
+        }
+        let height = window_height(cfg, width, self.items.len());
+        self.height = content_size(cfg, height, self.scale);
+        self.input.set(inv.input.clone().unwrap_or_default());
+        self.marked.clear();
+        self.refilter();
+        (width, height)
+    }
+
+    /// Re-apply colors, fonts and layout after the config or color file
+    /// changed. Returns the logical window size.
+    fn apply_config(&mut self, cfg: &Config, colors: Colors) -> (u32, u32) {
+        self.colors = colors;
+        let mut db = font_db();
+        match load_font(&mut db, &cfg.font) {
+            Some(family) => { self.fonts = Fonts::new(db, &cfg.font_fallback); self.font_family = family; }
+            None => eprintln!("wallrun: failed to load font {}", cfg.font),
+        }
+        self.font_size = cfg.font_size;
+        self.label_font_size = cfg.label_font_size;
+        self.show_labels = cfg.show_labels;
+        self.keys = cfg.keys.clone();
+        self.selection_radius = cfg.style.selection_radius;
+        let width = window_width(cfg);
+        let grid = (self.cols, self.width);
+        self.cols = cfg.columns.max(1);
+        self.width = content_size(cfg, width, self.scale);
+        if (self.cols, self.width) != grid { self.reload_thumbnails(); }
+        let height = window_height(cfg, width, self.items.len());
+        self.height = content_size(cfg, height, self.scale);
+        self.ensure_visible();
+        (width, height)
+    }
+
     fn effective_cols(&self) -> usize {
         let n = self.filtered.len();
         if n == 0 { return self.cols; }
@@ -215,12 +284,13 @@ impl App {
     }
 
     fn grid_metrics(&self) -> (f32, f32, u32, u32, f32, f32, usize) {
-        let grid_top = BAR_H as f32 + 12.0;
-        let cell_w = (self.width as f32 - PAD * 2.0) / self.cols as f32;
-        let thumb_w = (cell_w - CELL_PAD) as u32;
+        let s = self.scale;
+        let grid_top = (BAR_H as f32 + 12.0) * s;
+        let cell_w = (self.width as f32 - PAD * 2.0 * s) / self.cols as f32;
+        let thumb_w = (cell_w - CELL_PAD * s) as u32;
         let thumb_h = (thumb_w as f32 * 0.67) as u32;
-        let label_h = if self.show_labels { 28.0f32 } else { 0.0 };
-        let cell_h = thumb_h as f32 + label_h + CELL_PAD;
+        let label_h = if self.show_labels { 28.0 * s } else { 0.0 };
+        let cell_h = thumb_h as f32 + label_h + CELL_PAD * s;
         let rows = ((self.height as f32 - grid_top) / cell_h).max(0.0) as usize;
         let visible = rows * self.effective_cols();
         (grid_top, cell_w, thumb_w, thumb_h, label_h, cell_h, visible)
@@ -233,7 +303,7 @@ impl App {
         let on_screen = (start + visible).min(self.filtered.len()) - start;
         let eff_cols = if on_screen == 0 { ecols } else { on_screen.min(ecols) };
         let x_off = (self.width as f32 - eff_cols as f32 * cell_w) / 2.0;
-        let total_rows = if on_screen == 0 { 0 } else { (on_screen + ecols - 1) / ecols };
+        let total_rows = if on_screen == 0 { 0 } else { on_screen.div_ceil(ecols) };
         let avail_h = self.height as f32 - grid_top;
         let grid_h = total_rows as f32 * cell_h;
         let y_off = if grid_h < avail_h { (avail_h - grid_h) / 2.0 } else { 0.0 };
@@ -256,436 +326,315 @@ impl App {
             (0..self.items.len()).collect()
         } else {
             (0..self.items.len())
-                .filter(|&i| fuzzy_match(&self.items[i].label, &self.input))
+                .filter(|&i| fuzzy_match(&self.items[i].label, self.input.text()))
                 .collect()
         };
         self.selected = 0;
         self.scroll_offset = 0;
     }
 
-    fn handle_key(&mut self, event: &KeyEvent) {
-        if event.keysym == Keysym::Escape {
-            self.exit = true;
-            return;
-        }
-        if event.keysym == Keysym::Return && !self.filtered.is_empty() {
-            println!("{}", self.items[self.filtered[self.selected]].path.display());
-            self.exit = true;
-            return;
-        }
+    fn handle_key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> Update {
         let n = self.filtered.len();
         let cols = self.effective_cols();
-        let changed = match event.keysym {
-            Keysym::BackSpace => {
-                if self.input.pop().is_some() { self.refilter(); true } else { false }
-            }
-            Keysym::Left => { self.selected = if self.selected > 0 { self.selected - 1 } else { n.saturating_sub(1) }; true }
-            Keysym::Right => { self.selected = if self.selected + 1 < n { self.selected + 1 } else { 0 }; true }
-            Keysym::Up => { self.selected = if self.selected >= cols { self.selected - cols } else { (n.saturating_sub(1) / cols) * cols + self.selected % cols }; if self.selected >= n { self.selected = n.saturating_sub(1); } true }
-            Keysym::Down => { self.selected = if self.selected + cols < n { self.selected + cols } else { self.selected % cols }; if self.selected >= n { self.selected = 0; } true }
-            _ => match event.utf8 {
-                Some(ref text) if !text.is_empty() && text.chars().all(|c| !c.is_control()) => {
-                    self.input.push_str(text);
-                    self.refilter();
-                    true
+        let (_, _, _, _, _, _, visible) = self.grid_metrics();
+        let last = n.saturating_sub(1);
+        let changed = match self.keys.action(event, modifiers).cloned() {
+            Some(Action::Cancel) => return Update::Exit,
+            Some(Action::Select) if n > 0 => { self.emit_selected(); return Update::Exit; }
+            Some(Action::Run(cmd)) if n > 0 => { (self.spawn)(&cmd, &self.chosen()); return Update::Exit; }
+            Some(Action::Copy) if n > 0 => { self.clip = Some(Clip::text(&self.chosen().join("\n"))); return Update::Exit; }
+            Some(Action::CopyImage) if n > 0 => return self.copy_image(),
+            Some(Action::Paste) => return Update::Paste,
+            // A single-column grid has no use for Left/Right, so they edit
+            Some(Action::Left) if self.cols == 1 => self.edit(&Action::CaretLeft, modifiers.shift),
+            Some(Action::Right) if self.cols == 1 => self.edit(&Action::CaretRight, modifiers.shift),
+            Some(Action::Left | Action::Prev) => { self.selected = if self.selected > 0 { self.selected - 1 } else { last }; true }
+            Some(Action::Right | Action::Next) => { self.selected = if self.selected + 1 < n { self.selected + 1 } else { 0 }; true }
+            Some(Action::Up) => { self.selected = if self.selected >= cols { self.selected - cols } else { (last / cols) * cols + self.selected % cols }; if self.selected >= n { self.selected = last; } true }
+            Some(Action::Down) => { self.selected = if self.selected + cols < n { self.selected + cols } else { self.selected % cols }; if self.selected >= n { self.selected = 0; } true }
+            Some(Action::PageUp) => { self.selected = self.selected.saturating_sub(visible.max(1)); true }
+            Some(Action::PageDown) => { self.selected = (self.selected + visible.max(1)).min(last); true }
+            Some(Action::First) => { self.selected = 0; true }
+            Some(Action::Last) => { self.selected = last; true }
+            Some(Action::ToggleMark) if n > 0 => {
+                let item = self.filtered[self.selected];
+                match self.marked.iter().position(|&m| m == item) {
+                    Some(pos) => { self.marked.remove(pos); }
+                    None => self.marked.push(item),
                 }
-                _ => false,
+                if self.selected < last { self.selected += 1; }
+                true
+            }
+            Some(action) => self.edit(&action, modifiers.shift),
+            None => match keys::typed(event) {
+                Some(text) => { self.input.insert(text); self.refilter(); true }
+                None => false,
             },
         };
-        if changed {
-            self.ensure_visible();
-            self.draw();
-        }
+        if !changed { return Update::None; }
+        self.ensure_visible();
+        Update::Redraw
     }
 
-    fn draw(&mut self) {
-        let (grid_top, cell_w, thumb_w, thumb_h, label_h, cell_h, visible) = self.grid_metrics();
-        let (x_off, y_off) = self.grid_offsets();
-        let cols = self.effective_cols();
-        let c = &self.colors;
-        let bg = c.background;
-        let bar_bg = c.bar_bg;
-        let bar_border = c.bar_border;
-        let text_color = c.text;
-        let label_color = c.label;
-        let sel_color = c.selection;
-
-        let stride = self.width as i32 * 4;
-        let (wl_buf, canvas) = self.pool
-            .create_buffer(self.width as i32, self.height as i32, stride, wl_shm::Format::Argb8888)
-            .unwrap();
+    /// Apply a search-bar action; returns whether anything changed.
+    fn edit(&mut self, action: &Action, extend: bool) -> bool {
+        match self.input.apply(action, extend) {
+            Change::None => false,
+            Change::Caret => true,
+            Change::Text => { self.refilter(); true }
+        }
+    }
 
-        let mut pixmap = Pixmap::new(self.width, self.height).unwrap();
-        pixmap.fill(tiny_skia::Color::from_rgba8(bg[0], bg[1], bg[2], c.background_alpha));
+    /// Input method text goes into the search like typed text.
+    fn text_input(&mut self, event: &TextInput) -> Update {
+        match self.input.input_method(event) {
+            Change::None => return Update::None,
+            Change::Caret => {}
+            Change::Text => self.refilter(),
+        }
+        Update::Redraw
+    }
 
-        let pw = pixmap.width();
-        let ph = pixmap.height();
+    /// Pasted text goes into the search, up to the first line break.
+    fn paste(&mut self, text: &str) -> Update {
+        if self.input.insert(text.lines().next().unwrap_or_default()) == Change::None { return Update::None; }
+        self.refilter();
+        Update::Redraw
+    }
 
-        // Search bar
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, 0, 0, self.width, BAR_H, bar_bg, c.background_alpha);
-
-        // Window outline
-        fill_rect(pixmap.data_mut(), pw, ph, 0, 0, self.width, 2, bar_border);
-        fill_rect(pixmap.data_mut(), pw, ph, 0, self.height - 2, self.width, 2, bar_border);
-        fill_rect(pixmap.data_mut(), pw, ph, 0, 0, 2, self.height, bar_border);
-        fill_rect(pixmap.data_mut(), pw, ph, self.width - 2, 0, 2, self.height, bar_border);
-
-        if !self.input.is_empty() {
-            let text_y = (BAR_H as f32 + self.font_size) / 2.0;
-            let text_w = measure_text(&mut self.font_system, &self.input, self.font_size, &self.font_family);
-            let text_x = (self.width as f32 - text_w) / 2.0;
-            render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                &self.input, text_x, text_y, self.font_size, self.width as f32, BAR_H as f32, text_color,
-                &self.font_family);
-        }
+    fn item_at_pos(&self, mx: f32, my: f32) -> Option<usize> {
+        let (grid_top, cell_w, _, _, _, cell_h, _) = self.grid_metrics();
+        let (x_off, y_off) = self.grid_offsets();
+        let ecols = self.effective_cols();
+        if mx < x_off || my <= grid_top + y_off { return None; }
+        let row = ((my - grid_top - y_off) / cell_h) as usize;
+        let col = ((mx - x_off) / cell_w) as usize;
+        if col >= ecols { return None; }
+        let idx = self.scroll_offset + row * ecols + col;
+        if idx < self.filtered.len() { Some(idx) } else { None }
+    }
 
-        // Grid
-        let start = self.scroll_offset;
-        let end = (start + visible).min(self.filtered.len());
-
-        for i in start..end {
-            let vis_pos = (i - start) as u32;
-            let item_idx = self.filtered[i];
-            let col = vis_pos % cols as u32;
-            let row = vis_pos / cols as u32;
-            let cx = x_off + col as f32 * cell_w + CELL_PAD / 2.0;
-            let cy = grid_top + y_off + row as f32 * cell_h;
-
-            let tw = self.items[item_idx].thumb_w;
-            let th = self.items[item_idx].thumb_h;
-            let tx = cx + (thumb_w as f32 - tw as f32) / 2.0;
-            let ty = cy + (thumb_h as f32 - th as f32) / 2.0;
-            blit_rgba(pixmap.data_mut(), pw as i32, ph as i32,
-                tx as i32, ty as i32, tw as i32, th as i32, &self.items[item_idx].thumb_data);
-
-            if i == self.selected {
-                let bw: u32 = 2;
-                let bx = (tx as u32).saturating_sub(bw);
-                let by = (ty as u32).saturating_sub(bw);
-                let bwidth = tw + bw * 2;
-                let bheight = th + bw * 2;
-                // top
-                fill_rect(pixmap.data_mut(), pw, ph, bx, by, bwidth, bw, sel_color);
-                // bottom
-                fill_rect(pixmap.data_mut(), pw, ph, bx, by + bheight - bw, bwidth, bw, sel_color);
-                // left
-                fill_rect(pixmap.data_mut(), pw, ph, bx, by, bw, bheight, sel_color);
-                // right
-                fill_rect(pixmap.data_mut(), pw, ph, bx + bwidth - bw, by, bw, bheight, sel_color);
+    fn handle_pointer(&mut self, event: &PointerEvent) -> Update {
+        let (mx, my) = (event.position.0 as f32, event.position.1 as f32);
+        match event.kind {
+            PointerEventKind::Press { button: 0x110, .. } => {
+                if let Some(idx) = self.item_at_pos(mx, my) {
+                    self.selected = idx;
+                    self.emit_selected();
+                    return Update::Exit;
+                }
             }
-
-            if self.show_labels {
-                render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                    &self.items[item_idx].label, cx, cy + thumb_h as f32 + 4.0,
-                    self.label_font_size, thumb_w as f32, label_h, label_color,
-                    &self.font_family);
+            PointerEventKind::Motion { .. } => {
+                if let Some(idx) = self.item_at_pos(mx, my) && idx != self.selected {
+                    self.selected = idx;
+                    return Update::Redraw;
+                }
             }
+            PointerEventKind::Axis { ref vertical, .. } => {
+                let (_, _, _, _, _, _, visible) = self.grid_metrics();
+                let cols = self.effective_cols();
+                if vertical.absolute > 0.0 && self.scroll_offset + visible < self.filtered.len() {
+                    self.scroll_offset = (self.scroll_offset + cols)
+                        .min(self.filtered.len().saturating_sub(visible));
+                    return Update::Redraw;
+                } else if vertical.absolute < 0.0 && self.scroll_offset > 0 {
+                    self.scroll_offset = self.scroll_offset.saturating_sub(cols);
+                    return Update::Redraw;
+                }
+            }
+            _ => {}
         }
+        Update::None
+    }
 
-        // Copy RGBA premul -> BGRA (ARGB8888 on LE)
-        for (dst, src) in canvas.chunks_exact_mut(4).zip(pixmap.data().chunks_exact(4)) {
-            dst[0] = src[2];
-            dst[1] = src[1];
-            dst[2] = src[0];
-            dst[3] = src[3];
-        }
-
-        wl_buf.attach_to(self.layer.wl_surface()).unwrap();
-        self.layer.wl_surface().damage_buffer(0, 0, self.width as i32, self.height as i32);
-        self.layer.wl_surface().commit();
+    /// Text cursor over the search bar, a hand over entries.
+    fn cursor(&self, x: f64, y: f64) -> Shape {
+        if (y as f32) < BAR_H as f32 * self.scale { return Shape::Text; }
+        if self.item_at_pos(x as f32, y as f32).is_some() { Shape::Pointer } else { Shape::Default }
     }
-}
 
-// --- Rendering helpers ---
+    /// Re-rasterize thumbnails for the current grid size and device scale.
+    fn reload_thumbnails(&mut self) {
+        let (_, _, thumb_w, thumb_h, _, _, _) = self.grid_metrics();
+        let cd = thumb_cache_dir();
+        for item in &mut self.items {
+            if let Some((data, w, h)) = load_thumbnail(&item.path, &cd, thumb_w, thumb_h) {
+                (item.thumb_data, item.thumb_w, item.thumb_h) = (data, w, h);
+            }
+        }
+    }
 
-fn fuzzy_match(haystack: &str, needle: &str) -> bool {
-    let h = haystack.to_lowercase();
-    let n = needle.to_lowercase();
-    let mut hi = h.chars();
-    for nc in n.chars() {
-        if !hi.any(|hc| hc == nc) { return false; }
+    fn visible_range(&self) -> std::ops::Range<usize> {
+        let (_, _, _, _, _, _, visible) = self.grid_metrics();
+        let start = self.scroll_offset;
+        start..(start + visible).min(self.filtered.len())
     }
-    true
-}
 
-fn fill_rect_alpha(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3], a: u8) {
-    if a == 0xff { return fill_rect(data, pw, ph, x, y, w, h, c); }
-    let a = a as u32;
-    let inv = 255 - a;
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i]     = ((c[0] as u32 * a + data[i] as u32 * inv) / 255) as u8;
-            data[i + 1] = ((c[1] as u32 * a + data[i + 1] as u32 * inv) / 255) as u8;
-            data[i + 2] = ((c[2] as u32 * a + data[i + 2] as u32 * inv) / 255) as u8;
-            data[i + 3] = ((a + data[i + 3] as u32 * inv / 255)) as u8;
+    fn snapshot(&self) -> Shown {
+        let range = self.visible_range();
+        Shown {
+            input: self.input.clone(), start: range.start, items: self.filtered[range].to_vec(),
+            selected: self.selected, marked: self.marked.clone(),
         }
     }
-}
 
-fn fill_rect(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3]) {
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i] = c[0];
-            data[i + 1] = c[1];
-            data[i + 2] = c[2];
-            data[i + 3] = 0xff;
-        }
+    fn backdrop(&self) -> Backdrop {
+        let c = &self.colors;
+        Backdrop { background: c.background, border: c.bar_border }
     }
-}
 
-fn make_attrs(family: &str) -> Attrs<'_> {
-    Attrs::new().family(cosmic_text::Family::Name(family))
-}
+    /// Cell of filtered item `i` (must be visible), with room for the
+    /// selection outline and clipped to the window.
+    fn cell_rect(&self, i: usize) -> Rect {
+        let (grid_top, cell_w, _, _, _, cell_h, _) = self.grid_metrics();
+        let (x_off, y_off) = self.grid_offsets();
+        let s = self.scale;
+        let cols = self.effective_cols();
+        let vis_pos = i - self.scroll_offset;
+        let x = (x_off + (vis_pos % cols) as f32 * cell_w) as u32;
+        let y = (grid_top + y_off + (vis_pos / cols) as f32 * cell_h - CELL_PAD * s / 2.0) as u32;
+        let (x0, y0) = (x, y);
+        let x1 = (x + cell_w as u32).min(self.width);
+        let y1 = (y + cell_h as u32).min(self.height);
+        Rect { x: x0, y: y0, w: x1.saturating_sub(x0), h: y1.saturating_sub(y0) }
+    }
 
-fn measure_text(font_system: &mut FontSystem, text: &str, font_size: f32, family: &str) -> f32 {
-    let mut buf = Buffer::new(font_system, Metrics::new(font_size, font_size * 1.2));
-    buf.set_size(font_system, None, None);
-    buf.set_text(font_system, text, &make_attrs(family), Shaping::Advanced, None);
-    buf.shape_until_scroll(font_system, false);
-    buf.layout_runs().next().map_or(0.0, |r| r.line_w)
-}
+    fn draw(&mut self, pixmap: &mut Pixmap) {
+        let s = self.scale;
+        let bar_h = (BAR_H as f32 * s) as u32;
+        let c = &self.colors;
+        let bar_bg = faded(c.bar_bg, c.background[3]);
+        let text_color = c.text;
 
-fn render_text(
-    pixmap: &mut Pixmap, font_system: &mut FontSystem, swash_cache: &mut SwashCache,
-    text: &str, x: f32, y: f32, font_size: f32, max_w: f32, max_h: f32, color: [u8; 3],
-    family: &str,
-) {
-    let line_h = font_size * 1.2;
-    let mut buf = Buffer::new(font_system, Metrics::new(font_size, line_h));
-    buf.set_size(font_system, Some(max_w), Some(max_h));
-    buf.set_text(font_system, text, &make_attrs(family), Shaping::Advanced, None);
-    buf.shape_until_scroll(font_system, false);
-
-    let pw = pixmap.width() as i32;
-    let ph = pixmap.height() as i32;
-    for run in buf.layout_runs() {
-        for glyph in run.glyphs.iter() {
-            let physical = glyph.physical((x, y), 1.0);
-            if let Some(image) = swash_cache.get_image_uncached(font_system, physical.cache_key) {
-                let x0 = physical.x + image.placement.left;
-                let y0 = physical.y - image.placement.top;
-                let w = image.placement.width as i32;
-                let h = image.placement.height as i32;
-                match image.content {
-                    SwashContent::Mask => blit_mask(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data, &color),
-                    SwashContent::Color => blit_color(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data),
-                    SwashContent::SubpixelMask => {}
-                }
-            }
-        }
-    }
-}
+        let pw = pixmap.width();
+        let ph = pixmap.height();
+        // The panel shows through
+        clear_rect(pixmap.data_mut(), pw, ph, Rect { x: 0, y: 0, w: pw, h: ph }, [0; 3], 0);
 
-fn blit_mask(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, mask: &[u8], color: &[u8; 3]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let a = mask[(gy * w + gx) as usize] as u32;
-            if a == 0 { continue; }
-            let i = (py * pw + px) as usize * 4;
-            let inv = 255 - a;
-            data[i]     = ((color[0] as u32 * a + data[i] as u32 * inv) / 255) as u8;
-            data[i + 1] = ((color[1] as u32 * a + data[i + 1] as u32 * inv) / 255) as u8;
-            data[i + 2] = ((color[2] as u32 * a + data[i + 2] as u32 * inv) / 255) as u8;
-            data[i + 3] = ((a + data[i + 3] as u32 * inv / 255)) as u8;
-        }
-    }
-}
+        // Search bar
+        fill_rect(pixmap.data_mut(), pw, ph, 0, 0, self.width, bar_h, bar_bg);
+
+        let font_size = self.font_size * s;
+        let text_y = (bar_h as f32 - font_size * LINE_HEIGHT) / 2.0;
+        self.caret = self.input.draw(pixmap, &mut self.fonts, self.width as f32 / 2.0, text_y, font_size, &self.font_family,
+            text_color, self.colors.selection, s);
 
-fn blit_rgba(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, src: &[u8]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let si = (gy * w + gx) as usize * 4;
-            let di = (py * pw + px) as usize * 4;
-            data[di] = src[si];
-            data[di + 1] = src[si + 1];
-            data[di + 2] = src[si + 2];
-            data[di + 3] = src[si + 3];
+        // Grid
+        for i in self.visible_range() {
+            self.draw_cell(pixmap, i);
         }
+        self.shown = Some(self.snapshot());
     }
-}
 
-fn blit_color(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, rgba: &[u8]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let si = (gy * w + gx) as usize * 4;
-            let a = rgba[si + 3] as u32;
-            if a == 0 { continue; }
-            let i = (py * pw + px) as usize * 4;
-            let inv = 255 - a;
-            data[i]     = (rgba[si] as u32 * a / 255 + data[i] as u32 * inv / 255) as u8;
-            data[i + 1] = (rgba[si + 1] as u32 * a / 255 + data[i + 1] as u32 * inv / 255) as u8;
-            data[i + 2] = (rgba[si + 2] as u32 * a / 255 + data[i + 2] as u32 * inv / 255) as u8;
-            data[i + 3] = (a + data[i + 3] as u32 * inv / 255) as u8;
+    /// Moving the selection repaints the two cells involved; anything else
+    /// changes most of the window and gets a full draw.
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Option<Vec<Rect>> {
+        let now = self.snapshot();
+        let shown = self.shown.take()?;
+        if now == shown { self.shown = Some(now); return Some(Vec::new()); }
+        if now.input != shown.input || now.start != shown.start || now.items != shown.items
+            || now.marked != shown.marked { return None; }
+        let (pw, ph) = (pixmap.width(), pixmap.height());
+        let mut damage = Vec::new();
+        for i in [shown.selected, now.selected] {
+            if !self.visible_range().contains(&i) { continue; }
+            let rect = self.cell_rect(i);
+            clear_rect(pixmap.data_mut(), pw, ph, rect, [0; 3], 0);
+            self.draw_cell(pixmap, i);
+            damage.push(rect);
         }
+        self.shown = Some(now);
+        Some(damage)
     }
-}
 
-// --- Wayland handler boilerplate ---
+    /// Thumbnail, selection outline and label of filtered item `i` (must be visible).
+    fn draw_cell(&mut self, pixmap: &mut Pixmap, i: usize) {
+        let (grid_top, cell_w, thumb_w, thumb_h, _, cell_h, _) = self.grid_metrics();
+        let s = self.scale;
+        let bw = (2.0 * s).round() as u32;
+        let (x_off, y_off) = self.grid_offsets();
+        let cols = self.effective_cols();
+        let label_color = self.colors.label;
+        // Outlines and marks stay opaque; the selection's alpha is for text
+        let [r, g, b, _] = self.colors.selection;
+        let sel_color = [r, g, b];
+        let pw = pixmap.width();
+        let ph = pixmap.height();
 
-impl CompositorHandler for App {
-    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
-    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
-    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
-    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
-    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
-}
+        let vis_pos = (i - self.scroll_offset) as u32;
+        let item_idx = self.filtered[i];
+        let col = vis_pos % cols as u32;
+        let row = vis_pos / cols as u32;
+        let cx = x_off + col as f32 * cell_w + CELL_PAD * s / 2.0;
+        let cy = grid_top + y_off + row as f32 * cell_h;
+
+        let tw = self.items[item_idx].thumb_w;
+        let th = self.items[item_idx].thumb_h;
+        let tx = cx + (thumb_w as f32 - tw as f32) / 2.0;
+        let ty = cy + (thumb_h as f32 - th as f32) / 2.0;
+
+        // Selection outline: the thumbnail covers all but its edge
+        if i == self.selected {
+            let r = Rect { x: (tx as u32).saturating_sub(bw), y: (ty as u32).saturating_sub(bw), w: tw + bw * 2, h: th + bw * 2 };
+            fill_rounded(pixmap, r, self.selection_radius * s, sel_color, 0xff);
+        }
 
-impl OutputHandler for App {
-    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
-    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-}
+        blit_rgba(pixmap.data_mut(), pw as i32, ph as i32,
+            tx as i32, ty as i32, tw as i32, th as i32, &self.items[item_idx].thumb_data);
 
-impl SeatHandler for App {
-    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, capability: Capability) {
-        if capability == Capability::Keyboard && self.keyboard.is_none() {
-            self.keyboard = Some(self.seat_state.get_keyboard_with_repeat(
-                qh, &seat, None,
-                self.loop_handle.clone(),
-                Box::new(|state, _wl_kbd, event| {
-                    state.handle_key(&event);
-                }),
-            ).unwrap());
-        }
-        if capability == Capability::Pointer && self.pointer.is_none() {
-            self.pointer = Some(self.seat_state.get_pointer(qh, &seat).unwrap());
+        // Mark: a square in the thumbnail's top-left corner
+        if self.marked.contains(&item_idx) {
+            let size = (12.0 * s).round() as u32;
+            fill_rect_alpha(pixmap.data_mut(), pw, ph, tx as u32 + 2 * bw, ty as u32 + 2 * bw, size, size, sel_color, 0xff);
         }
-    }
-    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, _: Capability) {}
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-}
 
-impl KeyboardHandler for App {
-    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
-    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
-    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
-        self.handle_key(&event);
-    }
-    fn repeat_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
-        self.handle_key(&event);
+        if self.show_labels {
+            self.fonts.draw(pixmap, &self.items[item_idx].label, cx, cy + thumb_h as f32 + 4.0 * s,
+                self.label_font_size * s, thumb_w as f32, label_color,
+                &self.font_family, Weight::NORMAL);
+        }
     }
-    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
-    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: Modifiers, _: RawModifiers, _: u32) {}
 }
 
-impl PointerHandler for App {
-    fn pointer_frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, pointer: &wl_pointer::WlPointer, events: &[PointerEvent]) {
-        let mut redraw = false;
-        for event in events {
-            match event.kind {
-                PointerEventKind::Enter { serial, .. } => {
-                    let device = self.cursor_shape_manager.get_shape_device(pointer, qh);
-                    device.set_shape(serial, Shape::Default);
-                    device.destroy();
-                }
-                PointerEventKind::Press { button: 0x110, .. } => {
-                    let (grid_top, cell_w, _, _, _, cell_h, _) = self.grid_metrics();
-                    let (x_off, y_off) = self.grid_offsets();
-                    let ecols = self.effective_cols();
-                    let (mx, my) = (event.position.0 as f32, event.position.1 as f32);
-                    if mx >= x_off && my > grid_top + y_off {
-                        let row = ((my - grid_top - y_off) / cell_h) as usize;
-                        let col = ((mx - x_off) / cell_w) as usize;
-                        if col < ecols {
-                            let idx = self.scroll_offset + row * ecols + col;
-                            if idx < self.filtered.len() {
-                                self.selected = idx;
-                                println!("{}", self.items[self.filtered[idx]].path.display());
-                                self.exit = true;
-                                return;
-                            }
-                        }
-                    }
-                }
-                PointerEventKind::Motion { .. } => {
-                    let (grid_top, cell_w, _, _, _, cell_h, _) = self.grid_metrics();
-                    let (x_off, y_off) = self.grid_offsets();
-                    let ecols = self.effective_cols();
-                    let (mx, my) = (event.position.0 as f32, event.position.1 as f32);
-                    if mx >= x_off && my > grid_top + y_off {
-                        let row = ((my - grid_top - y_off) / cell_h) as usize;
-                        let col = ((mx - x_off) / cell_w) as usize;
-                        if col < ecols {
-                            let idx = self.scroll_offset + row * ecols + col;
-                            if idx < self.filtered.len() && idx != self.selected {
-                                self.selected = idx;
-                                redraw = true;
-                            }
-                        }
-                    }
-                }
-                PointerEventKind::Axis { ref vertical, .. } => {
-                    let (_, _, _, _, _, _, visible) = self.grid_metrics();
-                    let cols = self.effective_cols();
-                    if vertical.absolute > 0.0 && self.scroll_offset + visible < self.filtered.len() {
-                        self.scroll_offset = (self.scroll_offset + cols)
-                            .min(self.filtered.len().saturating_sub(visible));
-                        redraw = true;
-                    } else if vertical.absolute < 0.0 && self.scroll_offset > 0 {
-                        self.scroll_offset = self.scroll_offset.saturating_sub(cols);
-                        redraw = true;
-                    }
-                }
-                _ => {}
-            }
+impl Widget for App {
+    fn draw(&mut self, pixmap: &mut Pixmap) { self.draw(pixmap); }
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Option<Vec<Rect>> { self.draw_dirty(pixmap) }
+    fn resize(&mut self, width: u32, height: u32, scale: f32) {
+        let width_changed = width != self.width;
+        self.width = width;
+        self.height = height;
+        if scale != self.scale || width_changed {
+            self.scale = scale;
+            self.reload_thumbnails();
         }
-        if redraw { self.draw(); }
     }
+    fn backdrop(&self) -> Backdrop { self.backdrop() }
+    fn key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> Update { self.handle_key(event, modifiers) }
+    fn pointer(&mut self, event: &PointerEvent) -> Update { self.handle_pointer(event) }
+    fn cursor(&self, x: f64, y: f64) -> Shape { self.cursor(x, y) }
+    fn hidden(&mut self) { self.client = None; }
+    fn paste(&mut self, text: &str) -> Update { self.paste(text) }
+    fn text_cursor(&self) -> Option<Rect> { Some(self.caret) }
+    fn text_input(&mut self, event: &TextInput) -> Update { self.text_input(event) }
+    fn take_clip(&mut self) -> Option<Clip> { self.clip.take() }
 }
 
-impl ShmHandler for App {
-    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
-}
+// --- Fuzzy matching ---
 
-impl LayerShellHandler for App {
-    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
-        self.exit = true;
-    }
-    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
-        if configure.new_size.0 > 0 { self.width = configure.new_size.0; }
-        if configure.new_size.1 > 0 { self.height = configure.new_size.1; }
-        self.draw();
+fn fuzzy_match(haystack: &str, needle: &str) -> bool {
+    let h = haystack.to_lowercase();
+    let n = needle.to_lowercase();
+    let mut hi = h.chars();
+    for nc in n.chars() {
+        if !hi.any(|hc| hc == nc) { return false; }
     }
+    true
 }
 
-impl ProvidesRegistryState for App {
-    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
-    registry_handlers![OutputState, SeatState];
-}
-
-delegate_compositor!(App);
-delegate_output!(App);
-delegate_seat!(App);
-delegate_keyboard!(App);
-delegate_pointer!(App);
-delegate_shm!(App);
-delegate_layer!(App);
-delegate_registry!(App);
-
 // --- Thumbnail loading ---
 
-fn cache_dir() -> PathBuf {
-    let base = std::env::var("XDG_CACHE_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from(std::env::var("HOME").unwrap()).join(".cache"));
-    base.join("thumbnails/wallrun")
+fn thumb_cache_dir() -> PathBuf {
+    cache_dir().join("thumbnails/wallrun")
 }
 
 fn cache_key(path: &Path, thumb_w: u32, thumb_h: u32) -> Option<String> {
@@ -703,12 +652,10 @@ fn load_thumbnail(path: &Path, cache_dir: &Path, thumb_w: u32, thumb_h: u32) ->
     let key = cache_key(path, thumb_w, thumb_h)?;
     let cached = cache_dir.join(format!("{key}.png"));
 
-    if cached.exists() {
-        if let Ok(img) = image::open(&cached) {
-            let rgba = img.to_rgba8();
-            let (w, h) = rgba.dimensions();
-            return Some((rgba.into_raw(), w, h));
-        }
+    if cached.exists() && let Ok(img) = image::open(&cached) {
+        let rgba = img.to_rgba8();
+        let (w, h) = rgba.dimensions();
+        return Some((rgba.into_raw(), w, h));
     }
 
     let img = image::open(path).ok()?;
@@ -720,7 +667,7 @@ fn load_thumbnail(path: &Path, cache_dir: &Path, thumb_w: u32, thumb_h: u32) ->
 }
 
 fn load_items(dir: &str, exts: &[String], thumb_w: u32, thumb_h: u32) -> Vec<Item> {
-    let cd = cache_dir();
+    let cd = thumb_cache_dir();
     std::fs::create_dir_all(&cd).ok();
 
     let mut items = Vec::new();
@@ -736,7 +683,7 @@ fn load_items(dir: &str, exts: &[String], thumb_w: u32, thumb_h: u32) -> Vec<Ite
         };
         if !exts.iter().any(|e| e.eq_ignore_ascii_case(&ext)) { continue; }
         let label = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
-        match load_thumbnail(&path, &cd, thumb_w, thumb_h) {
+        match trace::span("thumbnails", || load_thumbnail(&path, &cd, thumb_w, thumb_h)) {
             Some((data, tw, th)) => items.push(Item { path, label, thumb_data: data, thumb_w: tw, thumb_h: th }),
             None => eprintln!("wallrun: skip {}", path.display()),
         }
@@ -747,109 +694,278 @@ fn load_items(dir: &str, exts: &[String], thumb_w: u32, thumb_h: u32) -> Vec<Ite
 
 // --- Main ---
 
-fn main() {
-    let cfg = load_config();
-    let colors = load_colors(cfg.color_file.as_deref());
-
-    let args: Vec<String> = std::env::args().collect();
-    let mut dir: Option<String> = None;
-    let mut exts: Vec<String> = ["png", "jpg", "jpeg", "webp"].iter().map(|s| s.to_string()).collect();
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--dir" if i + 1 < args.len() => { dir = Some(args[i + 1].clone()); i += 2; }
-            "--ext" if i + 1 < args.len() => { exts = args[i + 1].split(',').map(String::from).collect(); i += 2; }
-            _ => { eprintln!("wallrun: unknown arg: {}", args[i]); i += 1; }
+/// Per-invocation options, from the command line or `wallrun msg show`.
+struct Invocation {
+    dir: Option<String>,
+    exts: Vec<String>,
+    render_to: Option<String>,
+    replay: Option<String>,
+    input: Option<String>,
+    scale: f32,
+    daemon: bool,
+    dbus: bool,
+}
+
+const CLI: Spec = Spec {
+    name: "wallrun",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Wallpaper picker: prints the chosen image's path.",
+    usage: &["--dir DIR [OPTIONS]", "msg show --dir DIR [OPTIONS]", "msg hide|quit"],
+    opts: &[
+        Opt { name: "--dir", value: Some("DIR"), help: "Directory of wallpapers" },
+        Opt { name: "--ext", value: Some("LIST"), help: "Comma-separated extensions (default png,jpg,jpeg,webp)" },
+        Opt { name: "--input", value: Some("TEXT"), help: "Pre-fill the search bar" },
+        Opt { name: "--daemon", value: None, help: "Stay resident, hidden until `wallrun msg show`" },
+        Opt { name: "--replay", value: Some("SCRIPT"), help: "Feed scripted input without a compositor, then exit" },
+    ],
+};
+
+fn parse_args(opts: &Parsed, cfg: &mut Config) -> Result<Invocation, String> {
+    let mut inv = Invocation {
+        dir: None,
+        exts: ["png", "jpg", "jpeg", "webp"].iter().map(|s| s.to_string()).collect(),
+        render_to: None,
+        replay: None,
+        input: None,
+        scale: 1.0,
+        daemon: false,
+        dbus: false,
+    };
+    for (name, value) in opts {
+        let v = value.clone().unwrap_or_default();
+        match *name {
+            "--dir" => inv.dir = Some(v),
+            "--ext" => inv.exts = v.split(',').map(String::from).collect(),
+            "--render-to" => inv.render_to = Some(v),
+            "--replay" => inv.replay = Some(v),
+            "--input" => inv.input = Some(v),
+            "--scale" => inv.scale = cli::value(name, &v)?,
+            "--output" => cfg.output = Some(v),
+            "--set" => cli::set(cfg, &v)?,
+            "--daemon" => inv.daemon = true,
+            "--dbus" => inv.dbus = true,
+            _ => {}
         }
     }
+    Ok(inv)
+}
 
-    let cols = cfg.columns;
-
-    // Resolve width (fit = auto-size based on column count)
-    let width = match cfg.window_width {
+/// Logical panel width (fit = auto-size based on column count).
+fn window_width(cfg: &Config) -> u32 {
+    match cfg.window_width {
         Dimension::Fixed(w) => w,
-        Dimension::Auto(_) => (256.0 * cols as f32 + PAD * 2.0) as u32,
-    };
-
-    let cell_w = (width as f32 - PAD * 2.0) / cols as f32;
-    let thumb_w = (cell_w - CELL_PAD) as u32;
-    let thumb_h = (thumb_w as f32 * 0.67) as u32;
-
-    let items = match dir {
-        Some(ref d) => load_items(d, &exts, thumb_w, thumb_h),
-        None => Vec::new(),
-    };
+        Dimension::Auto(_) => (256.0 * cfg.columns.max(1) as f32 + PAD * 2.0 + cfg.style.inset() * 2.0) as u32,
+    }
+}
 
-    // Resolve height (fit = auto-size to show all items)
-    let height = match cfg.window_height {
+/// Logical panel height (fit = auto-size to show all `n` items).
+fn window_height(cfg: &Config, width: u32, n: usize) -> u32 {
+    let inset = cfg.style.inset() * 2.0;
+    let cols = cfg.columns.max(1);
+    match cfg.window_height {
         Dimension::Fixed(h) => h,
         Dimension::Auto(_) => {
-            let rows = if items.is_empty() { 1 } else { (items.len() + cols - 1) / cols };
+            let cell_w = (width as f32 - inset - PAD * 2.0) / cols as f32;
+            let thumb_h = ((cell_w - CELL_PAD) as u32 as f32 * 0.67) as u32;
+            let rows = if n == 0 { 1 } else { n.div_ceil(cols) };
             let grid_top = BAR_H as f32 + 12.0;
             let label_h = if cfg.show_labels { 28.0 } else { 0.0 };
             let cell_h = thumb_h as f32 + label_h + CELL_PAD;
-            (grid_top + rows as f32 * cell_h + CELL_PAD) as u32
+            (grid_top + rows as f32 * cell_h + CELL_PAD + inset) as u32
         }
-    };
+    }
+}
 
-    let conn = Connection::connect_to_env().unwrap();
-    let (globals, event_queue) = registry_queue_init::<App>(&conn).unwrap();
-    let qh = event_queue.handle();
+/// Content size in buffer pixels for a logical panel size: what the window
+/// will hand to `resize`, known ahead so thumbnails are made at that size.
+fn content_size(cfg: &Config, panel: u32, scale: f32) -> u32 {
+    ((panel as f32 - cfg.style.inset() * 2.0).max(1.0) * scale) as u32
+}
+
+/// Reload config and colors from disk; returns the files to keep watching.
+fn reload(win: &mut Window<App>) -> Vec<PathBuf> {
+    let base: Config = load_config("wallrun");
+    let colors: Colors = load_colors(base.color_file.as_deref());
+    let mut cfg = base.clone();
+    // Arguments were validated when they were first parsed
+    parse_args(&win.widget.opts, &mut cfg).ok();
+    (win.opts.width, win.opts.height) = win.widget.apply_config(&cfg, colors);
+    win.opts.placement = cfg.placement.clone();
+    win.opts.style = cfg.style.clone();
+    win.widget.config = base;
+    win.reload();
+    watch::config_files("wallrun", cfg.color_file.as_deref())
+}
+
+/// Reopen the resident window for `msg show` (`args` as on the command
+/// line) or D-Bus `Show`.
+fn show(win: &mut dyn Host<Widget = App>, args: &[String]) -> Result<(), String> {
+    let mut cfg = win.widget().config.clone();
+    let opts = CLI.parse(args)?;
+    let inv = parse_args(&opts, &mut cfg)?;
+    win.widget().opts = opts;
+    win.hide();
+    let (width, height) = win.widget().open(&cfg, &inv);
+    (win.opts().width, win.opts().height) = (width, height);
+    win.opts().output = cfg.output;
+    win.opts().placement = cfg.placement;
+    win.opts().style = cfg.style;
+    win.show();
+    Ok(())
+}
+
+struct Control(Remote<App>);
+
+#[interface(name = "org.widgets.Wallrun")]
+impl Control {
+    /// As `msg show` with no options; only with `--daemon`.
+    async fn show(&self) -> fdo::Result<()> {
+        self.0.call(|win| {
+            if !win.opts().resident { return Err("not running with --daemon".to_string()); }
+            show(win, &[])
+        }).await?.map_err(fdo::Error::Failed)
+    }
 
-    let mut event_loop: EventLoop<App> = EventLoop::try_new().unwrap();
-    let loop_handle = event_loop.handle();
-    WaylandSource::new(conn.clone(), event_queue).insert(loop_handle).unwrap();
+    async fn hide(&self) -> fdo::Result<()> { self.0.call(|win| win.dismiss()).await }
 
-    let compositor = CompositorState::bind(&globals, &qh).unwrap();
-    let layer_shell = LayerShell::bind(&globals, &qh).unwrap();
-    let shm = Shm::bind(&globals, &qh).unwrap();
-    let cursor_shape_manager = CursorShapeManager::bind(&globals, &qh).unwrap();
+    async fn quit(&self) -> fdo::Result<()> { self.0.call(|win| win.quit()).await }
 
-    let surface = compositor.create_surface(&qh);
-    let layer = layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("wallrun"), None);
-    layer.set_size(width, height);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
-    layer.wl_surface().commit();
+    #[zbus(property)]
+    async fn visible(&self) -> fdo::Result<bool> { self.0.call(|win| win.shown()).await }
+}
 
-    let pool = SlotPool::new((width * height * 4) as usize, &shm).unwrap();
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "msg") {
+        // Validate here so mistakes are reported by the client, not the daemon
+        let mut request = args[1..].to_vec();
+        match args.get(1).map(String::as_str) {
+            Some("show") => {
+                let opts = CLI.parse_or_exit(&args[2..]);
+                if let Err(e) = parse_args(&opts, &mut Config::default()) { CLI.fail(&e); }
+                // The daemon has its own working directory
+                request = vec!["show".into()];
+                for (name, value) in opts {
+                    request.push(name.into());
+                    let Some(v) = value else { continue };
+                    request.push(match name {
+                        "--dir" => std::path::absolute(&v).map_or(v, |p| p.display().to_string()),
+                        _ => v,
+                    });
+                }
+            }
+            Some("hide" | "quit") if args.len() == 2 => {}
+            _ => CLI.fail("usage: wallrun msg show [OPTIONS] | msg hide | msg quit"),
+        }
+        if let Err(e) = ipc::send("wallrun", &request, false) {
+            eprintln!("wallrun: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    let opts = CLI.parse_or_exit(&args);
+    trace::start("wallrun", &opts);
+    cli::config_commands::<Config>("wallrun", &opts);
 
-    let font_data = std::fs::read(expand_path(&cfg.font)).expect("failed to read font file");
-    let mut db = cosmic_text::fontdb::Database::new();
-    db.load_font_data(font_data);
-    let font_family = db.faces().next().expect("font file contains no faces").families[0].0.clone();
-    let font_system = FontSystem::new_with_locale_and_db("en-US".into(), db);
+    let base: Config = load_config("wallrun");
+    let colors: Colors = load_colors(base.color_file.as_deref());
+    let mut cfg = base.clone();
+    let inv = parse_args(&opts, &mut cfg).unwrap_or_else(|e| CLI.fail(&e));
+
+    let mut db = font_db();
+    let font_family = load_font(&mut db, &cfg.font).unwrap_or_else(|| {
+        eprintln!("wallrun: font not found: {}", cfg.font);
+        std::process::exit(1);
+    });
 
     let mut app = App {
-        registry_state: RegistryState::new(&globals),
-        seat_state: SeatState::new(&globals, &qh),
-        output_state: OutputState::new(&globals, &qh),
-        shm,
-        layer,
-        keyboard: None,
-        pointer: None,
-        cursor_shape_manager,
-        pool,
-        width,
-        height,
-        exit: false,
-        input: String::new(),
-        font_system,
-        swash_cache: SwashCache::new(),
-        loop_handle: event_loop.handle(),
-        filtered: (0..items.len()).collect(),
-        items,
+        width: 0,
+        height: 0,
+        scale: 1.0,
+        input: LineEdit::default(),
+        caret: Rect { x: 0, y: 0, w: 0, h: 0 },
+        fonts: Fonts::new(db, &cfg.font_fallback),
+        filtered: Vec::new(),
+        items: Vec::new(),
         selected: 0,
         scroll_offset: 0,
-        cols,
+        cols: cfg.columns.max(1),
         colors,
+        selection_radius: cfg.style.selection_radius,
         font_size: cfg.font_size,
         label_font_size: cfg.label_font_size,
         show_labels: cfg.show_labels,
         font_family,
+        keys: cfg.keys.clone(),
+        spawn: if inv.replay.is_some() { replay::print_run } else { keys::run },
+        marked: Vec::new(),
+        shown: None,
+        source: None,
+        client: None,
+        clip: None,
+        config: base,
+        opts,
     };
+    let (width, height) = app.open(&cfg, &inv);
 
-    loop {
-        event_loop.dispatch(Duration::from_millis(16), &mut app).unwrap();
-        if app.exit { break; }
+    if let Some(path) = &inv.render_to {
+        if let Err(e) = render_to_png(&mut app, width, height, inv.scale, &cfg.style, Path::new(path)) {
+            eprintln!("wallrun: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let options = Options {
+        namespace: "wallrun",
+        width,
+        height,
+        placement: cfg.placement.clone(),
+        style: cfg.style.clone(),
+        keyboard: KeyboardInteractivity::Exclusive,
+        output: cfg.output,
+        resident: inv.daemon,
+    };
+    let bus = if inv.dbus {
+        dbus::connect(Builder::session(), Control).inspect_err(|e| eprintln!("wallrun: D-Bus: {e}")).ok()
+    } else {
+        None
+    };
+
+    if let Some(path) = &inv.replay {
+        if let Err(e) = replay::run(app, options, inv.scale, Path::new(path), bus) {
+            eprintln!("wallrun: replay: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut event_loop: EventLoop<Window<App>> = EventLoop::try_new().unwrap();
+    let mut win = Window::new(&event_loop, options, app);
+
+    if inv.daemon {
+        let served = ipc::serve(&event_loop.handle(), "wallrun", move |req, win: &mut Window<App>| {
+            match req.command.as_str() {
+                "show" => match show(win, &req.args) {
+                    Ok(()) => win.widget.client = Some(req.stream),
+                    Err(e) => eprintln!("wallrun: msg show: {e}"),
+                },
+                "hide" => win.dismiss(),
+                "quit" => win.quit(),
+                cmd => eprintln!("wallrun: unknown command: {cmd}"),
+            }
+        });
+        if let Err(e) = served {
+            eprintln!("wallrun: {e}");
+            std::process::exit(1);
+        }
+    }
+    if let Some(bus) = bus && let Err(e) = bus.attach(&event_loop.handle()) {
+        eprintln!("wallrun: D-Bus: {e}");
+    }
+    let watched = watch::config_files("wallrun", cfg.color_file.as_deref());
+    if let Err(e) = watch::watch(&event_loop.handle(), watched, reload) {
+        eprintln!("wallrun: cannot watch config: {e}");
     }
+    win.run(&mut event_loop);
 }
@@ -0,0 +1,156 @@
+//! End-to-end tests: scripted input through `--replay`, over a scratch
+//! directory of five wallpapers laid out three to a row:
+//!
+//! ```text
+//! a b c
+//! d e
+//! ```
+
+use std::path::PathBuf;
+use std::process::Output;
+use std::time::{Duration, Instant};
+use widgets_core::replay::testing::{self, Bus};
+
+/// An empty home for one test, with the XDG directories and wallpapers inside it.
+fn scratch(test: &str) -> PathBuf {
+    let home = testing::scratch(&format!("wallrun-replay-{test}"));
+    std::fs::create_dir_all(home.join("walls")).unwrap();
+    for (i, name) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+        let shade = 40 * i as u8;
+        image::RgbImage::from_pixel(64, 40, image::Rgb([shade, 90, 200 - shade]))
+            .save(home.join(format!("walls/{name}.png"))).unwrap();
+    }
+    home
+}
+
+/// Press `keys` in order.
+fn replay(test: &str, keys: &[&str]) -> Output {
+    let home = scratch(test);
+    let config = "columns = 3\n[keys]\n\"ctrl+shift+c\" = \"copy-image\"\n";
+    let steps: Vec<String> = keys.iter().map(|k| format!("{{ key = {k:?} }}")).collect();
+    let script = format!("steps = [{}]", steps.join(", "));
+    let walls = home.join("walls");
+    let output = testing::replay(env!("CARGO_BIN_EXE_wallrun"), &home, config, &script, &["--dir", walls.to_str().unwrap()], "");
+    std::fs::remove_dir_all(&home).ok();
+    output
+}
+
+/// Press `keys` in order and return the name of the file wallrun printed.
+fn pick(test: &str, keys: &[&str]) -> String {
+    let output = replay(test, keys);
+    let printed = String::from_utf8_lossy(&output.stdout);
+    // Only the file name matters; the directory is the scratch one
+    printed.trim_end().rsplit('/').next().unwrap_or_default().to_string()
+}
+
+#[test]
+fn enter_prints_first() {
+    let picked = pick("enter", &["Return"]);
+    assert_eq!(picked, "a.png");
+}
+
+#[test]
+fn right_moves_along_row() {
+    let picked = pick("right", &["Right", "Right", "Return"]);
+    assert_eq!(picked, "c.png");
+}
+
+#[test]
+fn left_from_first_wraps_to_last() {
+    let picked = pick("left-wrap", &["Left", "Return"]);
+    assert_eq!(picked, "e.png");
+}
+
+#[test]
+fn right_from_last_wraps_to_first() {
+    let picked = pick("right-wrap", &["End", "Right", "Return"]);
+    assert_eq!(picked, "a.png");
+}
+
+#[test]
+fn down_moves_a_row() {
+    let picked = pick("down", &["Right", "Down", "Return"]);
+    assert_eq!(picked, "e.png");
+}
+
+#[test]
+fn down_from_bottom_row_wraps_to_top() {
+    let picked = pick("down-wrap", &["Right", "Down", "Down", "Return"]);
+    assert_eq!(picked, "b.png");
+}
+
+#[test]
+fn down_past_short_column_wraps_to_top() {
+    let picked = pick("down-short", &["Right", "Right", "Down", "Return"]);
+    assert_eq!(picked, "c.png");
+}
+
+#[test]
+fn up_from_top_row_wraps_to_bottom() {
+    let picked = pick("up-wrap", &["Up", "Return"]);
+    assert_eq!(picked, "d.png");
+}
+
+#[test]
+fn up_past_short_column_clamps_to_last() {
+    let picked = pick("up-short", &["Right", "Right", "Up", "Return"]);
+    assert_eq!(picked, "e.png");
+}
+
+#[test]
+fn escape_prints_nothing() {
+    let picked = pick("escape", &["Escape", "Return"]);
+    assert_eq!(picked, "");
+}
+
+#[test]
+fn copy_copies_path_instead_of_printing() {
+    let out = replay("copy", &["Right", "ctrl+c"]);
+    assert!(out.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.lines().any(|l| l.starts_with("replay: copy: /") && l.ends_with("/walls/b.png")), "{stderr}");
+}
+
+#[test]
+fn copy_image_copies_file_contents() {
+    let out = replay("copy-image", &["Right", "Right", "ctrl+shift+c"]);
+    assert!(out.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.lines().any(|l| l.starts_with("replay: copy: ") && l.ends_with(" bytes of image/png")), "{stderr}");
+}
+
+#[test]
+fn zero_columns_is_one() {
+    let home = scratch("zero-columns");
+    let config = "columns = 0\nwindow_width = \"fit\"\nwindow_height = \"fit\"\n";
+    let walls = home.join("walls");
+    let script = r#"steps = [{ key = "Down" }, { key = "Return" }]"#;
+    let out = testing::replay(env!("CARGO_BIN_EXE_wallrun"), &home, config, script, &["--dir", walls.to_str().unwrap()], "");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).ends_with("/walls/b.png\n"));
+}
+
+#[test]
+fn dbus_show_keeps_the_wallpapers() {
+    let home = scratch("dbus-show");
+    let bus = Bus::start();
+    let walls = home.join("walls");
+    let script = r#"steps = [{ serve_until = "shown" }, { key = "Right" }, { key = "Return" }, { serve_until = "exit" }]"#;
+    let args = ["--daemon", "--dir", walls.to_str().unwrap()];
+    let mut child = testing::serve(env!("CARGO_BIN_EXE_wallrun"), &home, "columns = 3\n", script, &args, &bus);
+    let wallrun = bus.proxy("org.widgets.Wallrun", &mut child);
+    assert!(!wallrun.get_property::<bool>("Visible").unwrap());
+    wallrun.call::<_, _, ()>("Show", &()).unwrap();
+    // Calls can arrive before the script's keys; quit once Return has hidden it
+    let end = Instant::now() + Duration::from_secs(10);
+    while wallrun.get_property::<bool>("Visible").unwrap() {
+        assert!(Instant::now() < end, "nothing to pick after Show");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    // It exits before it could answer
+    wallrun.call_noreply("Quit", &()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&home).ok();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).ends_with("/walls/b.png\n"), "{}", String::from_utf8_lossy(&out.stdout));
+}
@@ -1,45 +1,42 @@
-use std::path::PathBuf;
-use libc;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Child, Stdio};
+use std::sync::OnceLock;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
-use cosmic_text::{Attrs, Buffer, FontSystem, Metrics, Shaping, SwashCache, SwashContent, Weight};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use cosmic_text::Weight;
 use serde::{Deserialize, Serialize};
 use smithay_client_toolkit as sctk;
 use sctk::reexports::calloop::timer::{TimeoutAction, Timer};
 use sctk::reexports::calloop::EventLoop;
-use sctk::reexports::calloop_wayland_source::WaylandSource;
-use sctk::compositor::{CompositorHandler, CompositorState};
-use sctk::output::{OutputHandler, OutputState};
-use sctk::registry::{ProvidesRegistryState, RegistryState};
-use sctk::registry_handlers;
-use sctk::seat::pointer::{PointerEvent, PointerEventKind, PointerHandler};
-use sctk::seat::pointer::cursor_shape::CursorShapeManager;
-use sctk::seat::{Capability, SeatHandler, SeatState};
 use sctk::reexports::protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
-use sctk::shell::wlr_layer::{
-    KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
-    LayerSurfaceConfigure,
-};
-use sctk::shell::WaylandSurface;
-use sctk::shm::slot::SlotPool;
-use sctk::shm::{Shm, ShmHandler};
-use sctk::{
-    delegate_compositor, delegate_layer, delegate_output, delegate_pointer,
-    delegate_registry, delegate_seat, delegate_shm,
-};
-use wayland_client::globals::registry_queue_init;
-use wayland_client::protocol::{wl_output, wl_pointer, wl_seat, wl_shm, wl_surface};
-use wayland_client::{Connection, QueueHandle};
+use sctk::seat::keyboard::{KeyEvent, Keysym, Modifiers};
+use sctk::seat::pointer::{PointerEvent, PointerEventKind};
+use sctk::shell::wlr_layer::KeyboardInteractivity;
 use tiny_skia::Pixmap;
+use widgets_core::color::{load_colors, Palette};
+use widgets_core::cli::{self, Opt, Parsed, Spec};
+use widgets_core::config::{home, load_config, state_dir, Documented};
+use widgets_core::dbus::{self, Remote};
+use widgets_core::headless::render_to_png;
+use widgets_core::keys::{self, Spawn};
+use widgets_core::render::{clear_rect, faded, fill_rect, fill_rounded_rect_alpha, Rect};
+use widgets_core::replay;
+use widgets_core::style::{stroke_rounded, Backdrop, Style, STYLE_DOC};
+use widgets_core::text::{font_db, load_font, Fonts, LINE_HEIGHT};
+use widgets_core::trace;
+use widgets_core::watch;
+use widgets_core::window::{Options, Placement, Update, Widget, Window, PLACEMENT_KEYS};
+use zbus::blocking::connection::Builder;
+use zbus::{fdo, interface};
 
 // --- Config ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(default)]
 struct Config {
     color_file: Option<String>,
     font: String,
+    font_fallback: Vec<String>,
     icon_font: String,
     font_size: f32,
     timer1_duration: u64,
@@ -48,13 +45,18 @@ struct Config {
     bt_device_2: String,
     weather_lat: f64,
     weather_lon: f64,
+    output: Option<String>,
+    keyboard: bool,
+    style: Style,
+    #[serde(flatten)]
+    placement: Placement,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             color_file: Some("~/.cache/wal/colors-wavedash.toml".into()),
-            font: "~/.local/share/fonts/GoogleSansCode-Bold.ttf".into(),
+            font: "~/.local/share/fonts/GoogleSansCode-Bold.ttf".into(), font_fallback: Vec::new(),
             icon_font: "/usr/share/fonts/OTF/Font Awesome 7 Free-Solid-900.otf".into(),
             font_size: 39.0,
             timer1_duration: 3600,
@@ -63,139 +65,106 @@ impl Default for Config {
             bt_device_2: "EC:81:93:AC:8B:60".into(),
             weather_lat: 0.0,
             weather_lon: 0.0,
+            output: None,
+            keyboard: false,
+            style: Style { border_width: 0.0, ..Style::default() },
+            placement: Placement::default(),
         }
     }
 }
 
-fn load_config() -> Config {
-    let path = config_dir().join("wavedash.toml");
-    match std::fs::read_to_string(&path) {
-        Ok(s) => match toml::from_str(&s) {
-            Ok(cfg) => cfg,
-            Err(e) => { eprintln!("wavedash: failed to parse {}: {e}", path.display()); Config::default() }
-        }
-        Err(_) => Config::default(),
-    }
-}
-
-fn config_dir() -> PathBuf {
-    let base = std::env::var("XDG_CONFIG_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| home().join(".config"));
-    base.join("widgets")
-}
-
-fn home() -> PathBuf {
-    PathBuf::from(std::env::var("HOME").unwrap())
-}
-
-fn expand_path(p: &str) -> PathBuf {
-    if let Some(rest) = p.strip_prefix("~/") {
-        home().join(rest)
-    } else {
-        PathBuf::from(p)
+impl Documented for Config {
+    type Colors = Colors;
+    const FLATTENED: &'static [(&'static str, &'static str)] = PLACEMENT_KEYS;
+    const KEYS: &'static [(&'static str, &'static str)] = &[
+        ("color_file", "walrs color file"),
+        ("font", "Font file or family name for the clock and labels"),
+        ("font_fallback", "Fonts (files or family names) for characters `font` lacks, before the system's fallbacks"),
+        ("icon_font", "Font Awesome Solid font file or family name for icons"),
+        ("font_size", "Unused; kept for older configs"),
+        ("timer1_duration", "First timer length in seconds"),
+        ("timer2_duration", "Second timer length in seconds"),
+        ("bt_device_1", "Bluetooth headphones MAC address"),
+        ("bt_device_2", "Second Bluetooth device MAC address"),
+        ("weather_lat", "Weather latitude; 0.0 disables weather"),
+        ("weather_lon", "Weather longitude"),
+        ("output", "Monitor: connector name, \"focused\" or \"cursor\" (both need Hyprland); unset lets the compositor choose"),
+        ("keyboard", "Take keyboard focus when clicked, to move between tiles with the keys"),
+        ("style", STYLE_DOC),
+    ];
+    fn color_file(&self) -> Option<&str> { self.color_file.as_deref() }
+    fn fonts(&self) -> Vec<(&'static str, String)> {
+        let fallback = self.font_fallback.iter().map(|f| ("font_fallback", f.clone()));
+        [("font", self.font.clone()), ("icon_font", self.icon_font.clone())].into_iter().chain(fallback).collect()
     }
 }
 
 // --- Colors ---
 
 struct Colors {
-    background: [u8; 3],
-    background_alpha: u8,
-    border: [u8; 3],
-    divider: [u8; 3],
-    sun: [u8; 3],
-    clock: [u8; 3],
-    accentl: [u8; 3],
-    accentr: [u8; 3],
-    weather: [u8; 3],
-    audio: [u8; 3],
-    volume: [u8; 3],
-    notif: [u8; 3],
-    timer: [u8; 3],
-    dots: [[u8; 3]; 16],
+    background: [u8; 4],
+    border: [u8; 4],
+    divider: [u8; 4],
+    sun: [u8; 4],
+    clock: [u8; 4],
+    accentl: [u8; 4],
+    accentr: [u8; 4],
+    weather: [u8; 4],
+    audio: [u8; 4],
+    volume: [u8; 4],
+    notif: [u8; 4],
+    timer: [u8; 4],
+    dots: [[u8; 4]; 16],
 }
 
 impl Default for Colors {
     fn default() -> Self {
         Self {
-            background: [0x1e, 0x1e, 0x2e],
-            background_alpha: 0xe6, // ~0.9
-            border: [0xcd, 0xd6, 0xf4],
-            divider: [0xcd, 0xd6, 0xf4],
-            sun: [0xf9, 0xe2, 0xaf],
-            clock: [0x89, 0xb4, 0xfa],
-            accentl: [0x89, 0xb4, 0xfa],
-            accentr: [0x89, 0xb4, 0xfa],
-            weather: [0x94, 0xe2, 0xd5],
-            audio: [0xcb, 0xa6, 0xf7],
-            volume: [0xcb, 0xa6, 0xf7],
-            notif: [0xcb, 0xa6, 0xf7],
-            timer: [0xcb, 0xa6, 0xf7],
+            background: [0x1e, 0x1e, 0x2e, 0xe6], // ~0.9
+            border: [0xcd, 0xd6, 0xf4, 0xff],
+            divider: [0xcd, 0xd6, 0xf4, 0xff],
+            sun: [0xf9, 0xe2, 0xaf, 0xff],
+            clock: [0x89, 0xb4, 0xfa, 0xff],
+            accentl: [0x89, 0xb4, 0xfa, 0xff],
+            accentr: [0x89, 0xb4, 0xfa, 0xff],
+            weather: [0x94, 0xe2, 0xd5, 0xff],
+            audio: [0xcb, 0xa6, 0xf7, 0xff],
+            volume: [0xcb, 0xa6, 0xf7, 0xff],
+            notif: [0xcb, 0xa6, 0xf7, 0xff],
+            timer: [0xcb, 0xa6, 0xf7, 0xff],
             dots: [
                 [0xcd, 0xd6, 0xf4], // foreground
                 [0xf3, 0x8b, 0xa8], [0xa6, 0xe3, 0xa1], [0xf9, 0xe2, 0xaf], [0x89, 0xb4, 0xfa],
                 [0xcb, 0xa6, 0xf7], [0x94, 0xe2, 0xd5], [0xf2, 0xcd, 0xcd], [0xb4, 0xbe, 0xfe],
                 [0xf3, 0x8b, 0xa8], [0xa6, 0xe3, 0xa1], [0xf9, 0xe2, 0xaf], [0x89, 0xb4, 0xfa],
                 [0xcb, 0xa6, 0xf7], [0x94, 0xe2, 0xd5], [0xf2, 0xcd, 0xcd],
-            ],
+            ].map(|[r, g, b]| [r, g, b, 0xff]),
         }
     }
 }
 
-fn parse_hex(s: &str) -> Option<[u8; 3]> {
-    let s = s.strip_prefix('#').unwrap_or(s);
-    if s.len() != 6 { return None; }
-    Some([u8::from_str_radix(&s[0..2], 16).ok()?,
-          u8::from_str_radix(&s[2..4], 16).ok()?,
-          u8::from_str_radix(&s[4..6], 16).ok()?])
-}
-
-fn load_colors(path: Option<&str>) -> Colors {
-    let mut colors = Colors::default();
-    let content = match path {
-        Some(p) => std::fs::read_to_string(expand_path(p)).unwrap_or_default(),
-        None => return colors,
-    };
-    for line in content.lines() {
-        if let Some((key, val)) = line.split_once('=') {
-            let (key, val) = (key.trim(), val.trim());
-            match key {
-                "background_opacity" => {
-                    if let Ok(f) = val.parse::<f32>() {
-                        colors.background_alpha = (f.clamp(0.0, 1.0) * 255.0) as u8;
-                    }
-                }
-                _ => {
-                    if let Some(c) = parse_hex(val) {
-                        match key {
-                            "background" => colors.background = c,
-                            "border" => colors.border = c,
-                            "divider" => colors.divider = c,
-                            "sun" => colors.sun = c,
-                            "clock" => colors.clock = c,
-                            "accentl" => colors.accentl = c,
-                            "accentr" => colors.accentr = c,
-                            "weather" => colors.weather = c,
-                            "audio" => colors.audio = c,
-                            "volume" => colors.volume = c,
-                            "notif" => colors.notif = c,
-                            "timer" => colors.timer = c,
-                            "foreground" => colors.dots[0] = c,
-                            _ => {
-                                if let Some(n) = key.strip_prefix("color") {
-                                    if let Ok(i) = n.parse::<usize>() {
-                                        if i >= 1 && i <= 15 { colors.dots[i] = c; }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+impl Palette for Colors {
+    fn color(&mut self, key: &str) -> Option<&mut [u8; 4]> {
+        Some(match key {
+            "background" => &mut self.background,
+            "border" => &mut self.border,
+            "divider" => &mut self.divider,
+            "sun" => &mut self.sun,
+            "clock" => &mut self.clock,
+            "accentl" => &mut self.accentl,
+            "accentr" => &mut self.accentr,
+            "weather" => &mut self.weather,
+            "audio" => &mut self.audio,
+            "volume" => &mut self.volume,
+            "notif" => &mut self.notif,
+            "timer" => &mut self.timer,
+            "foreground" => &mut self.dots[0],
+            _ => {
+                let i = key.strip_prefix("color")?.parse::<usize>().ok().filter(|i| (1..=15).contains(i))?;
+                &mut self.dots[i]
             }
-        }
+        })
     }
-    colors
 }
 
 // --- State ---
@@ -216,10 +185,7 @@ struct State {
 }
 
 fn state_path() -> PathBuf {
-    let base = std::env::var("XDG_STATE_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| home().join(".local/state"));
-    base.join("widgets/wavedash.toml")
+    state_dir().join("wavedash.toml")
 }
 
 fn load_state(cfg: &Config) -> State {
@@ -234,6 +200,22 @@ fn load_state(cfg: &Config) -> State {
     st
 }
 
+/// Fixed state for `--render-to`: configured timer durations and a fake clear-sky reading.
+fn headless_state(cfg: &Config) -> State {
+    State {
+        timer1_duration: cfg.timer1_duration as i64,
+        timer1_base: cfg.timer1_duration as i64,
+        timer2_duration: cfg.timer2_duration as i64,
+        timer2_base: cfg.timer2_duration as i64,
+        weather_temp: 72.0,
+        weather_feels: 70.0,
+        weather_code: 0,
+        weather_is_day: true,
+        weather_fetched: now_unix(),
+        ..Default::default()
+    }
+}
+
 fn save_state(state: &State) {
     let path = state_path();
     std::fs::create_dir_all(path.parent().unwrap()).ok();
@@ -241,8 +223,13 @@ fn save_state(state: &State) {
 }
 
 const WEATHER_MAX_AGE: u64 = 3600;
+const HEADLESS_TIME: u64 = 1_700_000_000;
+
+// Pinned clock for headless renders
+static FIXED_NOW: OnceLock<u64> = OnceLock::new();
 
 fn now_unix() -> u64 {
+    if let Some(&t) = FIXED_NOW.get() { return t; }
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
 }
 
@@ -289,13 +276,6 @@ fn get_volume() -> (f32, bool) {
     }
 }
 
-fn set_volume(vol: f32) {
-    let v = vol.clamp(0.0, VOL_MAX);
-    Command::new("wpctl")
-        .args(["set-volume", "@DEFAULT_AUDIO_SINK@", &format!("{v:.2}")])
-        .spawn().ok();
-}
-
 fn is_headphones(bt_mac: &str) -> bool {
     let out = Command::new("wpctl").args(["inspect", "@DEFAULT_AUDIO_SINK@"]).output();
     match out {
@@ -310,13 +290,6 @@ fn is_headphones(bt_mac: &str) -> bool {
     }
 }
 
-fn switch_audio(target_mac: &str) {
-    Command::new("sh").arg("-c")
-        .arg(format!("{}/scripts/audio_switch.sh {target_mac}",
-            home().join(".config/quickshell").display()))
-        .spawn().ok();
-}
-
 // --- Signal (long press detection) ---
 
 static GOT_SIGUSR2: AtomicBool = AtomicBool::new(false);
@@ -340,7 +313,6 @@ const WEATHER_TEMP_SIZE: f32 = 36.0;
 const WEATHER_FEELS_SIZE: f32 = 18.0;
 const TIMER_SIZE: f32 = 32.0;
 const UTIL_ICON_SIZE: f32 = 21.0;
-const LINE_HEIGHT: f32 = 1.2;
 
 // Hover
 
@@ -351,22 +323,21 @@ const VOL_MAX: f32 = 2.0;
 // Timers
 const TIMER_SCROLL_STEP: i64 = 60;
 
+// Focus ring
+const FOCUS_RADIUS: f32 = 6.0;
+const FOCUS_WIDTH: f32 = 2.0;
+const FOCUS_ALPHA: u8 = 200;
+
 // Timing
 const TICK_MS: u64 = 100;
+// Volume, mute and sink are re-read (two wpctl runs) this often, in seconds
+const AUDIO_REFRESH_SECS: u64 = 2;
+// ...but not this soon after setting the volume, which wpctl may not report yet
 const AUDIO_REFRESH_COOLDOWN: u64 = 1;
 const LONG_PRESS_GRACE_MS: u64 = 300;
 
 // --- Tile geometry ---
 
-#[derive(Clone, Copy)]
-struct Rect { x: u32, y: u32, w: u32, h: u32 }
-
-impl Rect {
-    fn contains(&self, mx: u32, my: u32) -> bool {
-        mx >= self.x && mx < self.x + self.w && my >= self.y && my < self.y + self.h
-    }
-}
-
 struct Layout {
     toggle: Rect,
     clock: Rect,
@@ -379,6 +350,7 @@ struct Layout {
     audio: Rect,
 }
 
+/// Tile rects in logical units for a `w`x`h` logical surface.
 fn layout(w: u32, h: u32) -> Layout {
     let lm = LEFT_MARGIN as u32;
     let right = w - lm;
@@ -406,28 +378,38 @@ fn layout(w: u32, h: u32) -> Layout {
     }
 }
 
-// --- Hover ---
+// --- Hover and focus ---
 
 #[derive(PartialEq, Clone, Copy)]
 enum HoverTile { None, Toggle, Notif, Timer1, Timer2, Volume, Audio, Date }
 
+/// Tab order: down the left column, then the timers.
+const FOCUS_ORDER: [HoverTile; 7] = [
+    HoverTile::Date, HoverTile::Toggle, HoverTile::Notif, HoverTile::Audio,
+    HoverTile::Volume, HoverTile::Timer2, HoverTile::Timer1,
+];
+
+fn tile_rect(lay: &Layout, tile: HoverTile) -> Option<Rect> {
+    match tile {
+        HoverTile::None => None,
+        HoverTile::Toggle => Some(lay.toggle),
+        HoverTile::Notif => Some(lay.notif),
+        HoverTile::Timer1 => Some(lay.timer1),
+        HoverTile::Timer2 => Some(lay.timer2),
+        HoverTile::Volume => Some(lay.volume),
+        HoverTile::Audio => Some(lay.audio),
+        HoverTile::Date => Some(lay.date),
+    }
+}
+
 
 // --- App ---
 
 struct App {
-    registry_state: RegistryState,
-    seat_state: SeatState,
-    output_state: OutputState,
-    shm: Shm,
-    layer: LayerSurface,
-    pointer: Option<wl_pointer::WlPointer>,
-    cursor_shape_manager: CursorShapeManager,
-    pool: SlotPool,
     width: u32,
     height: u32,
-    exit: bool,
-    font_system: FontSystem,
-    swash_cache: SwashCache,
+    scale: f32,
+    fonts: Fonts,
     colors: Colors,
     font_family: String,
     icon_family: String,
@@ -444,15 +426,20 @@ struct App {
     bt_device_2: String,
     // Theme
     is_dim: bool,
+    shown: [String; 4],
     // Hover
     hover: HoverTile,
+    // Keyboard focus; None hides the ring
+    focus: HoverTile,
     // Base durations for reset (scroll-adjusted)
     timer1_base: i64,
     timer2_base: i64,
     // Config-defined durations (middle-click reset target)
     timer1_config: i64,
     timer2_config: i64,
+    // When the volume was last set and last read back, in unix seconds
     volume_set_at: u64,
+    audio_read_at: u64,
     // Weather
     weather_temp: f64,
     weather_feels: f64,
@@ -465,9 +452,24 @@ struct App {
     // Long press: None = undecided (grace period), Some(true) = exit on key release, Some(false) = persistent
     long_press: Option<bool>,
     startup: Instant,
+    // Starts the tiles' commands; only prints them under --replay
+    spawn: Spawn,
+    // Saves timers and weather; nowhere under --replay
+    save_state: fn(&State),
 }
 
 impl App {
+    /// Tile rects in buffer pixels.
+    fn layout(&self) -> Layout {
+        let s = self.scale;
+        let l = layout((self.width as f32 / s).round() as u32, (self.height as f32 / s).round() as u32);
+        Layout {
+            toggle: l.toggle.scaled(s), clock: l.clock.scaled(s), date: l.date.scaled(s),
+            notif: l.notif.scaled(s), weather: l.weather.scaled(s), timer1: l.timer1.scaled(s),
+            timer2: l.timer2.scaled(s), volume: l.volume.scaled(s), audio: l.audio.scaled(s),
+        }
+    }
+
     fn state(&self) -> State {
         State {
             timer1_duration: self.timer1_duration,
@@ -484,322 +486,388 @@ impl App {
         }
     }
 
+    /// Set the sink's volume, as scrolling over the slider does.
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, VOL_MAX);
+        self.volume_set_at = now_unix();
+        (self.spawn)(&format!("wpctl set-volume @DEFAULT_AUDIO_SINK@ {:.2}", self.volume), &[]);
+    }
+
     fn refresh_audio(&mut self) {
         let (v, m) = get_volume();
         self.volume = v;
         self.muted = m;
         self.headphones = is_headphones(&self.bt_device_1);
+        self.audio_read_at = now_unix();
     }
 
-    fn draw(&mut self) {
-        let c = &self.colors;
-        let bg = c.background;
-        let bg_a = c.background_alpha;
-        let lay = layout(self.width, self.height);
-        let hv = self.hover;
+    /// Repaint areas: clock + date, weather, icon column + volume, timers.
+    fn regions(&self) -> [Rect; 4] {
+        let lay = self.layout();
+        let sec_y = lay.toggle.y;
+        let sec_h = self.height - sec_y;
+        [
+            Rect { x: lay.clock.x, y: 0, w: lay.clock.w, h: sec_y },
+            Rect { x: lay.weather.x, y: 0, w: lay.weather.w, h: sec_y },
+            Rect { x: lay.toggle.x, y: sec_y, w: lay.volume.x + lay.volume.w - lay.toggle.x, h: sec_h },
+            Rect { x: lay.timer2.x, y: sec_y, w: lay.timer2.w, h: sec_h },
+        ]
+    }
 
-        let stride = self.width as i32 * 4;
-        let (wl_buf, canvas) = self.pool
-            .create_buffer(self.width as i32, self.height as i32, stride, wl_shm::Format::Argb8888)
-            .unwrap();
+    /// What each region currently shows; a region is repainted when its entry changes.
+    fn region_keys(&self) -> [String; 4] {
+        let hv = self.hover;
+        let fc = self.focus;
+        let now = chrono_now();
+        let fill_w = ((self.volume / VOL_MAX) * self.layout().volume.w as f32).round() as u32;
+        [
+            format!("{:?}", (now.0, now.1, format_date(), hv == HoverTile::Date, fc == HoverTile::Date)),
+            format!("{:?}", (self.weather_fetched > 0, self.weather_code, self.weather_is_day,
+                format!("{:.0} {:.0}", self.weather_temp, self.weather_feels))),
+            format!("{:?}", (self.weather_is_day, self.notif_paused, self.headphones, self.muted, fill_w,
+                hv == HoverTile::Toggle, hv == HoverTile::Notif, hv == HoverTile::Audio, hv == HoverTile::Volume,
+                [HoverTile::Toggle, HoverTile::Notif, HoverTile::Audio, HoverTile::Volume].map(|t| fc == t))),
+            format!("{:?}", (format_timer(timer_remaining(self.timer1_duration, self.timer1_started)),
+                format_timer(timer_remaining(self.timer2_duration, self.timer2_started)),
+                self.timer1_started > 0, self.timer2_started > 0, hv == HoverTile::Timer1, hv == HoverTile::Timer2,
+                fc == HoverTile::Timer1, fc == HoverTile::Timer2)),
+        ]
+    }
 
-        let mut pixmap = Pixmap::new(self.width, self.height).unwrap();
-        pixmap.fill(tiny_skia::Color::TRANSPARENT);
+    fn backdrop(&self) -> Backdrop {
+        let c = &self.colors;
+        Backdrop { background: c.background, border: c.border }
+    }
 
-        let pw = pixmap.width();
-        let ph = pixmap.height();
+    fn draw(&mut self, pixmap: &mut Pixmap) {
+        let c = &self.colors;
+        let (pw, ph) = (pixmap.width(), pixmap.height());
 
-        // Full-bleed background
-        fill_rect_alpha(pixmap.data_mut(), pw, ph, 0, 0, self.width, self.height, bg, bg_a);
+        // The panel shows through
+        clear_rect(pixmap.data_mut(), pw, ph, Rect { x: 0, y: 0, w: pw, h: ph }, [0; 3], 0);
 
         // Accent bars (left + right edge, full height)
-        fill_rect(pixmap.data_mut(), pw, ph, 0, 0, ACCENT_W, self.height, c.accentl);
-        fill_rect(pixmap.data_mut(), pw, ph, self.width - ACCENT_W, 0, ACCENT_W, self.height, c.accentr);
+        let accent_w = (ACCENT_W as f32 * self.scale).round() as u32;
+        fill_rect(pixmap.data_mut(), pw, ph, 0, 0, accent_w, self.height, c.accentl);
+        fill_rect(pixmap.data_mut(), pw, ph, self.width - accent_w, 0, accent_w, self.height, c.accentr);
 
-        let fa = &self.icon_family;
+        for i in 0..4 { self.draw_region(pixmap, i); }
+        self.shown = self.region_keys();
+    }
+
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Vec<Rect> {
+        let keys = self.region_keys();
+        let regions = self.regions();
+        let (pw, ph) = (pixmap.width(), pixmap.height());
+        let mut damage = Vec::new();
+        for i in 0..4 {
+            if keys[i] == self.shown[i] { continue; }
+            clear_rect(pixmap.data_mut(), pw, ph, regions[i], [0; 3], 0);
+            self.draw_region(pixmap, i);
+            damage.push(regions[i]);
+        }
+        self.shown = keys;
+        damage
+    }
+
+    fn draw_region(&mut self, pixmap: &mut Pixmap, region: usize) {
+        match region {
+            0 => self.draw_clock(pixmap),
+            1 => self.draw_weather(pixmap),
+            2 => self.draw_utils(pixmap),
+            _ => self.draw_timers(pixmap),
+        }
+        self.draw_focus(pixmap, region);
+    }
+
+    /// The focus ring, if the focused tile is in `region`; clipped to it so
+    /// repainting one region never leaves part of a ring in another.
+    fn draw_focus(&self, pixmap: &mut Pixmap, region: usize) {
+        let Some(r) = tile_rect(&self.layout(), self.focus) else { return };
+        let reg = self.regions()[region];
+        if !reg.contains(r.x, r.y) { return; }
+        let h = r.h.min(reg.y + reg.h - r.y);
+        let w = r.w.min(reg.x + reg.w - r.x);
+        let s = self.scale;
+        let c = faded(self.colors.border, FOCUS_ALPHA);
+        stroke_rounded(pixmap, Rect { w, h, ..r }, FOCUS_RADIUS * s, FOCUS_WIDTH * s, rgb(c), c[3]);
+    }
+
+    // --- Clock (top-left, hero) ---
+    fn draw_clock(&mut self, pixmap: &mut Pixmap) {
+        let c = &self.colors;
+        let lay = self.layout();
+        let s = self.scale;
+        let lm = LEFT_MARGIN * s;
+        let (clock_hm_size, date_size) = (CLOCK_HM_SIZE * s, DATE_SIZE * s);
 
-        // --- Clock (top-left, hero) ---
         let now = chrono_now();
         let h12 = if now.0 == 0 { 12 } else if now.0 > 12 { now.0 - 12 } else { now.0 };
         let ampm = if now.0 < 12 { "AM" } else { "PM" };
         let hm_str = format!("{}:{:02}", h12, now.1);
-        let clock_y = lay.clock.y as f32 + 4.0;
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-            &hm_str, LEFT_MARGIN, clock_y,
-            CLOCK_HM_SIZE, lay.clock.w as f32, lay.clock.h as f32, c.clock,
+        let clock_y = lay.clock.y as f32 + 4.0 * s;
+        self.fonts.draw(pixmap,
+            &hm_str, lm, clock_y,
+            clock_hm_size, lay.clock.w as f32, c.clock,
             &self.font_family, Weight::BOLD);
-        let ampm_size = CLOCK_HM_SIZE * 0.45;
-        let hm_w = measure_text(&mut self.font_system, &hm_str, CLOCK_HM_SIZE, &self.font_family, Weight::BOLD);
-        let ampm_x = LEFT_MARGIN + hm_w + 4.0;
-        let ampm_y = clock_y + CLOCK_HM_SIZE - ampm_size - 2.0;
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
+        let ampm_size = clock_hm_size * 0.45;
+        let hm_w = self.fonts.measure(&hm_str, clock_hm_size, &self.font_family, Weight::BOLD);
+        let ampm_x = lm + hm_w + 4.0 * s;
+        let ampm_y = clock_y + clock_hm_size - ampm_size - 2.0 * s;
+        self.fonts.draw(pixmap,
             ampm, ampm_x, ampm_y,
-            ampm_size, 50.0, 30.0, c.clock,
+            ampm_size, 50.0 * s, c.clock,
             &self.font_family, Weight::BOLD);
 
         // Date below clock (clickable — opens Google Calendar)
         let date_str = format_date();
-        let date_y = clock_y + CLOCK_HM_SIZE * LINE_HEIGHT + 2.0;
+        let date_y = clock_y + clock_hm_size * LINE_HEIGHT + 2.0 * s;
 
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-            &date_str, LEFT_MARGIN, date_y,
-            DATE_SIZE, lay.clock.w as f32, 30.0, hover_color(c.clock, hv == HoverTile::Date),
+        self.fonts.draw(pixmap,
+            &date_str, lm, date_y,
+            date_size, lay.clock.w as f32, hover_color(c.clock, self.hover == HoverTile::Date),
             &self.font_family, Weight::BOLD);
+    }
 
-        // --- Weather (top-right) ---
-        if self.weather_fetched > 0 {
-            let icon = weather_icon(self.weather_code, self.weather_is_day);
-            let temp_str = format!("{:.0}°", self.weather_temp);
-            let feels_str = format!("{:.0}°", self.weather_feels);
-            let icon_w = measure_text(&mut self.font_system, icon, WEATHER_ICON_SIZE, fa, Weight::NORMAL);
-            let temp_w = measure_text(&mut self.font_system, &temp_str, WEATHER_TEMP_SIZE, &self.font_family, Weight::BOLD);
-            let gap = 6.0;
-            let block_w = icon_w + gap + temp_w;
-            let weather_right = (lay.weather.x + lay.weather.w) as f32;
-            let weather_x = weather_right - block_w;
-            let weather_y = lay.weather.y as f32 + 4.0;
-            let icon_y = weather_y + (WEATHER_TEMP_SIZE - WEATHER_ICON_SIZE) * 0.5;
-            render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                icon, weather_x, icon_y,
-                WEATHER_ICON_SIZE, 50.0, 50.0, c.weather,
-                fa, Weight::NORMAL);
-            render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                &temp_str, weather_x + icon_w + gap, weather_y,
-                WEATHER_TEMP_SIZE, 100.0, 50.0, c.weather,
-                &self.font_family, Weight::BOLD);
-            // Feels-like below, right-aligned
-            let feels_w = measure_text(&mut self.font_system, &feels_str, WEATHER_FEELS_SIZE, &self.font_family, Weight::BOLD);
-            let feels_x = weather_right - feels_w;
-            let feels_y = weather_y + WEATHER_TEMP_SIZE * LINE_HEIGHT + 2.0;
-            render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-                &feels_str, feels_x, feels_y,
-                WEATHER_FEELS_SIZE, 100.0, 30.0, alpha_color(c.weather, 0.5),
-                &self.font_family, Weight::BOLD);
-        }
+    // --- Weather (top-right) ---
+    fn draw_weather(&mut self, pixmap: &mut Pixmap) {
+        if self.weather_fetched == 0 { return; }
+        let c = &self.colors;
+        let lay = self.layout();
+        let s = self.scale;
+        let fa = &self.icon_family;
+        let (weather_icon_size, weather_temp_size, weather_feels_size) = (WEATHER_ICON_SIZE * s, WEATHER_TEMP_SIZE * s, WEATHER_FEELS_SIZE * s);
+
+        let icon = weather_icon(self.weather_code, self.weather_is_day);
+        let temp_str = format!("{:.0}°", self.weather_temp);
+        let feels_str = format!("{:.0}°", self.weather_feels);
+        let icon_w = self.fonts.measure(icon, weather_icon_size, fa, Weight::NORMAL);
+        let temp_w = self.fonts.measure(&temp_str, weather_temp_size, &self.font_family, Weight::BOLD);
+        let gap = 6.0 * s;
+        let block_w = icon_w + gap + temp_w;
+        let weather_right = (lay.weather.x + lay.weather.w) as f32;
+        let weather_x = weather_right - block_w;
+        let weather_y = lay.weather.y as f32 + 4.0 * s;
+        let icon_y = weather_y + (weather_temp_size - weather_icon_size) * 0.5;
+        self.fonts.draw(pixmap,
+            icon, weather_x, icon_y,
+            weather_icon_size, 50.0 * s, c.weather,
+            fa, Weight::NORMAL);
+        self.fonts.draw(pixmap,
+            &temp_str, weather_x + icon_w + gap, weather_y,
+            weather_temp_size, 100.0 * s, c.weather,
+            &self.font_family, Weight::BOLD);
+        // Feels-like below, right-aligned
+        let feels_w = self.fonts.measure(&feels_str, weather_feels_size, &self.font_family, Weight::BOLD);
+        let feels_x = weather_right - feels_w;
+        let feels_y = weather_y + weather_temp_size * LINE_HEIGHT + 2.0 * s;
+        self.fonts.draw(pixmap,
+            &feels_str, feels_x, feels_y,
+            weather_feels_size, 100.0 * s, alpha_color(c.weather, 0.5),
+            &self.font_family, Weight::BOLD);
+    }
 
-        // --- Left icon column (toggle, notif, audio — stacked vertically) ---
-        let icon_x = lay.toggle.x as f32 + 2.0;
+    // --- Left icon column (toggle, notif, audio — stacked vertically) + volume bar ---
+    fn draw_utils(&mut self, pixmap: &mut Pixmap) {
+        let c = &self.colors;
+        let lay = self.layout();
+        let hv = self.hover;
+        let s = self.scale;
+        let fa = &self.icon_family;
+        let util_icon_size = UTIL_ICON_SIZE * s;
+        let (pw, ph) = (pixmap.width(), pixmap.height());
+
+        let icon_x = lay.toggle.x as f32 + 2.0 * s;
 
         // Toggle icon (sun/moon, top)
         let icon_char = if self.weather_is_day { "\u{f185}" } else { "\u{f186}" };
         let mut icon_color = c.sun;
         icon_color = hover_color(icon_color, hv == HoverTile::Toggle);
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-            icon_char, icon_x + 1.0, lay.toggle.y as f32 + 6.0,
-            UTIL_ICON_SIZE, 30.0, 30.0, icon_color,
+        self.fonts.draw(pixmap,
+            icon_char, icon_x + 1.0 * s, lay.toggle.y as f32 + 6.0 * s,
+            util_icon_size, 30.0 * s, icon_color,
             fa, Weight::BLACK);
 
         // Notif icon (middle)
         let notif_icon = if self.notif_paused { "\u{f1f6}" } else { "\u{f0f3}" };
         let notif_color = hover_color(c.notif, hv == HoverTile::Notif);
-        let notif_w_on = measure_text(&mut self.font_system, "\u{f0f3}", UTIL_ICON_SIZE, fa, Weight::BLACK);
-        let notif_w_off = measure_text(&mut self.font_system, "\u{f1f6}", UTIL_ICON_SIZE, fa, Weight::BLACK);
+        let notif_w_on = self.fonts.measure("\u{f0f3}", util_icon_size, fa, Weight::BLACK);
+        let notif_w_off = self.fonts.measure("\u{f1f6}", util_icon_size, fa, Weight::BLACK);
         let notif_w_cur = if self.notif_paused { notif_w_off } else { notif_w_on };
         let notif_x = icon_x + (notif_w_on.max(notif_w_off) - notif_w_cur) / 2.0;
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-            notif_icon, notif_x, lay.notif.y as f32 + 6.0,
-            UTIL_ICON_SIZE, 30.0, 30.0, notif_color,
+        self.fonts.draw(pixmap,
+            notif_icon, notif_x, lay.notif.y as f32 + 6.0 * s,
+            util_icon_size, 30.0 * s, notif_color,
             fa, Weight::BLACK);
 
         // Audio icon (bottom)
         let audio_icon = if self.headphones { "\u{f025}" } else { "\u{f028}" };
         let ai_alpha = if self.muted { 0.3 } else { 1.0 };
         let ai_hovered = hv == HoverTile::Audio;
-        let ai_w = measure_text(&mut self.font_system, audio_icon, UTIL_ICON_SIZE, fa, Weight::BLACK);
-        let ai_x = lay.audio.x as f32 + (lay.audio.w as f32 - ai_w) / 2.0 - 2.0;
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-            audio_icon, ai_x, lay.audio.y as f32 + 6.0,
-            UTIL_ICON_SIZE, 30.0, 30.0, alpha_color(hover_color(c.audio, ai_hovered), ai_alpha),
+        let ai_w = self.fonts.measure(audio_icon, util_icon_size, fa, Weight::BLACK);
+        let ai_x = lay.audio.x as f32 + (lay.audio.w as f32 - ai_w) / 2.0 - 2.0 * s;
+        self.fonts.draw(pixmap,
+            audio_icon, ai_x, lay.audio.y as f32 + 6.0 * s,
+            util_icon_size, 30.0 * s, alpha_color(hover_color(c.audio, ai_hovered), ai_alpha),
             fa, Weight::BLACK);
 
-        // --- Volume bar (same row as audio, rounded fill bar) ---
+        // Volume bar (same row as audio, rounded fill bar)
         let vol_hovered = hv == HoverTile::Volume;
         let vol_color = hover_color(c.volume, vol_hovered);
-        let bar_h: u32 = 8;
+        let bar_h = (8.0 * s).round() as u32;
         let bar_w = lay.volume.w;
         let bar_x = lay.volume.x;
-        let icon_center_y = lay.audio.y as f32 + 6.0 + UTIL_ICON_SIZE * LINE_HEIGHT / 2.0;
+        let icon_center_y = lay.audio.y as f32 + 6.0 * s + util_icon_size * LINE_HEIGHT / 2.0;
         let bar_y = (icon_center_y - bar_h as f32 / 2.0) as u32;
         let bar_r: u32 = bar_h / 2;
-        let track_alpha: u8 = if self.muted { 25 } else { 50 };
-        let fill_alpha: u8 = if self.muted { 77 } else { 255 };
-        fill_rounded_rect_alpha(pixmap.data_mut(), pw, ph, bar_x, bar_y, bar_w, bar_h, bar_r, vol_color, track_alpha);
+        let [track, fill] = [if self.muted { 25 } else { 50 }, if self.muted { 77 } else { 255 }].map(|a| faded(vol_color, a));
+        fill_rounded_rect_alpha(pixmap.data_mut(), pw, ph, bar_x, bar_y, bar_w, bar_h, bar_r, rgb(track), track[3]);
         let fill_w = ((self.volume / VOL_MAX) * bar_w as f32).round() as u32;
         if fill_w > 0 {
-            fill_rounded_rect_alpha(pixmap.data_mut(), pw, ph, bar_x, bar_y, fill_w.min(bar_w), bar_h, bar_r, vol_color, fill_alpha);
+            fill_rounded_rect_alpha(pixmap.data_mut(), pw, ph, bar_x, bar_y, fill_w.min(bar_w), bar_h, bar_r, rgb(fill), fill[3]);
         }
+    }
+
+    // --- Timers (bottom-right, stacked: short on top, long on bottom) ---
+    fn draw_timers(&mut self, pixmap: &mut Pixmap) {
+        let c = &self.colors;
+        let lay = self.layout();
+        let hv = self.hover;
+        let s = self.scale;
+        let timer_size = TIMER_SIZE * s;
 
-        // --- Timers (bottom-right, stacked: short on top, long on bottom) ---
         let t2_rem = timer_remaining(self.timer2_duration, self.timer2_started);
         let t2_str = format_timer(t2_rem);
         let t2_alpha = if self.timer2_started > 0 { 1.0 } else { 0.7 };
         let t2_hovered = hv == HoverTile::Timer2;
-        let t2_w = measure_text(&mut self.font_system, &t2_str, TIMER_SIZE, &self.font_family, Weight::BOLD);
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-            &t2_str, (lay.timer2.x + lay.timer2.w) as f32 - t2_w, lay.timer2.y as f32 + 6.0,
-            TIMER_SIZE, lay.timer2.w as f32, lay.timer2.h as f32, alpha_color(hover_color(c.timer, t2_hovered), t2_alpha),
+        let t2_w = self.fonts.measure(&t2_str, timer_size, &self.font_family, Weight::BOLD);
+        self.fonts.draw(pixmap,
+            &t2_str, (lay.timer2.x + lay.timer2.w) as f32 - t2_w, lay.timer2.y as f32 + 6.0 * s,
+            timer_size, lay.timer2.w as f32, alpha_color(hover_color(c.timer, t2_hovered), t2_alpha),
             &self.font_family, Weight::BOLD);
 
         let t1_rem = timer_remaining(self.timer1_duration, self.timer1_started);
         let t1_str = format_timer(t1_rem);
         let t1_alpha = if self.timer1_started > 0 { 1.0 } else { 0.7 };
         let t1_hovered = hv == HoverTile::Timer1;
-        let t1_w = measure_text(&mut self.font_system, &t1_str, TIMER_SIZE, &self.font_family, Weight::BOLD);
-        render_text(&mut pixmap, &mut self.font_system, &mut self.swash_cache,
-            &t1_str, (lay.timer1.x + lay.timer1.w) as f32 - t1_w, lay.timer1.y as f32 + 6.0,
-            TIMER_SIZE, lay.timer1.w as f32, lay.timer1.h as f32, alpha_color(hover_color(c.timer, t1_hovered), t1_alpha),
+        let t1_w = self.fonts.measure(&t1_str, timer_size, &self.font_family, Weight::BOLD);
+        self.fonts.draw(pixmap,
+            &t1_str, (lay.timer1.x + lay.timer1.w) as f32 - t1_w, lay.timer1.y as f32 + 6.0 * s,
+            timer_size, lay.timer1.w as f32, alpha_color(hover_color(c.timer, t1_hovered), t1_alpha),
             &self.font_family, Weight::BOLD);
-
-        // Copy RGBA premul -> BGRA (ARGB8888 on LE)
-        for (dst, src) in canvas.chunks_exact_mut(4).zip(pixmap.data().chunks_exact(4)) {
-            dst[0] = src[2];
-            dst[1] = src[1];
-            dst[2] = src[0];
-            dst[3] = src[3];
-        }
-
-        wl_buf.attach_to(self.layer.wl_surface()).unwrap();
-        self.layer.wl_surface().damage_buffer(0, 0, self.width as i32, self.height as i32);
-        self.layer.wl_surface().commit();
     }
 
-    fn handle_click(&mut self, x: f64, y: f64) {
+    fn handle_click(&mut self, x: f64, y: f64) -> Update {
         let (mx, my) = (x as u32, y as u32);
-        let lay = layout(self.width, self.height);
+        let lay = self.layout();
 
         if lay.toggle.contains(mx, my) {
             let arg = if self.is_dim { "1" } else { "0" };
-            Command::new("sh").arg("-c")
-                .arg(format!("{}/wgmn/scripts/dim_toggle.sh {arg}",
-                    home().display()))
-                .spawn().ok();
+            (self.spawn)(&format!("{}/wgmn/scripts/dim_toggle.sh {arg}", home().display()), &[]);
             self.is_dim = !self.is_dim;
-            self.draw();
-            return;
+            return Update::Redraw;
         }
 
         if lay.notif.contains(mx, my) {
-            Command::new("dunstctl").arg("set-paused").arg("toggle").spawn().ok();
+            (self.spawn)("dunstctl set-paused toggle", &[]);
             self.notif_paused = !self.notif_paused;
-            self.draw();
-            return;
+            return Update::Redraw;
         }
 
         if lay.timer1.contains(mx, my) {
-            if self.timer1_started > 0 {
-                let rem = timer_remaining(self.timer1_duration, self.timer1_started);
-                self.timer1_duration = rem;
-                self.timer1_started = 0;
-            } else {
-                self.timer1_started = now_unix();
-            }
-            save_state(&self.state());
-            self.draw();
-            return;
+            self.toggle_timer(1).ok();
+            return Update::Redraw;
         }
 
         if lay.timer2.contains(mx, my) {
-            if self.timer2_started > 0 {
-                let rem = timer_remaining(self.timer2_duration, self.timer2_started);
-                self.timer2_duration = rem;
-                self.timer2_started = 0;
-            } else {
-                self.timer2_started = now_unix();
-            }
-            save_state(&self.state());
-            self.draw();
-            return;
+            self.toggle_timer(2).ok();
+            return Update::Redraw;
         }
 
         if lay.date.contains(mx, my) {
-            Command::new("xdg-open").arg("https://calendar.google.com").stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().ok();
-            return;
+            (self.spawn)("xdg-open https://calendar.google.com", &[]);
+            return Update::None;
         }
 
         if lay.audio.contains(mx, my) {
             let target = if self.headphones { &self.bt_device_2 } else { &self.bt_device_1 };
-            let target = target.clone();
-            switch_audio(&target);
+            let script = home().join(".config/quickshell/scripts/audio_switch.sh");
+            (self.spawn)(&format!("{} {target}", script.display()), &[]);
             self.headphones = !self.headphones;
-            self.draw();
+            return Update::Redraw;
         }
+        Update::None
     }
 
-    fn handle_scroll(&mut self, x: f64, y: f64, dy: f64) {
+    fn handle_scroll(&mut self, x: f64, y: f64, dy: f64) -> Update {
         let (mx, my) = (x as u32, y as u32);
-        let lay = layout(self.width, self.height);
+        let lay = self.layout();
 
         if lay.volume.contains(mx, my) {
             let delta: f32 = if dy > 0.0 { -VOL_SCROLL_STEP } else { VOL_SCROLL_STEP };
-            self.volume = (self.volume + delta).clamp(0.0, VOL_MAX);
-            set_volume(self.volume);
-            self.draw();
-            return;
+            self.set_volume(self.volume + delta);
+            return Update::Redraw;
         }
 
         if lay.timer1.contains(mx, my) {
             let delta: i64 = if dy > 0.0 { -TIMER_SCROLL_STEP } else { TIMER_SCROLL_STEP };
             self.timer1_duration = (self.timer1_duration + delta).max(TIMER_SCROLL_STEP);
             self.timer1_base = self.timer1_duration;
-            save_state(&self.state());
-            self.draw();
-            return;
+            (self.save_state)(&self.state());
+            return Update::Redraw;
         }
 
         if lay.timer2.contains(mx, my) {
             let delta: i64 = if dy > 0.0 { -TIMER_SCROLL_STEP } else { TIMER_SCROLL_STEP };
             self.timer2_duration = (self.timer2_duration + delta).max(TIMER_SCROLL_STEP);
             self.timer2_base = self.timer2_duration;
-            save_state(&self.state());
-            self.draw();
+            (self.save_state)(&self.state());
+            return Update::Redraw;
         }
+        Update::None
     }
 
-    fn handle_middle_click(&mut self, x: f64, y: f64) {
+    fn handle_middle_click(&mut self, x: f64, y: f64) -> Update {
         let (mx, my) = (x as u32, y as u32);
-        let lay = layout(self.width, self.height);
+        let lay = self.layout();
 
         if lay.timer1.contains(mx, my) {
             self.timer1_duration = self.timer1_config;
             self.timer1_base = self.timer1_config;
             self.timer1_started = 0;
-            save_state(&self.state());
-            self.draw();
-            return;
+            (self.save_state)(&self.state());
+            return Update::Redraw;
         }
 
         if lay.timer2.contains(mx, my) {
             self.timer2_duration = self.timer2_config;
             self.timer2_base = self.timer2_config;
             self.timer2_started = 0;
-            save_state(&self.state());
-            self.draw();
+            (self.save_state)(&self.state());
+            return Update::Redraw;
         }
+        Update::None
     }
 
-    fn handle_right_click(&mut self, x: f64, y: f64) {
+    fn handle_right_click(&mut self, x: f64, y: f64) -> Update {
         let (mx, my) = (x as u32, y as u32);
-        let lay = layout(self.width, self.height);
+        let lay = self.layout();
 
         if lay.timer1.contains(mx, my) {
-            self.timer1_duration = self.timer1_base;
-            self.timer1_started = 0;
-            save_state(&self.state());
-            self.draw();
-            return;
+            self.reset_timer(1).ok();
+            return Update::Redraw;
         }
 
         if lay.timer2.contains(mx, my) {
-            self.timer2_duration = self.timer2_base;
-            self.timer2_started = 0;
-            save_state(&self.state());
-            self.draw();
+            self.reset_timer(2).ok();
+            return Update::Redraw;
         }
+        Update::None
     }
 
     fn hover_tile_at(&self, x: f64, y: f64) -> HoverTile {
         let (mx, my) = (x as u32, y as u32);
-        let lay = layout(self.width, self.height);
+        let lay = self.layout();
 
         if lay.date.contains(mx, my) { return HoverTile::Date; }
         if lay.toggle.contains(mx, my) { return HoverTile::Toggle; }
@@ -810,6 +878,279 @@ impl App {
         if lay.audio.contains(mx, my) { return HoverTile::Audio; }
         HoverTile::None
     }
+
+    /// A hand over tiles that act on click, a vertical resize cursor over
+    /// the ones adjusted by scrolling.
+    fn cursor(&self, x: f64, y: f64) -> Shape {
+        match self.hover_tile_at(x, y) {
+            HoverTile::None => Shape::Default,
+            HoverTile::Timer1 | HoverTile::Timer2 | HoverTile::Volume => Shape::NsResize,
+            HoverTile::Toggle | HoverTile::Notif | HoverTile::Audio | HoverTile::Date => Shape::Pointer,
+        }
+    }
+
+    fn handle_pointer(&mut self, event: &PointerEvent) -> Update {
+        let (x, y) = event.position;
+        match event.kind {
+            PointerEventKind::Press { button: 0x110, .. } => self.handle_click(x, y),
+            PointerEventKind::Press { button: 0x111, .. } => self.handle_right_click(x, y),
+            PointerEventKind::Press { button: 0x112, .. } => self.handle_middle_click(x, y),
+            PointerEventKind::Motion { .. } => {
+                let new_hover = self.hover_tile_at(x, y);
+                if new_hover == self.hover { return Update::None; }
+                self.hover = new_hover;
+                Update::Redraw
+            }
+            PointerEventKind::Leave { .. } => {
+                if self.hover == HoverTile::None { return Update::None; }
+                self.hover = HoverTile::None;
+                Update::Redraw
+            }
+            PointerEventKind::Axis { ref vertical, .. } if vertical.absolute != 0.0 => {
+                self.handle_scroll(x, y, vertical.absolute)
+            }
+            _ => Update::None,
+        }
+    }
+
+    // --- Keyboard ---
+
+    /// The next tile in tab order, `step` ahead (negative goes back).
+    fn focus_next(&self, step: isize) -> HoverTile {
+        let n = FOCUS_ORDER.len() as isize;
+        let i = match FOCUS_ORDER.iter().position(|&t| t == self.focus) {
+            Some(i) => i as isize + step,
+            None if step > 0 => 0,
+            None => n - 1,
+        };
+        FOCUS_ORDER[i.rem_euclid(n) as usize]
+    }
+
+    /// The nearest tile in direction `dx, dy`, preferring ones in line with
+    /// the focused tile; the focused tile itself if there is none.
+    fn focus_toward(&self, lay: &Layout, dx: i64, dy: i64) -> HoverTile {
+        let center = |r: Rect| ((r.x + r.w / 2) as i64, (r.y + r.h / 2) as i64);
+        let Some((cx, cy)) = tile_rect(lay, self.focus).map(center) else { return FOCUS_ORDER[0] };
+        FOCUS_ORDER.iter().copied()
+            .filter_map(|t| {
+                let (tx, ty) = center(tile_rect(lay, t)?);
+                let along = (tx - cx) * dx + (ty - cy) * dy;
+                let across = ((tx - cx) * dy - (ty - cy) * dx).abs();
+                (along > 0).then_some((along + 2 * across, t))
+            })
+            .min_by_key(|&(score, _)| score)
+            .map_or(self.focus, |(_, t)| t)
+    }
+
+    /// Tab and the arrows move the focus ring; Enter and Space click the
+    /// focused tile, +/- scroll it, R resets it as a right click does and
+    /// Shift+R as a middle click does. The first key only shows the ring.
+    fn handle_key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> Update {
+        let lay = self.layout();
+        let moved = match event.keysym {
+            Keysym::Tab if modifiers.shift => Some(self.focus_next(-1)),
+            Keysym::Tab => Some(self.focus_next(1)),
+            Keysym::ISO_Left_Tab => Some(self.focus_next(-1)),
+            Keysym::Left => Some(self.focus_toward(&lay, -1, 0)),
+            Keysym::Right => Some(self.focus_toward(&lay, 1, 0)),
+            Keysym::Up => Some(self.focus_toward(&lay, 0, -1)),
+            Keysym::Down => Some(self.focus_toward(&lay, 0, 1)),
+            Keysym::Escape => Some(HoverTile::None),
+            _ => None,
+        };
+        if let Some(focus) = moved {
+            if focus == self.focus { return Update::None; }
+            self.focus = focus;
+            return Update::Redraw;
+        }
+        let action = matches!(event.keysym,
+            Keysym::Return | Keysym::KP_Enter | Keysym::space | Keysym::plus | Keysym::equal | Keysym::KP_Add
+            | Keysym::minus | Keysym::KP_Subtract | Keysym::r | Keysym::R);
+        if !action { return Update::None; }
+        let Some(r) = tile_rect(&lay, self.focus) else {
+            self.focus = FOCUS_ORDER[0];
+            return Update::Redraw;
+        };
+        let (x, y) = ((r.x + r.w / 2) as f64, (r.y + r.h / 2) as f64);
+        match event.keysym {
+            Keysym::plus | Keysym::equal | Keysym::KP_Add => self.handle_scroll(x, y, -1.0),
+            Keysym::minus | Keysym::KP_Subtract => self.handle_scroll(x, y, 1.0),
+            Keysym::r => self.handle_right_click(x, y),
+            Keysym::R => self.handle_middle_click(x, y),
+            _ => self.handle_click(x, y),
+        }
+    }
+
+    fn unfocused(&mut self) -> Update {
+        if self.focus == HoverTile::None { return Update::None; }
+        self.focus = HoverTile::None;
+        Update::Redraw
+    }
+
+    // --- Timers ---
+    // Shared by clicks and D-Bus; clicks only ever pass 1 or 2, so their
+    // errors are ignored.
+
+    /// Timer `n` (1 or 2): its duration, start time and scroll-adjusted base.
+    fn timer(&mut self, n: u32) -> Result<(&mut i64, &mut u64, i64), String> {
+        match n {
+            1 => Ok((&mut self.timer1_duration, &mut self.timer1_started, self.timer1_base)),
+            2 => Ok((&mut self.timer2_duration, &mut self.timer2_started, self.timer2_base)),
+            _ => Err(format!("no timer {n}; they are 1 and 2")),
+        }
+    }
+
+    fn start_timer(&mut self, n: u32) -> Result<(), String> {
+        let (_, started, _) = self.timer(n)?;
+        if *started == 0 { *started = now_unix(); }
+        (self.save_state)(&self.state());
+        Ok(())
+    }
+
+    fn pause_timer(&mut self, n: u32) -> Result<(), String> {
+        let (duration, started, _) = self.timer(n)?;
+        if *started > 0 {
+            *duration = timer_remaining(*duration, *started);
+            *started = 0;
+        }
+        (self.save_state)(&self.state());
+        Ok(())
+    }
+
+    /// Pause timer `n` if it's running, else start it, as a left click does.
+    fn toggle_timer(&mut self, n: u32) -> Result<(), String> {
+        let (_, started, _) = self.timer(n)?;
+        if *started > 0 { self.pause_timer(n) } else { self.start_timer(n) }
+    }
+
+    /// Stop timer `n` and set it back to its base, as a right click does.
+    fn reset_timer(&mut self, n: u32) -> Result<(), String> {
+        let (duration, started, base) = self.timer(n)?;
+        *duration = base;
+        *started = 0;
+        (self.save_state)(&self.state());
+        Ok(())
+    }
+
+    fn tick(&mut self) -> Update {
+        // Long press detection: after grace period, decide mode based on whether key was released
+        if self.long_press.is_none() && self.startup.elapsed().as_millis() >= LONG_PRESS_GRACE_MS as u128 {
+            self.long_press = Some(!GOT_SIGUSR2.load(Ordering::Acquire));
+        }
+        if self.long_press == Some(true) && GOT_SIGUSR2.load(Ordering::Acquire) {
+            return Update::Exit;
+        }
+        let now = now_unix();
+        if now.saturating_sub(self.audio_read_at) >= AUDIO_REFRESH_SECS
+            && now.saturating_sub(self.volume_set_at) >= AUDIO_REFRESH_COOLDOWN
+        {
+            self.refresh_audio();
+        }
+        // Poll background weather fetch
+        let done = match self.weather_fetch.as_mut() {
+            Some(child) => child.try_wait().ok().flatten().is_some(),
+            None => false,
+        };
+        if done
+            && let Ok(output) = self.weather_fetch.take().unwrap().wait_with_output()
+            && output.status.success()
+        {
+            let text = String::from_utf8_lossy(&output.stdout);
+            // Scope to "current":{ to skip "current_units"
+            if let Some(ci) = text.find("\"current\":{") {
+                let s = &text[ci..];
+                let num_at = |s: &str, needle: &str| -> Option<f64> {
+                    let after = s[s.find(needle)? + needle.len()..].trim_start();
+                    after[..after.find([',', '}'])?].trim().parse().ok()
+                };
+                let temp = num_at(s, "\"temperature_2m\":");
+                let feels = num_at(s, "\"apparent_temperature\":");
+                let code = num_at(s, "\"weather_code\":").map(|v| v as u32);
+                let is_day = num_at(s, "\"is_day\":").map(|v| v as u32 == 1);
+                if let (Some(temp), Some(feels), Some(code), Some(is_day)) = (temp, feels, code, is_day) {
+                    self.weather_temp = temp;
+                    self.weather_feels = feels;
+                    self.weather_code = code;
+                    self.weather_is_day = is_day;
+                    self.weather_fetched = now_unix();
+                    (self.save_state)(&self.state());
+                }
+            }
+        }
+        Update::Redraw
+    }
+}
+
+impl Widget for App {
+    fn draw(&mut self, pixmap: &mut Pixmap) { self.draw(pixmap); }
+    fn draw_dirty(&mut self, pixmap: &mut Pixmap) -> Option<Vec<Rect>> { Some(self.draw_dirty(pixmap)) }
+    fn backdrop(&self) -> Backdrop { self.backdrop() }
+    fn resize(&mut self, width: u32, height: u32, scale: f32) {
+        self.width = width;
+        self.height = height;
+        self.scale = scale;
+    }
+    fn key(&mut self, event: &KeyEvent, modifiers: &Modifiers) -> Update { self.handle_key(event, modifiers) }
+    fn unfocused(&mut self) -> Update { self.unfocused() }
+    fn pointer(&mut self, event: &PointerEvent) -> Update { self.handle_pointer(event) }
+    fn cursor(&self, x: f64, y: f64) -> Shape { self.cursor(x, y) }
+}
+
+// --- D-Bus ---
+
+struct Control(Remote<App>);
+
+impl Control {
+    /// Run `f` on timer `n` and repaint.
+    async fn timer(&self, n: u32, f: fn(&mut App, u32) -> Result<(), String>) -> fdo::Result<()> {
+        self.0.call(move |win| {
+            f(win.widget(), n)?;
+            win.apply(Update::Redraw);
+            Ok(())
+        }).await?.map_err(fdo::Error::Failed)
+    }
+}
+
+#[interface(name = "org.widgets.Wavedash")]
+impl Control {
+    async fn start_timer(&self, timer: u32) -> fdo::Result<()> { self.timer(timer, App::start_timer).await }
+
+    async fn pause_timer(&self, timer: u32) -> fdo::Result<()> { self.timer(timer, App::pause_timer).await }
+
+    /// To its scroll-adjusted length, as a right click does.
+    async fn reset_timer(&self, timer: u32) -> fdo::Result<()> { self.timer(timer, App::reset_timer).await }
+
+    async fn set_volume(&self, volume: f64) -> fdo::Result<()> {
+        self.0.call(move |win| {
+            win.widget().set_volume(volume as f32);
+            win.apply(Update::Redraw);
+        }).await
+    }
+
+    /// Seconds left on timer 1.
+    #[zbus(property)]
+    async fn timer1_remaining(&self) -> fdo::Result<i64> {
+        self.0.call(|win| { let app = win.widget(); timer_remaining(app.timer1_duration, app.timer1_started) }).await
+    }
+
+    /// Seconds left on timer 2.
+    #[zbus(property)]
+    async fn timer2_remaining(&self) -> fdo::Result<i64> {
+        self.0.call(|win| { let app = win.widget(); timer_remaining(app.timer2_duration, app.timer2_started) }).await
+    }
+
+    #[zbus(property)]
+    async fn timer1_running(&self) -> fdo::Result<bool> { self.0.call(|win| win.widget().timer1_started > 0).await }
+
+    #[zbus(property)]
+    async fn timer2_running(&self) -> fdo::Result<bool> { self.0.call(|win| win.widget().timer2_started > 0).await }
+
+    /// 1.0 is 100%.
+    #[zbus(property)]
+    async fn volume(&self) -> fdo::Result<f64> { self.0.call(|win| win.widget().volume as f64).await }
+
+    #[zbus(property)]
+    async fn muted(&self) -> fdo::Result<bool> { self.0.call(|win| win.widget().muted).await }
 }
 
 // --- Time helpers (no chrono dependency, use libc) ---
@@ -838,256 +1179,96 @@ fn format_date() -> String {
 
 // --- Rendering helpers ---
 
-fn alpha_color(c: [u8; 3], a: f32) -> [u8; 3] {
-    [(c[0] as f32 * a) as u8, (c[1] as f32 * a) as u8, (c[2] as f32 * a) as u8]
+fn alpha_color(c: [u8; 4], a: f32) -> [u8; 4] {
+    [(c[0] as f32 * a) as u8, (c[1] as f32 * a) as u8, (c[2] as f32 * a) as u8, c[3]]
 }
 
-fn hover_color(c: [u8; 3], hovered: bool) -> [u8; 3] {
+fn hover_color(c: [u8; 4], hovered: bool) -> [u8; 4] {
     if !hovered { return c; }
     let b = |v: f32| (v + (255.0 - v) * 0.25).min(255.0) as u8;
-    [b(c[0] as f32), b(c[1] as f32), b(c[2] as f32)]
-}
-
-fn fill_rect(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3]) {
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i] = c[0]; data[i + 1] = c[1]; data[i + 2] = c[2]; data[i + 3] = 0xff;
-        }
-    }
-}
-
-fn fill_rect_alpha(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3], a: u8) {
-    if a == 0xff { return fill_rect(data, pw, ph, x, y, w, h, c); }
-    if a == 0 { return; }
-    let a32 = a as u32;
-    let inv = 255 - a32;
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            data[i]     = ((c[0] as u32 * a32 + data[i] as u32 * inv) / 255) as u8;
-            data[i + 1] = ((c[1] as u32 * a32 + data[i + 1] as u32 * inv) / 255) as u8;
-            data[i + 2] = ((c[2] as u32 * a32 + data[i + 2] as u32 * inv) / 255) as u8;
-            data[i + 3] = ((a32 + data[i + 3] as u32 * inv / 255)) as u8;
-        }
-    }
+    [b(c[0] as f32), b(c[1] as f32), b(c[2] as f32), c[3]]
 }
 
-fn fill_rounded_rect_alpha(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, r: u32, c: [u8; 3], a: u8) {
-    if a == 0 || w == 0 || h == 0 { return; }
-    let r = r.min(w / 2).min(h / 2);
-    let a32 = a as u32;
-    let inv = 255 - a32;
-    for py in y..y.saturating_add(h).min(ph) {
-        for px in x..x.saturating_add(w).min(pw) {
-            let lx = px - x;
-            let ly = py - y;
-            // Check if pixel is in a corner region and outside the rounded arc
-            let in_corner = (lx < r && ly < r) || (lx >= w - r && ly < r) || (lx < r && ly >= h - r) || (lx >= w - r && ly >= h - r);
-            if in_corner {
-                let cx = if lx < r { r } else { w - r };
-                let cy = if ly < r { r } else { h - r };
-                let dx = lx as f32 - cx as f32 + 0.5;
-                let dy = ly as f32 - cy as f32 + 0.5;
-                if dx * dx + dy * dy > (r as f32) * (r as f32) { continue; }
-            }
-            let i = (py as usize * pw as usize + px as usize) * 4;
-            if a == 0xff {
-                data[i] = c[0]; data[i + 1] = c[1]; data[i + 2] = c[2]; data[i + 3] = 0xff;
-            } else {
-                data[i]     = ((c[0] as u32 * a32 + data[i] as u32 * inv) / 255) as u8;
-                data[i + 1] = ((c[1] as u32 * a32 + data[i + 1] as u32 * inv) / 255) as u8;
-                data[i + 2] = ((c[2] as u32 * a32 + data[i + 2] as u32 * inv) / 255) as u8;
-                data[i + 3] = ((a32 + data[i + 3] as u32 * inv / 255)) as u8;
-            }
-        }
-    }
-}
-
-fn make_attrs(family: &str, weight: Weight) -> Attrs<'_> {
-    Attrs::new().weight(weight).family(cosmic_text::Family::Name(family))
-}
-
-fn measure_text(font_system: &mut FontSystem, text: &str, font_size: f32, family: &str, weight: Weight) -> f32 {
-    let mut buf = Buffer::new(font_system, Metrics::new(font_size, font_size * LINE_HEIGHT));
-    buf.set_size(font_system, None, None);
-    buf.set_text(font_system, text, &make_attrs(family, weight), Shaping::Advanced, None);
-    buf.shape_until_scroll(font_system, false);
-    buf.layout_runs().next().map_or(0.0, |r| r.line_w)
-}
-
-fn render_text(
-    pixmap: &mut Pixmap, font_system: &mut FontSystem, swash_cache: &mut SwashCache,
-    text: &str, x: f32, y: f32, font_size: f32, max_w: f32, max_h: f32, color: [u8; 3],
-    family: &str, weight: Weight,
-) {
-    let line_h = font_size * LINE_HEIGHT;
-    let mut buf = Buffer::new(font_system, Metrics::new(font_size, line_h));
-    buf.set_size(font_system, Some(max_w), Some(max_h));
-    buf.set_text(font_system, text, &make_attrs(family, weight), Shaping::Advanced, None);
-    buf.shape_until_scroll(font_system, false);
-
-    let pw = pixmap.width() as i32;
-    let ph = pixmap.height() as i32;
-    for run in buf.layout_runs() {
-        for glyph in run.glyphs.iter() {
-            let physical = glyph.physical((x, y + run.line_y), 1.0);
-            if let Some(image) = swash_cache.get_image_uncached(font_system, physical.cache_key) {
-                let x0 = physical.x + image.placement.left;
-                let y0 = physical.y - image.placement.top;
-                let w = image.placement.width as i32;
-                let h = image.placement.height as i32;
-                match image.content {
-                    SwashContent::Mask => blit_mask(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data, &color),
-                    SwashContent::Color => blit_color(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data),
-                    SwashContent::SubpixelMask => {}
-                }
-            }
-        }
-    }
-}
+fn rgb(c: [u8; 4]) -> [u8; 3] { [c[0], c[1], c[2]] }
 
-fn blit_mask(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, mask: &[u8], color: &[u8; 3]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let a = mask[(gy * w + gx) as usize] as u32;
-            if a == 0 { continue; }
-            let i = (py * pw + px) as usize * 4;
-            let inv = 255 - a;
-            data[i]     = ((color[0] as u32 * a + data[i] as u32 * inv) / 255) as u8;
-            data[i + 1] = ((color[1] as u32 * a + data[i + 1] as u32 * inv) / 255) as u8;
-            data[i + 2] = ((color[2] as u32 * a + data[i + 2] as u32 * inv) / 255) as u8;
-            data[i + 3] = ((a + data[i + 3] as u32 * inv / 255)) as u8;
-        }
-    }
-}
+// --- Main ---
 
-fn blit_color(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, rgba: &[u8]) {
-    for gy in 0..h {
-        let py = y0 + gy;
-        if py < 0 || py >= ph { continue; }
-        for gx in 0..w {
-            let px = x0 + gx;
-            if px < 0 || px >= pw { continue; }
-            let si = (gy * w + gx) as usize * 4;
-            let a = rgba[si + 3] as u32;
-            if a == 0 { continue; }
-            let i = (py * pw + px) as usize * 4;
-            let inv = 255 - a;
-            data[i]     = (rgba[si] as u32 * a / 255 + data[i] as u32 * inv / 255) as u8;
-            data[i + 1] = (rgba[si + 1] as u32 * a / 255 + data[i + 1] as u32 * inv / 255) as u8;
-            data[i + 2] = (rgba[si + 2] as u32 * a / 255 + data[i + 2] as u32 * inv / 255) as u8;
-            data[i + 3] = (a + data[i + 3] as u32 * inv / 255) as u8;
-        }
-    }
+/// Text and icon fonts, returning their family names.
+fn load_fonts(cfg: &Config) -> Result<(Fonts, String, String), String> {
+    let mut db = font_db();
+    let font_family = load_font(&mut db, &cfg.font).ok_or(format!("font not found: {}", cfg.font))?;
+    let icon_family = load_font(&mut db, &cfg.icon_font).ok_or(format!("icon font not found: {}", cfg.icon_font))?;
+    // Load FA Regular for outline icons (same family, Weight::NORMAL)
+    load_font(&mut db, "/usr/share/fonts/OTF/Font Awesome 7 Free-Regular-400.otf");
+    Ok((Fonts::new(db, &cfg.font_fallback), font_family, icon_family))
 }
 
-// --- Wayland handler boilerplate ---
+const CLI: Spec = Spec {
+    name: "wavedash",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Status overlay: clock, weather, timers, volume and toggles.",
+    usage: &["[OPTIONS]"],
+    opts: &[
+        Opt { name: "--time", value: Some("UNIX_SECS"), help: "Clock time for --render-to and --replay" },
+        Opt { name: "--replay", value: Some("SCRIPT"), help: "Feed scripted input without a compositor, then exit" },
+    ],
+};
 
-impl CompositorHandler for App {
-    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
-    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
-    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
-    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
-    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+/// Keyboard focus only when asked for: it would otherwise take keys from
+/// the focused window whenever wavedash is shown.
+fn keyboard_mode(enabled: bool) -> KeyboardInteractivity {
+    if enabled { KeyboardInteractivity::OnDemand } else { KeyboardInteractivity::None }
 }
 
-impl OutputHandler for App {
-    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
-    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
-    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+/// Per-run options from the command line.
+struct Invocation {
+    render_to: Option<String>,
+    replay: Option<String>,
+    time: Option<u64>,
+    scale: f32,
+    dbus: bool,
 }
 
-impl SeatHandler for App {
-    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, capability: Capability) {
-        if capability == Capability::Pointer && self.pointer.is_none() {
-            self.pointer = Some(self.seat_state.get_pointer(qh, &seat).unwrap());
+fn parse_args(opts: &Parsed, cfg: &mut Config) -> Result<Invocation, String> {
+    let mut inv = Invocation { render_to: None, replay: None, time: None, scale: 1.0, dbus: false };
+    for (name, value) in opts {
+        let v = value.clone().unwrap_or_default();
+        match *name {
+            "--render-to" => inv.render_to = Some(v),
+            "--replay" => inv.replay = Some(v),
+            "--time" => inv.time = Some(cli::value(name, &v)?),
+            "--scale" => inv.scale = cli::value(name, &v)?,
+            "--dbus" => inv.dbus = true,
+            "--output" => cfg.output = Some(v),
+            "--set" => cli::set(cfg, &v)?,
+            _ => {}
         }
     }
-    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, _: Capability) {}
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    Ok(inv)
 }
 
-impl PointerHandler for App {
-    fn pointer_frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, pointer: &wl_pointer::WlPointer, events: &[PointerEvent]) {
-        for event in events {
-            match event.kind {
-                PointerEventKind::Enter { serial } => {
-                    let device = self.cursor_shape_manager.get_shape_device(pointer, qh);
-                    device.set_shape(serial, Shape::Default);
-                    device.destroy();
-                }
-                PointerEventKind::Press { button: 0x110, .. } => {
-                    self.handle_click(event.position.0, event.position.1);
-                }
-                PointerEventKind::Press { button: 0x111, .. } => {
-                    self.handle_right_click(event.position.0, event.position.1);
-                }
-                PointerEventKind::Press { button: 0x112, .. } => {
-                    self.handle_middle_click(event.position.0, event.position.1);
-                }
-                PointerEventKind::Release { .. } => {}
-                PointerEventKind::Motion { .. } => {
-                    let new_hover = self.hover_tile_at(event.position.0, event.position.1);
-                    if new_hover != self.hover {
-                        self.hover = new_hover;
-                        self.draw();
-                    }
-                }
-                PointerEventKind::Leave { .. } => {
-                    if self.hover != HoverTile::None {
-                        self.hover = HoverTile::None;
-                        self.draw();
-                    }
-                }
-                PointerEventKind::Axis { ref vertical, .. } => {
-                    if vertical.absolute != 0.0 {
-                        self.handle_scroll(event.position.0, event.position.1, vertical.absolute);
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
-}
-
-impl ShmHandler for App {
-    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
-}
-
-impl LayerShellHandler for App {
-    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
-        self.exit = true;
-    }
-    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
-        if configure.new_size.0 > 0 { self.width = configure.new_size.0; }
-        if configure.new_size.1 > 0 { self.height = configure.new_size.1; }
-        self.draw();
+/// Reload config and colors from disk, keeping command-line overrides;
+/// returns the files to keep watching.
+fn reload(win: &mut Window<App>, opts: &Parsed) -> Vec<PathBuf> {
+    let mut cfg: Config = load_config("wavedash");
+    parse_args(opts, &mut cfg).ok();
+    let app = &mut win.widget;
+    app.colors = load_colors(cfg.color_file.as_deref());
+    match load_fonts(&cfg) {
+        Ok((fonts, font_family, icon_family)) => (app.fonts, app.font_family, app.icon_family) = (fonts, font_family, icon_family),
+        Err(e) => eprintln!("wavedash: {e}"),
     }
+    app.bt_device_1 = cfg.bt_device_1;
+    app.bt_device_2 = cfg.bt_device_2;
+    app.timer1_config = cfg.timer1_duration as i64;
+    app.timer2_config = cfg.timer2_duration as i64;
+    win.opts.placement = cfg.placement.clone();
+    win.opts.style = cfg.style.clone();
+    win.opts.keyboard = keyboard_mode(cfg.keyboard);
+    win.reload();
+    watch::config_files("wavedash", cfg.color_file.as_deref())
 }
 
-impl ProvidesRegistryState for App {
-    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
-    registry_handlers![OutputState, SeatState];
-}
-
-delegate_compositor!(App);
-delegate_output!(App);
-delegate_seat!(App);
-delegate_pointer!(App);
-delegate_shm!(App);
-delegate_layer!(App);
-delegate_registry!(App);
-
-// --- Main ---
-
 fn main() {
     // Install SIGUSR2 handler immediately — must be before any setup so we
     // catch the release signal even if the key is released during startup.
@@ -1099,10 +1280,21 @@ fn main() {
         libc::sigaction(libc::SIGUSR2, &sa, std::ptr::null_mut());
     }
 
-    let cfg = load_config();
-    let colors = load_colors(cfg.color_file.as_deref());
-    let st = load_state(&cfg);
-    let weather_fetch = if cfg.weather_lat != 0.0 && now_unix() - st.weather_fetched > WEATHER_MAX_AGE {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = CLI.parse_or_exit(&args);
+    trace::start("wavedash", &opts);
+    cli::config_commands::<Config>("wavedash", &opts);
+
+    let mut cfg: Config = load_config("wavedash");
+    let inv = parse_args(&opts, &mut cfg).unwrap_or_else(|e| CLI.fail(&e));
+    let colors: Colors = load_colors(cfg.color_file.as_deref());
+
+    // Headless runs use a pinned clock and fake audio/weather instead of the live system
+    let headless = inv.render_to.is_some() || inv.replay.is_some();
+    if headless { FIXED_NOW.set(inv.time.unwrap_or(HEADLESS_TIME)).ok(); }
+
+    let st = if headless { headless_state(&cfg) } else { load_state(&cfg) };
+    let weather_fetch = if !headless && cfg.weather_lat != 0.0 && now_unix() - st.weather_fetched > WEATHER_MAX_AGE {
         Command::new("curl")
             .args(["-s", "--max-time", "5", &format!(
                 "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}&current=temperature_2m,apparent_temperature,weather_code,is_day&temperature_unit=fahrenheit",
@@ -1114,60 +1306,22 @@ fn main() {
         None
     };
 
-    let notif_paused = Command::new("dunstctl").arg("is-paused").output()
+    let notif_paused = !headless && Command::new("dunstctl").arg("is-paused").output()
         .map(|o| String::from_utf8_lossy(&o.stdout).trim() == "true").unwrap_or(false);
 
-    let (volume, muted) = get_volume();
-    let headphones = is_headphones(&cfg.bt_device_1);
-
-    let conn = Connection::connect_to_env().unwrap();
-    let (globals, event_queue) = registry_queue_init::<App>(&conn).unwrap();
-    let qh = event_queue.handle();
-
-    let mut event_loop: EventLoop<App> = EventLoop::try_new().unwrap();
-    let loop_handle = event_loop.handle();
-    WaylandSource::new(conn.clone(), event_queue).insert(loop_handle).unwrap();
-
-    let compositor = CompositorState::bind(&globals, &qh).unwrap();
-    let layer_shell = LayerShell::bind(&globals, &qh).unwrap();
-    let shm = Shm::bind(&globals, &qh).unwrap();
-    let cursor_shape_manager = CursorShapeManager::bind(&globals, &qh).unwrap();
-
-    let surface = compositor.create_surface(&qh);
-    let layer = layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("wavedash"), None);
-    layer.set_size(WIDTH, HEIGHT);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::None);
-    layer.wl_surface().commit();
-
-    let pool = SlotPool::new((WIDTH * HEIGHT * 4) as usize, &shm).unwrap();
-
-    let font_data = std::fs::read(expand_path(&cfg.font)).expect("failed to read font file");
-    let icon_data = std::fs::read(expand_path(&cfg.icon_font)).expect("failed to read icon font file");
-    let mut db = cosmic_text::fontdb::Database::new();
-    db.load_font_data(font_data);
-    let font_family = db.faces().next().expect("font file contains no faces").families[0].0.clone();
-    db.load_font_data(icon_data);
-    let icon_family = db.faces().last().expect("icon font file contains no faces").families[0].0.clone();
-    // Load FA Regular for outline icons (same family, Weight::NORMAL)
-    if let Ok(data) = std::fs::read("/usr/share/fonts/OTF/Font Awesome 7 Free-Regular-400.otf") {
-        db.load_font_data(data);
-    }
-    let font_system = FontSystem::new_with_locale_and_db("en-US".into(), db);
+    let (volume, muted) = if headless { (0.6, false) } else { get_volume() };
+    let headphones = !headless && is_headphones(&cfg.bt_device_1);
+
+    let (fonts, font_family, icon_family) = load_fonts(&cfg).unwrap_or_else(|e| {
+        eprintln!("wavedash: {e}");
+        std::process::exit(1);
+    });
 
     let mut app = App {
-        registry_state: RegistryState::new(&globals),
-        seat_state: SeatState::new(&globals, &qh),
-        output_state: OutputState::new(&globals, &qh),
-        shm,
-        layer,
-        pointer: None,
-        cursor_shape_manager,
-        pool,
         width: WIDTH,
         height: HEIGHT,
-        exit: false,
-        font_system,
-        swash_cache: SwashCache::new(),
+        scale: 1.0,
+        fonts,
         colors,
         font_family,
         icon_family,
@@ -1181,12 +1335,15 @@ fn main() {
         bt_device_1: cfg.bt_device_1,
         bt_device_2: cfg.bt_device_2,
         is_dim: false,
+        shown: Default::default(),
         hover: HoverTile::None,
+        focus: HoverTile::None,
         timer1_base: st.timer1_base,
         timer2_base: st.timer2_base,
         timer1_config: cfg.timer1_duration as i64,
         timer2_config: cfg.timer2_duration as i64,
         volume_set_at: 0,
+        audio_read_at: now_unix(),
         weather_temp: st.weather_temp,
         weather_feels: st.weather_feels,
         weather_code: st.weather_code,
@@ -1196,64 +1353,61 @@ fn main() {
         notif_paused,
         long_press: None,
         startup: Instant::now(),
+        spawn: if inv.replay.is_some() { replay::print_run } else { keys::run },
+        save_state: if inv.replay.is_some() { |_| {} } else { save_state },
     };
 
-    // 1-second timer for clock/timer redraws
-    let timer = Timer::from_duration(std::time::Duration::from_millis(TICK_MS));
-    event_loop.handle().insert_source(timer, |_, _, app| {
-        // Long press detection: after grace period, decide mode based on whether key was released
-        if app.long_press.is_none() && app.startup.elapsed().as_millis() >= LONG_PRESS_GRACE_MS as u128 {
-            app.long_press = Some(!GOT_SIGUSR2.load(Ordering::Acquire));
-        }
-        if app.long_press == Some(true) && GOT_SIGUSR2.load(Ordering::Acquire) {
-            app.exit = true;
-            return TimeoutAction::Drop;
+    if let Some(path) = inv.render_to {
+        if let Err(e) = render_to_png(&mut app, WIDTH, HEIGHT, inv.scale, &cfg.style, Path::new(&path)) {
+            eprintln!("wavedash: {e}");
+            std::process::exit(1);
         }
-        if now_unix() - app.volume_set_at >= AUDIO_REFRESH_COOLDOWN {
-            app.refresh_audio();
-        }
-        // Poll background weather fetch
-        let done = match app.weather_fetch.as_mut() {
-            Some(child) => child.try_wait().ok().flatten().is_some(),
-            None => false,
-        };
-        if done {
-            let child = app.weather_fetch.take().unwrap();
-            if let Ok(output) = child.wait_with_output() {
-                if output.status.success() {
-                    let text = String::from_utf8_lossy(&output.stdout);
-                    // Scope to "current":{ to skip "current_units"
-                    if let Some(ci) = text.find("\"current\":{") {
-                        let s = &text[ci..];
-                        let num_at = |s: &str, needle: &str| -> Option<f64> {
-                            let after = s[s.find(needle)? + needle.len()..].trim_start();
-                            after[..after.find(|c: char| c == ',' || c == '}')?].trim().parse().ok()
-                        };
-                        let temp = num_at(s, "\"temperature_2m\":");
-                        let feels = num_at(s, "\"apparent_temperature\":");
-                        let code = num_at(s, "\"weather_code\":").map(|v| v as u32);
-                        let is_day = num_at(s, "\"is_day\":").map(|v| v as u32 == 1);
-                        if let (Some(temp), Some(feels), Some(code), Some(is_day)) = (temp, feels, code, is_day) {
-                            app.weather_temp = temp;
-                            app.weather_feels = feels;
-                            app.weather_code = code;
-                            app.weather_is_day = is_day;
-                            app.weather_fetched = now_unix();
-                            save_state(&app.state());
-                        }
-                    }
-                }
-            }
+        return;
+    }
+
+    let options = Options {
+        namespace: "wavedash",
+        width: WIDTH,
+        height: HEIGHT,
+        placement: cfg.placement.clone(),
+        style: cfg.style.clone(),
+        keyboard: keyboard_mode(cfg.keyboard),
+        output: cfg.output,
+        resident: false,
+    };
+    let bus = if inv.dbus {
+        dbus::connect(Builder::session(), Control).inspect_err(|e| eprintln!("wavedash: D-Bus: {e}")).ok()
+    } else {
+        None
+    };
+
+    if let Some(path) = &inv.replay {
+        if let Err(e) = replay::run(app, options, inv.scale, Path::new(path), bus) {
+            eprintln!("wavedash: replay: {e}");
+            std::process::exit(1);
         }
-        app.draw();
-        TimeoutAction::ToDuration(std::time::Duration::from_millis(TICK_MS))
+        return;
+    }
+
+    let mut event_loop: EventLoop<Window<App>> = EventLoop::try_new().unwrap();
+    let mut win = Window::new(&event_loop, options, app);
+
+    // Periodic tick for clock/timer redraws
+    let timer = Timer::from_duration(Duration::from_millis(TICK_MS));
+    event_loop.handle().insert_source(timer, |_, _, win| {
+        let update = win.widget.tick();
+        win.apply(update);
+        if win.exit { return TimeoutAction::Drop; }
+        TimeoutAction::ToDuration(Duration::from_millis(TICK_MS))
     }).unwrap();
 
-    loop {
-        event_loop.dispatch(std::time::Duration::from_millis(TICK_MS), &mut app).unwrap();
-        if app.exit {
-            save_state(&app.state());
-            break;
-        }
+    if let Some(bus) = bus && let Err(e) = bus.attach(&event_loop.handle()) {
+        eprintln!("wavedash: D-Bus: {e}");
+    }
+    let watched = watch::config_files("wavedash", cfg.color_file.as_deref());
+    if let Err(e) = watch::watch(&event_loop.handle(), watched, move |win| reload(win, &opts)) {
+        eprintln!("wavedash: cannot watch config: {e}");
     }
+    win.run(&mut event_loop);
+    save_state(&win.widget.state());
 }
@@ -0,0 +1,80 @@
+//! `org.widgets.Wavedash` on a private bus, with wavedash under `--replay`:
+//! its clock is pinned, so a running timer's remaining time holds still.
+
+use std::path::PathBuf;
+use std::process::Child;
+use widgets_core::replay::testing::{self, Bus};
+use zbus::blocking::Proxy;
+
+const IFACE: &str = "org.widgets.Wavedash";
+
+/// 10 and 20 minute timers, and an icon font that exists.
+fn config() -> String {
+    format!("icon_font = {:?}\ntimer1_duration = 600\ntimer2_duration = 1200\n", testing::font().display().to_string())
+}
+
+/// Wavedash serving on `bus` until the test ends it.
+fn start(test: &str, bus: &Bus) -> (PathBuf, Child, Proxy<'static>) {
+    let home = testing::scratch(&format!("wavedash-dbus-{test}"));
+    let script = r#"steps = [{ serve_until = "exit" }]"#;
+    let mut child = testing::serve(env!("CARGO_BIN_EXE_wavedash"), &home, &config(), script, &[], bus);
+    let proxy = bus.proxy(IFACE, &mut child);
+    (home, child, proxy)
+}
+
+/// Stop wavedash and return what it printed to stderr.
+fn stop(home: PathBuf, mut child: Child) -> String {
+    child.kill().unwrap();
+    let out = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&home).ok();
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+#[test]
+fn timers() {
+    let bus = Bus::start();
+    let (home, child, wavedash) = start("timers", &bus);
+    let running = |n: u32| wavedash.get_property::<bool>(&format!("Timer{n}Running")).unwrap();
+    let remaining = |n: u32| wavedash.get_property::<i64>(&format!("Timer{n}Remaining")).unwrap();
+    assert_eq!((running(1), remaining(1)), (false, 600));
+    assert_eq!((running(2), remaining(2)), (false, 1200));
+
+    wavedash.call::<_, _, ()>("StartTimer", &(2u32,)).unwrap();
+    assert_eq!((running(1), running(2)), (false, true));
+    assert_eq!(remaining(2), 1200);
+    wavedash.call::<_, _, ()>("PauseTimer", &(2u32,)).unwrap();
+    assert!(!running(2));
+    wavedash.call::<_, _, ()>("StartTimer", &(1u32,)).unwrap();
+    wavedash.call::<_, _, ()>("ResetTimer", &(1u32,)).unwrap();
+    assert_eq!((running(1), remaining(1)), (false, 600));
+
+    let err = wavedash.call::<_, _, ()>("StartTimer", &(3u32,)).unwrap_err();
+    assert!(matches!(err, zbus::Error::MethodError(_, Some(ref msg), _) if msg == "no timer 3; they are 1 and 2"), "{err}");
+    let stderr = stop(home, child);
+    assert!(!stderr.contains("replay: run:"), "{stderr}");
+}
+
+#[test]
+fn set_volume() {
+    let bus = Bus::start();
+    let (home, child, wavedash) = start("volume", &bus);
+    wavedash.call::<_, _, ()>("SetVolume", &(0.25f64,)).unwrap();
+    assert_eq!(wavedash.get_property::<f64>("Volume").unwrap(), 0.25);
+    wavedash.call::<_, _, ()>("SetVolume", &(9.0f64,)).unwrap();
+    assert_eq!(wavedash.get_property::<f64>("Volume").unwrap(), 2.0);
+    let stderr = stop(home, child);
+    assert!(stderr.contains("replay: run: wpctl set-volume @DEFAULT_AUDIO_SINK@ 0.25\n"), "{stderr}");
+    assert!(stderr.contains("replay: run: wpctl set-volume @DEFAULT_AUDIO_SINK@ 2.00\n"), "{stderr}");
+}
+
+#[test]
+fn name_is_owned_once() {
+    let bus = Bus::start();
+    let (home, child, _) = start("owned", &bus);
+    let second = testing::serve(env!("CARGO_BIN_EXE_wavedash"), &home, &config(), "steps = []", &[], &bus);
+    let out = second.wait_with_output().unwrap();
+    stop(home, child);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{stderr}");
+    assert!(stderr.starts_with("wavedash: D-Bus: "), "{stderr}");
+}
@@ -0,0 +1,42 @@
+//! Time-based animation values, sampled from `wl_surface.frame` callbacks
+//! (see [`crate::window::Widget::animate`]) so speed doesn't depend on the
+//! display's refresh rate.
+
+use std::time::{Duration, Instant};
+
+pub fn ease_out_cubic(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+/// A value easing from `from` to `to` over `duration`, starting when created.
+#[derive(Clone, Copy)]
+pub struct Tween {
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Tween {
+    pub fn new(from: f32, to: f32, duration: Duration) -> Self {
+        Self { from, to, start: Instant::now(), duration }
+    }
+
+    /// A tween that has already finished at `value`.
+    pub fn at(value: f32) -> Self {
+        Self { from: value, to: value, start: Instant::now(), duration: Duration::ZERO }
+    }
+
+    pub fn progress(&self, now: Instant) -> f32 {
+        if self.duration.is_zero() { return 1.0; }
+        (now.saturating_duration_since(self.start).as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+
+    pub fn value(&self, now: Instant) -> f32 {
+        self.from + (self.to - self.from) * ease_out_cubic(self.progress(now))
+    }
+
+    pub fn done(&self, now: Instant) -> bool {
+        self.progress(now) >= 1.0
+    }
+}
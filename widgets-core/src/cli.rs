@@ -0,0 +1,148 @@
+//! Command-line parsing shared by the widgets.
+//!
+//! Each widget describes its own options in a [`Spec`]; the options every
+//! widget takes ([`COMMON`]) are appended. Parsing is strict: unknown options
+//! and missing values are errors, reported with a pointer to `--help` and
+//! exit status 2.
+
+use std::str::FromStr;
+use crate::config::{all_keys, check_config, default_config, Documented};
+
+/// One accepted option.
+pub struct Opt {
+    pub name: &'static str,
+    /// Placeholder for the value it takes; `None` for a plain flag.
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/// Options every widget accepts.
+pub const COMMON: &[Opt] = &[
+    Opt { name: "--set", value: Some("KEY=VALUE"), help: "Override a config key for this run (repeatable)" },
+    Opt { name: "--output", value: Some("SPEC"), help: "Monitor: connector name, focused or cursor" },
+    Opt { name: "--render-to", value: Some("PNG"), help: "Draw one frame to a PNG and exit" },
+    Opt { name: "--scale", value: Some("FACTOR"), help: "Scale for --render-to" },
+    Opt { name: "--dbus", value: None, help: "Take commands over the session bus as org.widgets.<Name>" },
+    Opt { name: "--trace-startup", value: None, help: "Print startup phase timings to stderr as JSON" },
+    Opt { name: "--check-config", value: None, help: "Validate the config and color file, then exit" },
+    Opt { name: "--print-default-config", value: None, help: "Print the default config with comments, then exit" },
+    Opt { name: "--help", value: None, help: "Show this help (also -h)" },
+    Opt { name: "--version", value: None, help: "Show the version (also -V)" },
+];
+
+/// Parsed options in command-line order, with their values.
+pub type Parsed = Vec<(&'static str, Option<String>)>;
+
+/// A widget's command line.
+pub struct Spec {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub about: &'static str,
+    /// Usage lines after the program name.
+    pub usage: &'static [&'static str],
+    pub opts: &'static [Opt],
+}
+
+impl Spec {
+    fn all(&self) -> impl Iterator<Item = &Opt> {
+        self.opts.iter().chain(COMMON)
+    }
+
+    pub fn help(&self) -> String {
+        let mut out = format!("{} {}\n{}\n\nUsage:", self.name, self.version, self.about);
+        for (i, usage) in self.usage.iter().enumerate() {
+            let pad = if i == 0 { " " } else { "       " };
+            out += &format!("{pad}{} {usage}\n", self.name);
+        }
+        out += "\nOptions:\n";
+        let label = |o: &Opt| match o.value {
+            Some(v) => format!("{} <{v}>", o.name),
+            None => o.name.to_string(),
+        };
+        let width = self.all().map(|o| label(o).len()).max().unwrap_or(0);
+        for o in self.all() {
+            out += &format!("  {:width$}  {}\n", label(o), o.help);
+        }
+        out
+    }
+
+    /// Split `args` into `(option, value)` pairs, accepting both
+    /// `--opt value` and `--opt=value`. `-h` and `-V` map to the long forms.
+    pub fn parse(&self, args: &[String]) -> Result<Parsed, String> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            let arg = match args[i].as_str() { "-h" => "--help", "-V" => "--version", a => a };
+            let (name, inline) = match arg.split_once('=') {
+                Some((n, v)) => (n, Some(v.to_string())),
+                None => (arg, None),
+            };
+            let opt = self.all().find(|o| o.name == name).ok_or_else(|| format!("unknown option: {}", args[i]))?;
+            let value = match (opt.value, inline) {
+                (None, Some(_)) => return Err(format!("{name} takes no value")),
+                (None, None) => None,
+                (Some(_), Some(v)) => Some(v),
+                (Some(v), None) => {
+                    i += 1;
+                    Some(args.get(i).cloned().ok_or_else(|| format!("{name} needs a value <{v}>"))?)
+                }
+            };
+            out.push((opt.name, value));
+            i += 1;
+        }
+        Ok(out)
+    }
+
+    /// [`Spec::parse`], handling `--help` and `--version` and exiting on errors.
+    pub fn parse_or_exit(&self, args: &[String]) -> Parsed {
+        let opts = self.parse(args).unwrap_or_else(|e| self.fail(&e));
+        if opts.iter().any(|(n, _)| *n == "--help") {
+            print!("{}", self.help());
+            std::process::exit(0);
+        }
+        if opts.iter().any(|(n, _)| *n == "--version") {
+            println!("{} {}", self.name, self.version);
+            std::process::exit(0);
+        }
+        opts
+    }
+
+    /// Report a command-line error and exit with status 2.
+    pub fn fail(&self, msg: &str) -> ! {
+        eprintln!("{}: {msg}\nTry `{} --help`.", self.name, self.name);
+        std::process::exit(2);
+    }
+}
+
+/// Parse the value of option `name`.
+pub fn value<T: FromStr>(name: &str, v: &str) -> Result<T, String> {
+    v.parse().map_err(|_| format!("invalid value for {name}: {v}"))
+}
+
+/// Handle `--print-default-config` and `--check-config`, which exit.
+pub fn config_commands<C: Documented>(name: &str, opts: &Parsed) {
+    if opts.iter().any(|(n, _)| *n == "--print-default-config") {
+        print!("{}", default_config::<C>());
+        std::process::exit(0);
+    }
+    if opts.iter().any(|(n, _)| *n == "--check-config") {
+        std::process::exit(if check_config::<C>(name) { 0 } else { 1 });
+    }
+}
+
+/// Apply `--set key=value` to `cfg`. The value is read as a TOML value, or
+/// as a bare string if it isn't one (`--set terminal=kitty -e`).
+pub fn set<C: Documented>(cfg: &mut C, assignment: &str) -> Result<(), String> {
+    let (key, raw) = assignment.split_once('=').ok_or_else(|| format!("--set needs KEY=VALUE, got {assignment}"))?;
+    let key = key.trim();
+    if !all_keys::<C>().any(|(k, _)| *k == key) {
+        return Err(format!("--set: unknown config key `{key}`"));
+    }
+    let value = format!("v = {raw}").parse::<toml::Table>().ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+    let mut table = toml::Table::try_from(&*cfg).map_err(|e| e.to_string())?;
+    table.insert(key.to_string(), value);
+    *cfg = toml::Value::Table(table).try_into().map_err(|e: toml::de::Error| format!("--set {key}: {}", e.message()))?;
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Copying to the clipboard without `wl-copy`.
+//!
+//! A widget hands its window a [`Clip`] from
+//! [`crate::window::Widget::take_clip`]. The window offers it through
+//! `wl_data_device` when the copy came from a key press, click or tap, and
+//! through `zwlr_data_control_v1` otherwise (evoke never has keyboard focus),
+//! then writes it to each client that pastes.
+//!
+//! A clipboard empties when the client owning it exits, so a window that
+//! exits while still holding a copy forks, as `wl-copy` does: the parent
+//! exits at once and the child, renamed `<widget>-clip`, serves the copy
+//! until something else is copied.
+
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::ffi::CString;
+use std::os::fd::{AsRawFd, RawFd};
+use std::rc::Rc;
+use smithay_client_toolkit::data_device_manager::data_source::CopyPasteSource;
+use smithay_client_toolkit::data_device_manager::WritePipe;
+use smithay_client_toolkit::reexports::calloop::{LoopHandle, PostAction};
+use smithay_client_toolkit::reexports::protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::ZwlrDataControlOfferV1, zwlr_data_control_source_v1::ZwlrDataControlSourceV1,
+};
+use wayland_client::backend::ObjectId;
+use wayland_client::Proxy;
+
+/// Clipboard types read as text, most preferred first; copied text is
+/// offered as all of them.
+pub const TEXT_MIMES: &[&str] = &["text/plain;charset=utf-8", "UTF8_STRING", "text/plain", "TEXT", "STRING"];
+
+/// Something to copy: the same bytes offered under each of `mimes`.
+#[derive(Clone)]
+pub struct Clip {
+    pub mimes: Vec<String>,
+    pub data: Rc<[u8]>,
+}
+
+impl Clip {
+    pub fn text(text: &str) -> Self {
+        Clip { mimes: TEXT_MIMES.iter().map(|m| m.to_string()).collect(), data: text.as_bytes().into() }
+    }
+
+    pub fn bytes(mime: &str, data: Vec<u8>) -> Self {
+        Clip { mimes: vec![mime.to_string()], data: data.into() }
+    }
+
+    /// The text, if this is a text copy.
+    pub fn as_text(&self) -> Option<&str> {
+        self.mimes.iter().any(|m| m == TEXT_MIMES[0]).then(|| std::str::from_utf8(&self.data).ok()).flatten()
+    }
+}
+
+/// What offers our copy to other clients; dropping it withdraws the offer.
+pub(crate) enum Source {
+    Device(CopyPasteSource),
+    Control(ZwlrDataControlSourceV1),
+}
+
+impl Source {
+    fn id(&self) -> ObjectId {
+        match self {
+            Source::Device(s) => s.inner().id(),
+            Source::Control(s) => s.id(),
+        }
+    }
+}
+
+impl Drop for Source {
+    fn drop(&mut self) {
+        // A CopyPasteSource destroys itself
+        if let Source::Control(s) = self { s.destroy(); }
+    }
+}
+
+/// The window's clipboard state beyond what sctk keeps for `wl_data_device`.
+pub(crate) struct Clipboard {
+    pub manager: Option<ZwlrDataControlManagerV1>,
+    pub device: Option<ZwlrDataControlDeviceV1>,
+    /// The last selection announced to the data-control device. Never read;
+    /// kept to be destroyed when the next one replaces it.
+    pub offer: Option<ZwlrDataControlOfferV1>,
+    /// The same for the primary selection.
+    pub primary_offer: Option<ZwlrDataControlOfferV1>,
+    owned: Option<(Source, Clip)>,
+}
+
+impl Clipboard {
+    pub fn new(manager: Option<ZwlrDataControlManagerV1>) -> Self {
+        Clipboard { manager, device: None, offer: None, primary_offer: None, owned: None }
+    }
+
+    /// Whether the clipboard holds our copy.
+    pub fn holding(&self) -> bool { self.owned.is_some() }
+
+    /// Offer `clip` through `source`, already offering its types,
+    /// withdrawing what we offered before.
+    pub fn own(&mut self, source: Source, clip: Clip) { self.owned = Some((source, clip)); }
+
+    /// Our copy, if `source` is what is offering it.
+    pub fn offered_by(&self, source: &ObjectId) -> Option<Rc<[u8]>> {
+        self.owned.as_ref().filter(|(s, _)| s.id() == *source).map(|(_, clip)| clip.data.clone())
+    }
+
+    /// Another client took the clipboard from `source`.
+    pub fn cancelled(&mut self, source: &ObjectId) {
+        if self.owned.as_ref().is_some_and(|(s, _)| s.id() == *source) { self.owned = None; }
+    }
+}
+
+/// Write `data` to a pasting client's `pipe` as it drains, so a slow reader
+/// doesn't stall the event loop.
+pub(crate) fn serve<D: 'static>(loop_handle: &LoopHandle<'static, D>, pipe: WritePipe, data: Rc<[u8]>) {
+    unsafe {
+        let fd = pipe.as_raw_fd();
+        libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK);
+    }
+    let mut written = 0;
+    let source = loop_handle.insert_source(pipe, move |_, file, _| {
+        match (&**file as &File).write(&data[written..]) {
+            Ok(n) => {
+                written += n;
+                if written < data.len() { PostAction::Continue } else { PostAction::Remove }
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock) => PostAction::Continue,
+            // The reader went away
+            Err(_) => PostAction::Remove,
+        }
+    });
+    if let Err(e) = source { eprintln!("widgets: cannot write clipboard: {e}"); }
+}
+
+/// Turn a forked child into the process that keeps serving a copy: leave
+/// the terminal and any pipe reading our output, so whatever started the
+/// widget sees it finish, go by `<name>-clip` so `pgrep -x <name>` and
+/// `pkill -x <name>` don't find it, and close every socket but the Wayland
+/// connection `keep`, giving up the `msg` socket, its clients and the
+/// session bus name to the next instance.
+pub(crate) fn detach(name: &str, keep: RawFd) {
+    let null = unsafe { libc::open(c"/dev/null".as_ptr(), libc::O_RDWR) };
+    if null < 0 { return; }
+    unsafe {
+        libc::setsid();
+        for fd in 0..3 { libc::dup2(null, fd); }
+        // The kernel keeps 15 bytes of the name
+        if let Ok(comm) = CString::new(format!("{name}-clip")) {
+            libc::prctl(libc::PR_SET_NAME, comm.as_ptr());
+        }
+    }
+    let Ok(fds) = std::fs::read_dir("/proc/self/fd") else { return };
+    let fds: Vec<RawFd> = fds.flatten().filter_map(|e| e.file_name().to_str()?.parse().ok()).collect();
+    for fd in fds {
+        if fd == keep { continue; }
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        // Covered with /dev/null rather than closed: whatever owns the fd
+        // may still close it later, and must not close a reused number
+        if unsafe { libc::fstat(fd, &mut stat) } == 0 && stat.st_mode & libc::S_IFMT == libc::S_IFSOCK {
+            unsafe { libc::dup2(null, fd) };
+        }
+    }
+    unsafe { libc::close(null) };
+}
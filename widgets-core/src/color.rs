@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::config::{config_dir, expand_path};
+use crate::trace;
+
+/// A widget's color set, filled from a walrs-generated color file.
+///
+/// Color files are TOML. Colors are `#rrggbb` or `#rrggbbaa`, or the name of
+/// another key to take its color (`selection = "color4"`); a `<key>_opacity`
+/// key sets a color's alpha from a 0.0..1.0 float. `extends = "<file>"` layers the file over a base
+/// palette, by default `<config_dir>/colors.toml` when it exists, so a
+/// theme's shared colors are written once and each widget's file only names
+/// its own keys.
+pub trait Palette: Default {
+    /// A named color as RGBA, or None if the widget has no such color. Its
+    /// alpha stays as it is for a color given without one.
+    fn color(&mut self, key: &str) -> Option<&mut [u8; 4]>;
+}
+
+/// `#rrggbb` or `#rrggbbaa`, with the alpha if there is one.
+pub fn parse_rgba(s: &str) -> Option<([u8; 3], Option<u8>)> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    match hex.len() {
+        6 => Some((parse_hex(hex)?, None)),
+        8 => Some((parse_hex(&hex[..6])?, Some(u8::from_str_radix(&hex[6..], 16).ok()?))),
+        _ => None,
+    }
+}
+
+pub fn parse_hex(s: &str) -> Option<[u8; 3]> {
+    let s = s.strip_prefix('#').unwrap_or(s);
+    if s.len() != 6 { return None; }
+    Some([u8::from_str_radix(&s[0..2], 16).ok()?,
+          u8::from_str_radix(&s[2..4], 16).ok()?,
+          u8::from_str_radix(&s[4..6], 16).ok()?])
+}
+
+/// A `#rrggbb` or `#rrggbbaa` color written in a config file.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hex(pub [u8; 3], pub Option<u8>);
+
+impl Hex {
+    /// As RGBA, with `alpha` if it was written without one.
+    pub fn or_alpha(self, alpha: u8) -> [u8; 4] {
+        let Hex([r, g, b], a) = self;
+        [r, g, b, a.unwrap_or(alpha)]
+    }
+}
+
+impl TryFrom<String> for Hex {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, String> {
+        parse_rgba(&s).map(|(c, a)| Hex(c, a)).ok_or_else(|| format!("not a #rrggbb or #rrggbbaa color: {s}"))
+    }
+}
+
+impl From<Hex> for String {
+    fn from(Hex([r, g, b], a): Hex) -> Self {
+        match a {
+            Some(a) => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+            None => format!("#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+}
+
+/// Load a widget's colors over its defaults, printing a warning for each
+/// key that is unknown, malformed or refers to a missing color. A missing
+/// color file leaves the defaults.
+pub fn load_colors<P: Palette>(path: Option<&str>) -> P {
+    let (colors, problems) = trace::span("colors", || read_colors(path, false));
+    for p in problems { eprintln!("widgets: {p}"); }
+    colors
+}
+
+/// What `load_colors` would warn about, plus a missing color file.
+pub fn check_colors<P: Palette>(path: &str) -> Vec<String> {
+    read_colors::<P>(Some(path), true).1
+}
+
+/// The color file and the base palettes it extends, for watching. The
+/// default base is always included so creating it takes effect.
+pub fn palette_files(path: Option<&str>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    read_chain(path.map(expand_path), &mut files, &mut Vec::new(), &mut Vec::new());
+    let default_base = config_dir().join("colors.toml");
+    if !files.contains(&default_base) { files.push(default_base); }
+    files
+}
+
+/// A color file's own table, the file it extends, and what couldn't be read.
+fn read_table(path: &Path, problems: &mut Vec<String>) -> Option<(toml::Table, Option<PathBuf>)> {
+    let content = std::fs::read_to_string(path).map_err(|e| problems.push(format!("{}: {e}", path.display()))).ok()?;
+    let mut table = content.parse::<toml::Table>().map_err(|e| problems.push(format!("{}: {e}", path.display()))).ok()?;
+    let base = match table.remove("extends") {
+        Some(toml::Value::String(file)) => {
+            let file = expand_path(&file);
+            Some(path.parent().map_or(file.clone(), |dir| dir.join(file)))
+        }
+        Some(v) => {
+            problems.push(format!("{}: `extends` is not a file name: {v}", path.display()));
+            None
+        }
+        None => None,
+    };
+    Some((table, base))
+}
+
+/// Read `path` and the palettes under it, base first, into `tables` (one per
+/// file, with the file's path). Without `path`, only the default base.
+fn read_chain(
+    path: Option<PathBuf>, files: &mut Vec<PathBuf>, tables: &mut Vec<(PathBuf, toml::Table)>, problems: &mut Vec<String>,
+) {
+    let default_base = Some(config_dir().join("colors.toml")).filter(|p| p.exists());
+    let own = path.is_some();
+    let mut next = path.or_else(|| default_base.clone());
+    let mut chain = Vec::new();
+    while let Some(file) = next.take() {
+        if files.contains(&file) {
+            problems.push(format!("{}: `extends` loops back to itself", file.display()));
+            break;
+        }
+        files.push(file.clone());
+        let Some((table, base)) = read_table(&file, problems) else { break };
+        // The default base only goes under the widget's own file
+        next = match base {
+            Some(base) => Some(base),
+            None if own && chain.is_empty() => default_base.clone().filter(|b| *b != file),
+            None => None,
+        };
+        chain.push((file, table));
+    }
+    tables.extend(chain.into_iter().rev());
+}
+
+fn read_colors<P: Palette>(path: Option<&str>, missing_is_error: bool) -> (P, Vec<String>) {
+    let mut colors = P::default();
+    let mut problems = Vec::new();
+    // Before the first walrs run the file may not exist yet; the base still applies
+    let path = path.map(expand_path).filter(|p| missing_is_error || p.exists());
+    let mut tables = Vec::new();
+    read_chain(path.clone(), &mut Vec::new(), &mut tables, &mut problems);
+
+    // Later files override earlier ones; each key remembers where it's from
+    let mut merged: HashMap<String, (toml::Value, &Path)> = HashMap::new();
+    for (file, table) in &tables {
+        for (key, value) in table { merged.insert(key.clone(), (value.clone(), file)); }
+    }
+    let mut keys: Vec<&String> = merged.keys().collect();
+    keys.sort();
+    // Only the widget's own file has to use the widget's keys: a base palette
+    // is shared with widgets that use other ones
+    let own = |file: &Path| path.as_deref() == Some(file);
+
+    let mut opacities = Vec::new();
+    for key in keys {
+        let (value, file) = &merged[key];
+        let at = |msg: String| format!("{}: `{key}`: {msg}", file.display());
+        if let Some(color) = key.strip_suffix("_opacity") {
+            match value.as_float().or(value.as_integer().map(|i| i as f64)) {
+                Some(f) if (0.0..=1.0).contains(&f) => opacities.push((key, color, (f * 255.0) as u8, *file)),
+                _ => problems.push(at(format!("not an opacity between 0.0 and 1.0: {value}"))),
+            }
+            continue;
+        }
+        let ([r, g, b], alpha) = match resolve(&merged, key, &mut HashSet::new()) {
+            Ok(c) => c,
+            Err(e) => { problems.push(at(e)); continue; }
+        };
+        match colors.color(key) {
+            Some(c) => *c = [r, g, b, alpha.unwrap_or(c[3])],
+            None if own(file) => problems.push(at("unknown color".into())),
+            None => {}
+        }
+    }
+    // After the colors, so an explicit opacity wins over a color's alpha
+    for (key, color, a, file) in opacities {
+        match colors.color(color) {
+            Some(c) => c[3] = a,
+            None if own(file) => problems.push(format!("{}: `{key}`: unknown color", file.display())),
+            None => {}
+        }
+    }
+    (colors, problems)
+}
+
+/// The color of `key`, following references to other keys.
+fn resolve(
+    merged: &HashMap<String, (toml::Value, &Path)>, key: &str, seen: &mut HashSet<String>,
+) -> Result<([u8; 3], Option<u8>), String> {
+    if !seen.insert(key.to_string()) { return Err("refers back to itself".into()); }
+    let (value, _) = &merged[key];
+    let Some(value) = value.as_str() else { return Err(format!("not a color: {value}")) };
+    match parse_rgba(value) {
+        Some(c) => Ok(c),
+        None if value.starts_with('#') => Err(format!("not a #rrggbb or #rrggbbaa color: {value}")),
+        None if merged.contains_key(value) => resolve(merged, value, seen),
+        None => Err(format!("not a color or the name of one: {value}")),
+    }
+}
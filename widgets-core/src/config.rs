@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::color::{check_colors, Palette};
+use crate::text::{font_db, is_font_file, load_font};
+use crate::trace;
+
+pub fn home() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap())
+}
+
+pub fn expand_path(p: &str) -> PathBuf {
+    if let Some(rest) = p.strip_prefix("~/") {
+        home().join(rest)
+    } else {
+        PathBuf::from(p)
+    }
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    std::env::var(var)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| home().join(fallback))
+}
+
+/// `$XDG_CONFIG_HOME/widgets`
+pub fn config_dir() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config").join("widgets")
+}
+
+/// `$XDG_STATE_HOME/widgets`
+pub fn state_dir() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state").join("widgets")
+}
+
+/// `$XDG_CACHE_HOME`
+pub fn cache_dir() -> PathBuf {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// `<config_dir>/<name>.toml`
+pub fn config_path(name: &str) -> PathBuf {
+    config_dir().join(format!("{name}.toml"))
+}
+
+/// Load `<config_dir>/<name>.toml`, falling back to defaults if it is missing or invalid.
+pub fn load_config<T: DeserializeOwned + Default>(name: &str) -> T {
+    trace::span("config", || read_config(name))
+}
+
+fn read_config<T: DeserializeOwned + Default>(name: &str) -> T {
+    let path = config_path(name);
+    match std::fs::read_to_string(&path) {
+        Ok(s) => match toml::from_str(&s) {
+            Ok(cfg) => cfg,
+            Err(e) => { eprintln!("{name}: failed to parse {}: {e}", path.display()); T::default() }
+        }
+        Err(_) => T::default(),
+    }
+}
+
+// --- Validation and defaults ---
+
+/// A widget's config struct, with what `--check-config` and
+/// `--print-default-config` need to know about it.
+pub trait Documented: Serialize + DeserializeOwned + Default {
+    /// The widget's colors, to check its color file against.
+    type Colors: Palette;
+    /// `(key, description)` for every field, in printing order.
+    const KEYS: &'static [(&'static str, &'static str)];
+    /// Docs for keys of `#[serde(flatten)]`ed structs, printed after `KEYS`.
+    const FLATTENED: &'static [(&'static str, &'static str)] = &[];
+    fn color_file(&self) -> Option<&str>;
+    /// Files the config points at that must exist, as `(key, path)`.
+    fn files(&self) -> Vec<(&'static str, String)> { Vec::new() }
+    /// Fonts the config names, as `(key, file or family)`.
+    fn fonts(&self) -> Vec<(&'static str, String)> { Vec::new() }
+}
+
+pub(crate) fn all_keys<C: Documented>() -> impl Iterator<Item = &'static (&'static str, &'static str)> {
+    C::KEYS.iter().chain(C::FLATTENED)
+}
+
+/// The default config as TOML, each key preceded by its description.
+/// Keys that are unset by default are left commented out.
+pub fn default_config<C: Documented>() -> String {
+    let table = toml::Table::try_from(C::default()).unwrap_or_default();
+    let mut out = String::new();
+    let mut sections = String::new();
+    for (key, doc) in all_keys::<C>() {
+        let comment: String = doc.lines().map(|line| format!("# {line}\n")).collect();
+        match table.get(*key) {
+            // Tables go last as `[key]` sections, since TOML can't return to
+            // top-level keys after one
+            Some(toml::Value::Table(t)) => {
+                let section = toml::Table::from_iter([(key.to_string(), toml::Value::Table(t.clone()))]);
+                sections += &format!("\n{comment}{}", toml::to_string(&section).unwrap_or_default());
+            }
+            Some(value) => out += &format!("\n{comment}{key} = {value}\n"),
+            None => out += &format!("\n{comment}# {key} =\n"),
+        }
+    }
+    out.trim_start().to_string() + &sections
+}
+
+/// Validate `<config_dir>/<name>.toml` and the files it refers to, printing
+/// one line per problem. Returns whether everything checked out.
+pub fn check_config<C: Documented>(name: &str) -> bool {
+    let path = config_path(name);
+    let at = |p: String| format!("{}: {p}", path.display());
+    let mut problems = Vec::new();
+    let table = match std::fs::read_to_string(&path) {
+        Ok(s) => match s.parse::<toml::Table>() {
+            Ok(t) => t,
+            Err(e) => { println!("{}: {e}", path.display()); return false; }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("{}: not found, using defaults", path.display());
+            toml::Table::new()
+        }
+        Err(e) => { println!("{}: {e}", path.display()); return false; }
+    };
+
+    // Each key is tried on its own so one bad value doesn't hide the rest
+    let mut valid = toml::Table::new();
+    for (key, value) in table {
+        if !all_keys::<C>().any(|(k, _)| *k == key) {
+            problems.push(at(format!("unknown key `{key}`")));
+            continue;
+        }
+        let single = toml::Table::from_iter([(key.clone(), value.clone())]);
+        match toml::Value::Table(single).try_into::<C>() {
+            Ok(_) => { valid.insert(key, value); }
+            Err(e) => problems.push(at(format!("`{key}`: {}", e.message()))),
+        }
+    }
+    let cfg: C = toml::Value::Table(valid).try_into().unwrap_or_default();
+
+    let (files, families): (Vec<_>, Vec<_>) = cfg.fonts().into_iter().partition(|(_, f)| is_font_file(f));
+    for (key, file) in cfg.files().into_iter().chain(files) {
+        if !expand_path(&file).exists() { problems.push(at(format!("`{key}`: {file} does not exist"))); }
+    }
+    if !families.is_empty() {
+        let mut db = font_db();
+        for (key, family) in families {
+            if load_font(&mut db, &family).is_none() { problems.push(at(format!("`{key}`: no installed font family {family}"))); }
+        }
+    }
+    if let Some(file) = cfg.color_file() {
+        problems.extend(check_colors::<C::Colors>(file));
+    }
+
+    for p in &problems { println!("{p}"); }
+    if problems.is_empty() { println!("{}", at("ok".into())); }
+    problems.is_empty()
+}
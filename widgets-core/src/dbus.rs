@@ -0,0 +1,74 @@
+//! Remote control over the session bus.
+//!
+//! With `--dbus` a widget owns `org.widgets.<Name>` and serves a
+//! `#[zbus::interface]` of the same name on `/org/widgets/<Name>`, so
+//! scripts can start a timer or show the launcher with
+//! `busctl --user call ...` instead of signals or `msg`.
+//!
+//! zbus answers on its own thread, but widget state lives on the event
+//! loop, so interfaces hold a [`Remote`] and run each call on the loop
+//! through it, against the widget's [`Host`]: its window, or under
+//! `--replay` the script standing in for one.
+
+use smithay_client_toolkit::reexports::calloop::channel::{self, Channel, Event, Sender};
+use smithay_client_toolkit::reexports::calloop::LoopHandle;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::fdo::{self, RequestNameFlags, RequestNameReply};
+use zbus::object_server::Interface;
+use crate::window::Host;
+
+type Call<W> = Box<dyn FnOnce(&mut dyn Host<Widget = W>) + Send>;
+
+/// A handle on the event loop's [`Host`] of widget `W` for interface methods.
+pub struct Remote<W>(Sender<Call<W>>);
+
+impl<W> Remote<W> {
+    /// Run `f` on the event loop and return what it returns, or fail if the
+    /// loop has gone.
+    pub async fn call<R: Send + 'static>(&self, f: impl FnOnce(&mut dyn Host<Widget = W>) -> R + Send + 'static) -> fdo::Result<R> {
+        let gone = || fdo::Error::Failed("the widget is exiting".into());
+        let (reply, result) = async_channel::bounded(1);
+        self.0.send(Box::new(move |host| { reply.try_send(f(host)).ok(); })).map_err(|_| gone())?;
+        result.recv().await.map_err(|_| gone())
+    }
+}
+
+/// The object path for interface `name`: `org.widgets.Foo` is served on
+/// `/org/widgets/Foo`.
+pub fn path(name: &str) -> String {
+    format!("/{}", name.replace('.', "/"))
+}
+
+/// A bus connection owning an interface's name, its calls queued until
+/// [`Served::attach`] hands them to an event loop.
+pub struct Served<W> {
+    connection: Connection,
+    calls: Channel<Call<W>>,
+}
+
+/// Own the interface's name on `bus` (the session bus unless a test picks
+/// another) and serve `iface`, built around a [`Remote`]. Fails if another
+/// process owns the name.
+pub fn connect<W, I: Interface>(bus: zbus::Result<Builder<'static>>, iface: impl FnOnce(Remote<W>) -> I) -> zbus::Result<Served<W>> {
+    let (sender, calls) = channel::channel();
+    let name = I::name();
+    let connection = bus?.serve_at(path(&name), iface(Remote(sender)))?.build()?;
+    if connection.request_name_with_flags(name.as_str(), RequestNameFlags::DoNotQueue.into())? != RequestNameReply::PrimaryOwner {
+        return Err(zbus::Error::NameTaken);
+    }
+    Ok(Served { connection, calls })
+}
+
+impl<W: 'static> Served<W> {
+    /// Run calls on the loop whose state is `H`, for as long as it runs.
+    pub fn attach<H: Host<Widget = W> + 'static>(self, loop_handle: &LoopHandle<'static, H>) -> zbus::Result<()> {
+        let Served { connection, calls } = self;
+        loop_handle.insert_source(calls, move |event, _, host| {
+            // Served for as long as the loop is
+            let _ = &connection;
+            if let Event::Msg(call) = event { call(host) }
+        }).map_err(|e| zbus::Error::Failure(e.to_string()))?;
+        Ok(())
+    }
+}
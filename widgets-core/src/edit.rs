@@ -0,0 +1,182 @@
+//! Single-line text editing for the search bars: the text, a caret and a
+//! selection, driven by the editing [`Action`]s, plus an input method's
+//! uncommitted composition (preedit) shown inline at the caret.
+
+use std::borrow::Cow;
+use std::ops::Range;
+use cosmic_text::Weight;
+use tiny_skia::Pixmap;
+use crate::keys::Action;
+use crate::render::{fill_rect, Rect};
+use crate::text::{Fonts, LINE_HEIGHT};
+use crate::window::TextInput;
+
+/// What an edit touched.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Change { None, Caret, Text }
+
+#[derive(Default, Clone, PartialEq)]
+pub struct LineEdit {
+    text: String,
+    /// Byte offsets; the selection runs between them when they differ.
+    caret: usize,
+    anchor: usize,
+    /// Not part of `text` until the input method commits it.
+    preedit: String,
+    /// Caret offset into `preedit`; `None` hides the caret.
+    preedit_caret: Option<usize>,
+}
+
+fn is_word(c: char) -> bool { c.is_alphanumeric() }
+
+impl LineEdit {
+    pub fn text(&self) -> &str { &self.text }
+    pub fn is_empty(&self) -> bool { self.text.is_empty() }
+    pub fn caret(&self) -> usize { self.caret }
+
+    pub fn selection(&self) -> Option<Range<usize>> {
+        (self.caret != self.anchor).then(|| self.caret.min(self.anchor)..self.caret.max(self.anchor))
+    }
+
+    /// Replace the text, with the caret at its end.
+    pub fn set(&mut self, text: String) {
+        self.caret = text.len();
+        self.anchor = self.caret;
+        self.text = text;
+    }
+
+    /// Type or paste `s` over the selection.
+    pub fn insert(&mut self, s: &str) -> Change {
+        let range = self.selection().unwrap_or(self.caret..self.caret);
+        if s.is_empty() && range.is_empty() { return Change::None; }
+        self.text.replace_range(range.clone(), s);
+        self.caret = range.start + s.len();
+        self.anchor = self.caret;
+        Change::Text
+    }
+
+    fn prev_char(&self, i: usize) -> usize {
+        self.text[..i].char_indices().next_back().map_or(0, |(j, _)| j)
+    }
+
+    fn next_char(&self, i: usize) -> usize {
+        self.text[i..].chars().next().map_or(i, |c| i + c.len_utf8())
+    }
+
+    /// Start of the word before `i`, skipping spaces and punctuation first.
+    fn prev_word(&self, i: usize) -> usize {
+        self.text[..i].trim_end_matches(|c| !is_word(c)).trim_end_matches(is_word).len()
+    }
+
+    /// End of the word after `i`.
+    fn next_word(&self, i: usize) -> usize {
+        let rest = self.text[i..].trim_start_matches(|c| !is_word(c)).trim_start_matches(is_word);
+        self.text.len() - rest.len()
+    }
+
+    /// Delete the selection, or from the caret to `to`.
+    fn delete(&mut self, to: usize) -> Change {
+        let range = self.selection().unwrap_or(self.caret.min(to)..self.caret.max(to));
+        if range.is_empty() { return Change::None; }
+        self.text.replace_range(range.clone(), "");
+        self.caret = range.start;
+        self.anchor = self.caret;
+        Change::Text
+    }
+
+    /// Apply a caret or editing action. With `extend` (Shift held) caret
+    /// moves grow the selection instead of clearing it.
+    pub fn apply(&mut self, action: &Action, extend: bool) -> Change {
+        let (caret, len) = (self.caret, self.text.len());
+        let to = match (self.selection(), action) {
+            (Some(sel), Action::CaretLeft) if !extend => sel.start,
+            (Some(sel), Action::CaretRight) if !extend => sel.end,
+            (_, Action::CaretLeft) => self.prev_char(caret),
+            (_, Action::CaretRight) => self.next_char(caret),
+            (_, Action::WordLeft) => self.prev_word(caret),
+            (_, Action::WordRight) => self.next_word(caret),
+            (_, Action::LineStart) => 0,
+            (_, Action::LineEnd) => len,
+            (_, Action::SelectAll) => {
+                if (self.anchor, self.caret) == (0, len) { return Change::None; }
+                (self.anchor, self.caret) = (0, len);
+                return Change::Caret;
+            }
+            (_, Action::Clear) => return self.delete_all(),
+            (_, Action::DeleteChar) => return self.delete(self.prev_char(caret)),
+            (_, Action::DeleteForward) => return self.delete(self.next_char(caret)),
+            (_, Action::DeleteWord) => return self.delete(self.prev_word(caret)),
+            (_, Action::DeleteWordForward) => return self.delete(self.next_word(caret)),
+            _ => return Change::None,
+        };
+        let before = (self.caret, self.anchor);
+        self.caret = to;
+        if !extend { self.anchor = to; }
+        if (self.caret, self.anchor) == before { Change::None } else { Change::Caret }
+    }
+
+    /// Apply an input method update: delete around the caret, insert the
+    /// committed text, then show the new composition.
+    pub fn input_method(&mut self, event: &TextInput) -> Change {
+        let before = self.clone();
+        let (back, forward) = event.delete;
+        let range = self.caret.saturating_sub(back)..(self.caret + forward).min(self.text.len());
+        if !range.is_empty() && self.text.is_char_boundary(range.start) && self.text.is_char_boundary(range.end) {
+            self.text.replace_range(range.clone(), "");
+            self.caret = range.start;
+            self.anchor = self.caret;
+        }
+        if let Some(commit) = &event.commit { self.insert(commit); }
+        (self.preedit, self.preedit_caret) = event.preedit.clone().unwrap_or_default();
+        if self.text != before.text { Change::Text } else if *self != before { Change::Caret } else { Change::None }
+    }
+
+    fn delete_all(&mut self) -> Change {
+        if self.text.is_empty() { return Change::None; }
+        self.set(String::new());
+        Change::Text
+    }
+
+    /// Draw the text centered on `cx` with its line box starting at `y`,
+    /// over the selection highlight, with any preedit underlined at the caret,
+    /// followed by the caret. Returns the caret's rect.
+    pub fn draw(
+        &self, pixmap: &mut Pixmap, fonts: &mut Fonts, cx: f32, y: f32, font_size: f32, family: &str,
+        color: [u8; 4], selection: [u8; 4], scale: f32,
+    ) -> Rect {
+        let shown = match self.preedit.is_empty() {
+            true => Cow::Borrowed(&self.text),
+            false => Cow::Owned(format!("{}{}{}", &self.text[..self.caret], self.preedit, &self.text[self.caret..])),
+        };
+        let mut x_at = |end: usize| fonts.measure(&shown[..end], font_size, family, Weight::NORMAL);
+        let x = cx - x_at(shown.len()) / 2.0;
+        // Offsets past the caret move right by the preedit
+        let at = |i: usize| if i > self.caret { i + self.preedit.len() } else { i };
+        let sel_x = self.selection().map(|sel| (x + x_at(at(sel.start)), x + x_at(at(sel.end))));
+        let preedit_x = (x + x_at(self.caret), x + x_at(self.caret + self.preedit.len()));
+        let caret_x = match (self.preedit.is_empty(), self.preedit_caret) {
+            (true, _) => Some(preedit_x.0),
+            (false, Some(c)) => Some(x + x_at(self.caret + c)),
+            (false, None) => None,
+        };
+        let (pw, ph) = (pixmap.width(), pixmap.height());
+        let line_h = (font_size * LINE_HEIGHT) as u32;
+        if let Some((x0, x1)) = sel_x {
+            fill_rect(pixmap.data_mut(), pw, ph, x0 as u32, y as u32, (x1 - x0) as u32, line_h, selection);
+        }
+        if !shown.is_empty() {
+            fonts.draw(pixmap, &shown, x, y, font_size, pw as f32, color, family, Weight::NORMAL);
+        }
+        let caret_w = (2.0 * scale).round().max(1.0) as u32;
+        if !self.preedit.is_empty() {
+            let (x0, x1) = preedit_x;
+            let underline_y = (y + font_size * LINE_HEIGHT - caret_w as f32) as u32;
+            fill_rect(pixmap.data_mut(), pw, ph, x0.max(0.0) as u32, underline_y, (x1 - x0) as u32, caret_w / 2 + 1, color);
+        }
+        let caret = Rect { x: caret_x.unwrap_or(preedit_x.0).max(0.0) as u32, y: y as u32, w: caret_w, h: line_h };
+        if caret_x.is_some() {
+            fill_rect(pixmap.data_mut(), pw, ph, caret.x, caret.y, caret.w, caret.h, color);
+        }
+        caret
+    }
+}
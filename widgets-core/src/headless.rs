@@ -0,0 +1,26 @@
+//! One-shot rendering without a compositor, for screenshots and golden images.
+
+use std::path::Path;
+use tiny_skia::Pixmap;
+use crate::render::Rect;
+use crate::style::{Chrome, Style};
+use crate::trace;
+use crate::window::Widget;
+
+/// Draw a single frame of `widget` in a logical `width`x`height` panel styled
+/// by `style`, times `scale`, and write it to `path` as PNG. The image
+/// includes the shadow margin, as the surface would.
+pub fn render_to_png<W: Widget>(widget: &mut W, width: u32, height: u32, scale: f32, style: &Style, path: &Path) -> Result<(), String> {
+    let m = 2 * style.margin();
+    let (pw, ph) = (((width + m) as f32 * scale).round() as u32, ((height + m) as f32 * scale).round() as u32);
+    let mut frame = Pixmap::new(pw, ph).ok_or_else(|| format!("invalid size {pw}x{ph}"))?;
+    let chrome = Chrome::new(style, widget.backdrop(), pw, ph, scale);
+    let c = chrome.content;
+    let mut content = Pixmap::new(c.w, c.h).ok_or_else(|| format!("invalid size {}x{}", c.w, c.h))?;
+    widget.resize(c.w, c.h, scale);
+    trace::span("draw", || widget.draw(&mut content));
+    chrome.compose(&mut frame, &content, Rect { x: 0, y: 0, w: pw, h: ph });
+    let saved = trace::span("png", || frame.save_png(path));
+    trace::finish();
+    saved.map_err(|e| format!("{}: {e}", path.display()))
+}
@@ -0,0 +1,120 @@
+//! Unix-socket control for resident (`--daemon`) widgets.
+//!
+//! `<widget> msg <command> [args...]` connects to
+//! `$XDG_RUNTIME_DIR/widgets/<widget>.sock` and sends one request: the
+//! command and its arguments NUL-separated on the first line, then (for
+//! dmenu-style use) the client's stdin. The daemon writes whatever the widget
+//! would have printed back on the same connection and closes it when the
+//! surface is hidden, at which point the client exits. Requests are read
+//! without blocking, so a slow stdin never holds up the daemon's UI.
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::rc::Rc;
+use smithay_client_toolkit::reexports::calloop::generic::Generic;
+use smithay_client_toolkit::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+
+pub fn socket_path(name: &str) -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+    dir.join("widgets").join(format!("{name}.sock"))
+}
+
+/// One `msg` invocation.
+pub struct Request {
+    pub command: String,
+    pub args: Vec<String>,
+    pub stdin: String,
+    /// Reply channel; dropping it lets the client exit.
+    pub stream: UnixStream,
+}
+
+/// Shared between the listener and each client still sending its request.
+type Handler<D> = Rc<RefCell<dyn FnMut(Request, &mut D)>>;
+
+fn parse_request(data: &[u8], stream: UnixStream) -> Request {
+    let data = String::from_utf8_lossy(data);
+    let (head, stdin) = data.split_once('\n').unwrap_or((&data, ""));
+    let mut parts = head.split('\0').map(String::from);
+    let command = parts.next().unwrap_or_default();
+    Request { command, args: parts.collect(), stdin: stdin.to_string(), stream }
+}
+
+/// Collect one client's request without blocking the loop: clients send
+/// everything up front and shut down their write half, however long their
+/// stdin takes, so the request is complete at EOF.
+fn read_request<D: 'static>(
+    loop_handle: &LoopHandle<'static, D>, stream: UnixStream, handle: Handler<D>,
+) -> io::Result<()> {
+    stream.set_nonblocking(true)?;
+    let mut reply = Some(stream.try_clone()?);
+    let mut data = Vec::new();
+    let source = Generic::new(stream, Interest::READ, Mode::Level);
+    loop_handle.insert_source(source, move |_, stream, state| {
+        let mut buf = [0; 4096];
+        loop {
+            match (&**stream).read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    eprintln!("widgets: bad request: {e}");
+                    return Ok(PostAction::Remove);
+                }
+            }
+        }
+        if let Some(reply) = reply.take() {
+            // Replies are written from the widget, which expects a blocking stream
+            match reply.set_nonblocking(false) {
+                Ok(()) => (handle.borrow_mut())(parse_request(&data, reply), state),
+                Err(e) => eprintln!("widgets: bad request: {e}"),
+            }
+        }
+        Ok(PostAction::Remove)
+    }).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(())
+}
+
+/// Listen on the widget's socket and hand each request to `handle`.
+pub fn serve<D: 'static>(
+    loop_handle: &LoopHandle<'static, D>, name: &str, handle: impl FnMut(Request, &mut D) + 'static,
+) -> io::Result<()> {
+    let path = socket_path(name);
+    if UnixStream::connect(&path).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} already running", path.display())));
+    }
+    if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+    std::fs::remove_file(&path).ok();
+    let listener = UnixListener::bind(&path)?;
+    listener.set_nonblocking(true)?;
+    let handle: Handler<D> = Rc::new(RefCell::new(handle));
+    let streams = loop_handle.clone();
+    let source = Generic::new(listener, Interest::READ, Mode::Level);
+    loop_handle.insert_source(source, move |_, listener, _| {
+        while let Ok((stream, _)) = listener.accept() {
+            if let Err(e) = read_request(&streams, stream, handle.clone()) {
+                eprintln!("widgets: bad request: {e}");
+            }
+        }
+        Ok(PostAction::Continue)
+    }).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(())
+}
+
+/// Client side of `msg`: send `args` (and stdin if `forward_stdin`), then
+/// copy the reply to stdout until the daemon hangs up.
+pub fn send(name: &str, args: &[String], forward_stdin: bool) -> io::Result<()> {
+    let path = socket_path(name);
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e} (is `{name} --daemon` running?)", path.display())))?;
+    // Connect first so a caller falling back to a plain run still has stdin
+    let mut stdin = String::new();
+    if forward_stdin { io::stdin().read_to_string(&mut stdin)?; }
+    stream.write_all(format!("{}\n", args.join("\0")).as_bytes())?;
+    stream.write_all(stdin.as_bytes())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    io::copy(&mut stream, &mut io::stdout())?;
+    Ok(())
+}
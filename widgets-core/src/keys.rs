@@ -0,0 +1,328 @@
+//! Keybindings: the `[keys]` config table, mapping key combos to actions.
+//!
+//! A combo is modifiers and a key joined by `+`: `ctrl+j`, `shift+Tab`,
+//! `Page_Down`. Keys use xkb keysym names, matched case-insensitively; a
+//! letter with `shift` is written lowercase (`ctrl+shift+k`). The table is
+//! layered over [`DEFAULT`], so a config lists only what it changes, and
+//! binding a combo to `"none"` removes it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, Keysym, Modifiers};
+
+/// Bindings every list widget starts from.
+pub const DEFAULT: &[(&str, &str)] = &[
+    ("Escape", "cancel"),
+    ("Return", "select"),
+    ("KP_Enter", "select"),
+    ("Left", "left"),
+    ("Right", "right"),
+    ("Up", "up"),
+    ("Down", "down"),
+    ("ctrl+n", "next"),
+    ("ctrl+p", "prev"),
+    ("ctrl+j", "next"),
+    ("ctrl+k", "prev"),
+    ("Page_Up", "page-up"),
+    ("Page_Down", "page-down"),
+    ("Home", "first"),
+    ("End", "last"),
+    ("ctrl+b", "caret-left"),
+    ("ctrl+f", "caret-right"),
+    ("ctrl+Left", "word-left"),
+    ("ctrl+Right", "word-right"),
+    ("alt+b", "word-left"),
+    ("alt+f", "word-right"),
+    ("ctrl+a", "line-start"),
+    ("ctrl+e", "line-end"),
+    ("BackSpace", "delete-char"),
+    ("Delete", "delete-forward"),
+    ("ctrl+d", "delete-forward"),
+    ("ctrl+BackSpace", "clear"),
+    ("ctrl+u", "clear"),
+    ("ctrl+w", "delete-word"),
+    ("ctrl+Delete", "delete-word-forward"),
+    ("alt+d", "delete-word-forward"),
+    ("ctrl+v", "paste"),
+    ("ctrl+c", "copy"),
+    ("shift+Insert", "paste"),
+    ("Tab", "toggle-mark"),
+];
+
+/// What a key does.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    /// Choose the selected entry, or every marked one.
+    Select,
+    Cancel,
+    /// Selection one entry forward/back in list order.
+    Next,
+    Prev,
+    /// Selection one cell in the grid. In a single-column list `left` and
+    /// `right` move the search caret instead.
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Selection one screenful forward/back.
+    PageUp,
+    PageDown,
+    First,
+    Last,
+    /// Search caret movement; with Shift held these extend the selection.
+    CaretLeft,
+    CaretRight,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    SelectAll,
+    /// Empty the search.
+    Clear,
+    /// Delete the selection, or before/after the caret.
+    DeleteChar,
+    DeleteForward,
+    DeleteWord,
+    DeleteWordForward,
+    /// Insert the clipboard's text at the caret.
+    Paste,
+    /// Mark or unmark the selected entry and move on.
+    ToggleMark,
+    /// Copy the chosen entries to the clipboard instead of printing them, then close.
+    Copy,
+    /// Copy the selected image itself (wallrun), then close.
+    CopyImage,
+    /// `run:CMD`: run `sh -c CMD` with the chosen entries as `$1...`, then close.
+    Run(String),
+}
+
+const NAMES: &[(&str, Action)] = &[
+    ("select", Action::Select), ("cancel", Action::Cancel),
+    ("next", Action::Next), ("prev", Action::Prev),
+    ("up", Action::Up), ("down", Action::Down), ("left", Action::Left), ("right", Action::Right),
+    ("page-up", Action::PageUp), ("page-down", Action::PageDown),
+    ("first", Action::First), ("last", Action::Last),
+    ("caret-left", Action::CaretLeft), ("caret-right", Action::CaretRight),
+    ("word-left", Action::WordLeft), ("word-right", Action::WordRight),
+    ("line-start", Action::LineStart), ("line-end", Action::LineEnd), ("select-all", Action::SelectAll),
+    ("clear", Action::Clear), ("delete-char", Action::DeleteChar), ("delete-forward", Action::DeleteForward),
+    ("delete-word", Action::DeleteWord), ("delete-word-forward", Action::DeleteWordForward),
+    ("paste", Action::Paste), ("copy", Action::Copy), ("copy-image", Action::CopyImage),
+    ("toggle-mark", Action::ToggleMark),
+];
+
+impl Action {
+    /// Caret moves, which Shift turns into selecting.
+    pub fn moves_caret(&self) -> bool {
+        matches!(self, Action::Left | Action::Right | Action::CaretLeft | Action::CaretRight
+            | Action::WordLeft | Action::WordRight | Action::LineStart | Action::LineEnd)
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        if let Some(cmd) = s.strip_prefix("run:") {
+            return Ok(Action::Run(cmd.trim().to_string()));
+        }
+        NAMES.iter().find(|(n, _)| *n == s).map(|(_, a)| a.clone())
+            .ok_or_else(|| format!("unknown action `{s}`"))
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Run(cmd) => write!(f, "run:{cmd}"),
+            a => f.write_str(NAMES.iter().find(|(_, n)| n == a).map_or("", |(n, _)| n)),
+        }
+    }
+}
+
+// --- Combos ---
+
+/// A key plus the modifiers that must be held, no more and no fewer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Combo {
+    key: Keysym,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    logo: bool,
+}
+
+/// Names to print for keysyms xkb lists under several.
+const PREFERRED: &[(&str, Keysym)] = &[("Page_Up", Keysym::Page_Up), ("Page_Down", Keysym::Page_Down)];
+
+/// Spellings xkb's own names don't cover.
+const ALIASES: &[(&str, Keysym)] = &[
+    ("PageUp", Keysym::Page_Up), ("PageDown", Keysym::Page_Down),
+    ("Enter", Keysym::Return), ("Esc", Keysym::Escape), ("Del", Keysym::Delete),
+];
+
+/// Letters compare lowercase and Shift+Tab arrives as ISO_Left_Tab; fold
+/// both so combos match what the keyboard reports.
+fn normalize(key: Keysym) -> Keysym {
+    if key == Keysym::ISO_Left_Tab { return Keysym::Tab; }
+    match key.key_char() {
+        Some(c) if c.is_uppercase() => c.to_lowercase().next().map_or(key, Keysym::from_char),
+        _ => key,
+    }
+}
+
+fn keysym_from_name(name: &str) -> Option<Keysym> {
+    if let Some(&(_, k)) = PREFERRED.iter().chain(ALIASES).find(|(n, _)| n.eq_ignore_ascii_case(name)) { return Some(k); }
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) { return Some(Keysym::from_char(c)); }
+    // xkeysym only maps keysyms to names, so index the ranges with named keys
+    // once; scanning them per combo was most of a debug build's startup
+    static BY_NAME: OnceLock<HashMap<String, Keysym>> = OnceLock::new();
+    let by_name = BY_NAME.get_or_init(|| {
+        let mut map = HashMap::new();
+        for k in (0..0x10000).chain(0x1008_ff00..0x1009_0000).map(Keysym::new) {
+            // The lowest keysym wins, as the scan used to find it first
+            if let Some((prefix, n)) = xkb_name(k) { map.entry(format!("{prefix}{n}").to_lowercase()).or_insert(k); }
+        }
+        map
+    });
+    by_name.get(&name.to_lowercase()).copied()
+}
+
+/// xkb's name for `key` as `(prefix, rest)`: `("", "Return")`, `("XF86", "AudioPlay")`.
+fn xkb_name(key: Keysym) -> Option<(&'static str, &'static str)> {
+    let name = key.name()?;
+    name.strip_prefix("XF86XK_").map(|n| ("XF86", n)).or_else(|| name.strip_prefix("XK_").map(|n| ("", n)))
+}
+
+impl Combo {
+    pub fn from_event(event: &KeyEvent, m: &Modifiers) -> Self {
+        Combo { key: normalize(event.keysym), ctrl: m.ctrl, alt: m.alt, shift: m.shift, logo: m.logo }
+    }
+
+    /// The key press a keyboard would report for this combo, for scripted
+    /// input: letters come uppercase with Shift, and only keys pressed
+    /// without Ctrl, Alt or Super type text.
+    pub fn event(&self) -> (KeyEvent, Modifiers) {
+        let key = match self.key.key_char() {
+            Some(c) if self.shift && c.is_lowercase() => c.to_uppercase().next().map_or(self.key, Keysym::from_char),
+            _ if self.shift && self.key == Keysym::Tab => Keysym::ISO_Left_Tab,
+            _ => self.key,
+        };
+        let typing = !(self.ctrl || self.alt || self.logo);
+        let utf8 = key.key_char().filter(|_| typing).map(String::from);
+        let modifiers = Modifiers { ctrl: self.ctrl, alt: self.alt, shift: self.shift, logo: self.logo, ..Default::default() };
+        (KeyEvent { time: 0, raw_code: 0, keysym: key, utf8 }, modifiers)
+    }
+}
+
+impl FromStr for Combo {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        // A trailing `+` is the key itself: `ctrl++`
+        let (mods, key) = match s.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let key = keysym_from_name(key.trim()).ok_or_else(|| format!("unknown key `{key}` in `{s}`"))?;
+        let mut combo = Combo { key: normalize(key), ctrl: false, alt: false, shift: false, logo: false };
+        for m in mods.split('+').map(str::trim).filter(|m| !m.is_empty()) {
+            match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => combo.ctrl = true,
+                "alt" => combo.alt = true,
+                "shift" => combo.shift = true,
+                "super" | "logo" | "mod4" => combo.logo = true,
+                _ => return Err(format!("unknown modifier `{m}` in `{s}`")),
+            }
+        }
+        Ok(combo)
+    }
+}
+
+impl fmt::Display for Combo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "ctrl+"), (self.alt, "alt+"), (self.shift, "shift+"), (self.logo, "super+")] {
+            if held { f.write_str(name)?; }
+        }
+        if let Some((name, _)) = PREFERRED.iter().find(|(_, k)| *k == self.key) { return f.write_str(name); }
+        match xkb_name(self.key) {
+            Some((prefix, name)) => write!(f, "{prefix}{name}"),
+            None => write!(f, "{}", self.key.key_char().unwrap_or('?')),
+        }
+    }
+}
+
+// --- Tables ---
+
+/// A widget's bindings: [`DEFAULT`] with the config's `[keys]` laid over it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "BTreeMap<String, String>", into = "BTreeMap<String, String>")]
+pub struct Keys(Vec<(Combo, Action)>);
+
+impl Keys {
+    /// The action bound to `event`. A caret move bound without Shift also
+    /// answers with Shift held, for selecting.
+    pub fn action(&self, event: &KeyEvent, modifiers: &Modifiers) -> Option<&Action> {
+        let combo = Combo::from_event(event, modifiers);
+        let find = |combo: Combo| self.0.iter().find(|(c, _)| *c == combo).map(|(_, a)| a);
+        find(combo).or_else(|| {
+            if !combo.shift { return None; }
+            find(Combo { shift: false, ..combo }).filter(|a| a.moves_caret())
+        })
+    }
+
+    fn bind(&mut self, combo: Combo, action: Option<Action>) {
+        self.0.retain(|(c, _)| *c != combo);
+        if let Some(action) = action { self.0.push((combo, action)); }
+    }
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Keys(DEFAULT.iter().map(|(c, a)| (c.parse().unwrap(), a.parse().unwrap())).collect())
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for Keys {
+    type Error = String;
+    fn try_from(table: BTreeMap<String, String>) -> Result<Self, String> {
+        let mut keys = Keys::default();
+        for (combo, action) in table {
+            let action = match action.as_str() { "none" => None, a => Some(a.parse()?) };
+            keys.bind(combo.parse()?, action);
+        }
+        Ok(keys)
+    }
+}
+
+impl From<Keys> for BTreeMap<String, String> {
+    fn from(keys: Keys) -> Self {
+        // Defaults that were unbound are written as "none" so the table
+        // reads back the same
+        let unbound = Keys::default().0.into_iter()
+            .filter(|(c, _)| !keys.0.iter().any(|(k, _)| k == c))
+            .map(|(c, _)| (c.to_string(), "none".to_string()));
+        keys.0.iter().map(|(c, a)| (c.to_string(), a.to_string())).chain(unbound).collect()
+    }
+}
+
+// --- Helpers for the actions ---
+
+/// Printable text typed by `event`, if any.
+pub fn typed(event: &KeyEvent) -> Option<&str> {
+    event.utf8.as_deref().filter(|t| !t.is_empty() && t.chars().all(|c| !c.is_control()))
+}
+
+/// How a widget starts commands: [`run`], or [`crate::replay::print_run`]
+/// while a script replays.
+pub type Spawn = fn(&str, &[String]);
+
+/// Start a `run:` command with `args` as its positional parameters.
+pub fn run(cmd: &str, args: &[String]) {
+    let spawned = Command::new("sh").arg("-c").arg(cmd).arg("sh").args(args)
+        .stdin(Stdio::null()).stdout(Stdio::null()).spawn();
+    if let Err(e) = spawned { eprintln!("widgets: cannot run `{cmd}`: {e}"); }
+}
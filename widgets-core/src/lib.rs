@@ -0,0 +1,28 @@
+//! Shared pieces of the widgets: animation easing, command-line parsing,
+//! clipboard copying, color files, config loading, daemon IPC, D-Bus control,
+//! output selection, pixel helpers, text rendering, the layer-shell window
+//! scaffold, headless rendering, reload-on-change watching, keybindings,
+//! search-bar line editing, the panel styling, startup tracing and scripted
+//! input replay.
+
+// The pixel helpers take (buffer, size, rect, color) and are clearer unbundled.
+#![allow(clippy::too_many_arguments)]
+
+pub mod anim;
+pub mod cli;
+pub mod clipboard;
+pub mod color;
+pub mod config;
+pub mod dbus;
+pub mod edit;
+pub mod headless;
+pub mod ipc;
+pub mod keys;
+pub mod output;
+pub mod render;
+pub mod replay;
+pub mod style;
+pub mod text;
+pub mod trace;
+pub mod watch;
+pub mod window;
@@ -0,0 +1,64 @@
+//! Picking the monitor a layer surface opens on.
+//!
+//! `output` specs (config key or `--output`): a connector name such as
+//! `DP-1`, `"focused"` (the monitor with keyboard focus) or `"cursor"` (the
+//! monitor under the pointer). Wayland has no portable way to ask for either
+//! policy, so both need Hyprland and go through `hyprctl`; elsewhere they
+//! warn and let the compositor choose, which on most compositors means the
+//! focused output.
+
+use std::process::Command;
+use smithay_client_toolkit::output::OutputState;
+use wayland_client::protocol::wl_output::WlOutput;
+
+/// Resolve `spec` among the outputs the window knows of; `None` leaves the
+/// choice to the compositor.
+pub fn pick(outputs: &OutputState, spec: &str) -> Option<WlOutput> {
+    let mut known = outputs.outputs().filter_map(|o| Some((outputs.info(&o)?, o)));
+    match spec {
+        "focused" | "cursor" if !hyprland() => {
+            eprintln!("widgets: output \"{spec}\" needs Hyprland; letting the compositor choose");
+            None
+        }
+        "focused" => {
+            let name = hyprland_focused()?;
+            known.find(|(info, _)| info.name.as_deref() == Some(&name)).map(|(_, o)| o)
+        }
+        "cursor" => {
+            let (x, y) = hyprland_cursor()?;
+            known.find(|(info, _)| {
+                let (Some((ox, oy)), Some((w, h))) = (info.logical_position, info.logical_size) else { return false };
+                x >= ox && x < ox + w && y >= oy && y < oy + h
+            }).map(|(_, o)| o)
+        }
+        name => {
+            let known: Vec<_> = known.collect();
+            let found = known.iter().find(|(info, _)| info.name.as_deref() == Some(name)).map(|(_, o)| o.clone());
+            if found.is_none() {
+                let names: Vec<_> = known.iter().filter_map(|(info, _)| info.name.as_deref()).collect();
+                eprintln!("widgets: no output named {name} (have: {})", names.join(", "));
+            }
+            found
+        }
+    }
+}
+
+fn hyprland() -> bool {
+    std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some()
+}
+
+/// `workspace ID 1 (1) on monitor DP-1:` -> `DP-1`
+fn hyprland_focused() -> Option<String> {
+    let out = Command::new("hyprctl").arg("activeworkspace").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let line = text.lines().next()?;
+    Some(line.split(" on monitor ").nth(1)?.trim_end_matches(':').to_string())
+}
+
+/// `1234, 567` in global logical coordinates.
+fn hyprland_cursor() -> Option<(i32, i32)> {
+    let out = Command::new("hyprctl").arg("cursorpos").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let (x, y) = text.trim().split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
@@ -0,0 +1,185 @@
+//! Pixel helpers over a tightly packed RGBA buffer (`Pixmap::data_mut`).
+
+/// Pixel rectangle; also used for damage regions.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rect { pub x: u32, pub y: u32, pub w: u32, pub h: u32 }
+
+impl Rect {
+    pub fn contains(&self, mx: u32, my: u32) -> bool {
+        mx >= self.x && mx < self.x + self.w && my >= self.y && my < self.y + self.h
+    }
+
+    pub fn scaled(self, s: f32) -> Rect {
+        let f = |v: u32| (v as f32 * s).round() as u32;
+        Rect { x: f(self.x), y: f(self.y), w: f(self.w), h: f(self.h) }
+    }
+}
+
+/// Overwrite a rect with `c` at opacity `a` (premultiplied, like `Pixmap::fill`),
+/// so a region can be repainted from scratch.
+pub fn clear_rect(data: &mut [u8], pw: u32, ph: u32, r: Rect, c: [u8; 3], a: u8) {
+    let px = tiny_skia::Color::from_rgba8(c[0], c[1], c[2], a).premultiply().to_color_u8();
+    let px = [px.red(), px.green(), px.blue(), px.alpha()];
+    for py in r.y..r.y.saturating_add(r.h).min(ph) {
+        let row = py as usize * pw as usize;
+        for x in r.x..r.x.saturating_add(r.w).min(pw) {
+            let i = (row + x as usize) * 4;
+            data[i..i + 4].copy_from_slice(&px);
+        }
+    }
+}
+
+/// `c` with its alpha scaled by `a`, for parts of a panel that fade with it.
+pub fn faded(c: [u8; 4], a: u8) -> [u8; 4] {
+    [c[0], c[1], c[2], (c[3] as u32 * a as u32 / 255) as u8]
+}
+
+/// Blend an RGBA color over a rect.
+pub fn fill_rect(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 4]) {
+    fill_rect_alpha(data, pw, ph, x, y, w, h, [c[0], c[1], c[2]], c[3]);
+}
+
+pub fn fill_rect_alpha(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, c: [u8; 3], a: u8) {
+    if a == 0 { return; }
+    if a == 0xff {
+        for py in y..y.saturating_add(h).min(ph) {
+            for px in x..x.saturating_add(w).min(pw) {
+                let i = (py as usize * pw as usize + px as usize) * 4;
+                data[i] = c[0]; data[i + 1] = c[1]; data[i + 2] = c[2]; data[i + 3] = 0xff;
+            }
+        }
+        return;
+    }
+    let a32 = a as u32;
+    let inv = 255 - a32;
+    for py in y..y.saturating_add(h).min(ph) {
+        for px in x..x.saturating_add(w).min(pw) {
+            let i = (py as usize * pw as usize + px as usize) * 4;
+            data[i]     = ((c[0] as u32 * a32 + data[i] as u32 * inv) / 255) as u8;
+            data[i + 1] = ((c[1] as u32 * a32 + data[i + 1] as u32 * inv) / 255) as u8;
+            data[i + 2] = ((c[2] as u32 * a32 + data[i + 2] as u32 * inv) / 255) as u8;
+            data[i + 3] = (a32 + data[i + 3] as u32 * inv / 255) as u8;
+        }
+    }
+}
+
+pub fn fill_rounded_rect_alpha(data: &mut [u8], pw: u32, ph: u32, x: u32, y: u32, w: u32, h: u32, r: u32, c: [u8; 3], a: u8) {
+    if a == 0 || w == 0 || h == 0 { return; }
+    let r = r.min(w / 2).min(h / 2);
+    let a32 = a as u32;
+    let inv = 255 - a32;
+    for py in y..y.saturating_add(h).min(ph) {
+        for px in x..x.saturating_add(w).min(pw) {
+            let lx = px - x;
+            let ly = py - y;
+            // Check if pixel is in a corner region and outside the rounded arc
+            let in_corner = (lx < r || lx >= w - r) && (ly < r || ly >= h - r);
+            if in_corner {
+                let cx = if lx < r { r } else { w - r };
+                let cy = if ly < r { r } else { h - r };
+                let dx = lx as f32 - cx as f32 + 0.5;
+                let dy = ly as f32 - cy as f32 + 0.5;
+                if dx * dx + dy * dy > (r as f32) * (r as f32) { continue; }
+            }
+            let i = (py as usize * pw as usize + px as usize) * 4;
+            if a == 0xff {
+                data[i] = c[0]; data[i + 1] = c[1]; data[i + 2] = c[2]; data[i + 3] = 0xff;
+            } else {
+                data[i]     = ((c[0] as u32 * a32 + data[i] as u32 * inv) / 255) as u8;
+                data[i + 1] = ((c[1] as u32 * a32 + data[i + 1] as u32 * inv) / 255) as u8;
+                data[i + 2] = ((c[2] as u32 * a32 + data[i + 2] as u32 * inv) / 255) as u8;
+                data[i + 3] = (a32 + data[i + 3] as u32 * inv / 255) as u8;
+            }
+        }
+    }
+}
+
+/// Blend a single-channel glyph mask in `color`, scaled by its alpha.
+pub fn blit_mask(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, mask: &[u8], color: &[u8; 4]) {
+    for gy in 0..h {
+        let py = y0 + gy;
+        if py < 0 || py >= ph { continue; }
+        for gx in 0..w {
+            let px = x0 + gx;
+            if px < 0 || px >= pw { continue; }
+            let a = mask[(gy * w + gx) as usize] as u32 * color[3] as u32 / 255;
+            if a == 0 { continue; }
+            let i = (py * pw + px) as usize * 4;
+            let inv = 255 - a;
+            data[i]     = ((color[0] as u32 * a + data[i] as u32 * inv) / 255) as u8;
+            data[i + 1] = ((color[1] as u32 * a + data[i + 1] as u32 * inv) / 255) as u8;
+            data[i + 2] = ((color[2] as u32 * a + data[i + 2] as u32 * inv) / 255) as u8;
+            data[i + 3] = (a + data[i + 3] as u32 * inv / 255) as u8;
+        }
+    }
+}
+
+/// Blend a straight-alpha RGBA image (icons, thumbnails).
+pub fn blit_rgba(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, src: &[u8]) {
+    for gy in 0..h {
+        let py = y0 + gy;
+        if py < 0 || py >= ph { continue; }
+        for gx in 0..w {
+            let px = x0 + gx;
+            if px < 0 || px >= pw { continue; }
+            let si = (gy * w + gx) as usize * 4;
+            let di = (py * pw + px) as usize * 4;
+            let a = src[si + 3] as u32;
+            if a == 0 { continue; }
+            if a == 255 {
+                data[di] = src[si];
+                data[di + 1] = src[si + 1];
+                data[di + 2] = src[si + 2];
+                data[di + 3] = 255;
+            } else {
+                let inv = 255 - a;
+                data[di]     = ((src[si] as u32 * a + data[di] as u32 * inv) / 255) as u8;
+                data[di + 1] = ((src[si + 1] as u32 * a + data[di + 1] as u32 * inv) / 255) as u8;
+                data[di + 2] = ((src[si + 2] as u32 * a + data[di + 2] as u32 * inv) / 255) as u8;
+                data[di + 3] = (a + data[di + 3] as u32 * inv / 255) as u8;
+            }
+        }
+    }
+}
+
+/// Blend a color glyph (emoji) bitmap.
+pub fn blit_color(data: &mut [u8], pw: i32, ph: i32, x0: i32, y0: i32, w: i32, h: i32, rgba: &[u8]) {
+    for gy in 0..h {
+        let py = y0 + gy;
+        if py < 0 || py >= ph { continue; }
+        for gx in 0..w {
+            let px = x0 + gx;
+            if px < 0 || px >= pw { continue; }
+            let si = (gy * w + gx) as usize * 4;
+            let a = rgba[si + 3] as u32;
+            if a == 0 { continue; }
+            let i = (py * pw + px) as usize * 4;
+            let inv = 255 - a;
+            data[i]     = (rgba[si] as u32 * a / 255 + data[i] as u32 * inv / 255) as u8;
+            data[i + 1] = (rgba[si + 1] as u32 * a / 255 + data[i + 1] as u32 * inv / 255) as u8;
+            data[i + 2] = (rgba[si + 2] as u32 * a / 255 + data[i + 2] as u32 * inv / 255) as u8;
+            data[i + 3] = (a + data[i + 3] as u32 * inv / 255) as u8;
+        }
+    }
+}
+
+/// Copy RGBA premul -> BGRA (ARGB8888 on LE).
+pub fn copy_to_argb(dst: &mut [u8], src: &[u8]) {
+    for (dst, src) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        dst[0] = src[2];
+        dst[1] = src[1];
+        dst[2] = src[0];
+        dst[3] = src[3];
+    }
+}
+
+/// [`copy_to_argb`] restricted to one rect of a `pw`-wide image.
+pub fn copy_rect_to_argb(dst: &mut [u8], src: &[u8], pw: u32, ph: u32, r: Rect) {
+    let x0 = r.x.min(pw) as usize;
+    let x1 = r.x.saturating_add(r.w).min(pw) as usize;
+    for py in r.y..r.y.saturating_add(r.h).min(ph) {
+        let row = py as usize * pw as usize;
+        let span = (row + x0) * 4..(row + x1) * 4;
+        copy_to_argb(&mut dst[span.clone()], &src[span]);
+    }
+}
@@ -0,0 +1,440 @@
+//! Scripted input for end-to-end tests: `--replay SCRIPT` drives a widget
+//! without a compositor, feeding it the key, pointer and configure events a
+//! [`crate::window::Window`] would and drawing whenever it asks to.
+//!
+//! A script is TOML: a `steps` array, run in order, and the `clipboard` text
+//! handed to a widget that asks to paste.
+//!
+//! ```toml
+//! clipboard = "pasted text"
+//! steps = [
+//!     { configure = { width = 800, height = 500, scale = 2 } },
+//!     { type = "fire" },          # one key press per character
+//!     { key = "ctrl+j" },         # a combo, as in [keys]
+//!     { motion = [120, 80] },
+//!     { click = [120, 80] },      # left button; right_click for the other
+//!     { scroll = [120, 80, 2] },  # wheel steps, positive is down
+//!     { wait_ms = 300 },          # let animations run
+//!     { render = "frame.png" },
+//!     { serve_until = "hidden" }, # answer D-Bus calls until then
+//! ]
+//! ```
+//!
+//! Positions are logical surface coordinates, shadow margin and border
+//! included, as a compositor would report them. The script ends early when
+//! the widget exits, as it would close its window. Whatever the widget
+//! prints is its output as usual, and copies are printed to stderr as
+//! `replay: copy: TEXT` (or the size and type of other data). Widgets run
+//! under a script should also be given [`print_run`] as their
+//! [`crate::keys::Spawn`] and keep their state files to themselves.
+//!
+//! The script is the widget's [`Host`], so with `--dbus` its interface is
+//! served too, though only during `serve_until` steps: they run calls as
+//! they come until the widget is `shown`, `hidden` or has exited (`exit`).
+//! Resident widgets (`--daemon`) start hidden, as their window would.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use smithay_client_toolkit::reexports::calloop::EventLoop;
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, Keysym, Modifiers};
+use smithay_client_toolkit::seat::pointer::{AxisScroll, PointerEvent, PointerEventKind, BTN_LEFT, BTN_RIGHT};
+use tiny_skia::Pixmap;
+use wayland_client::backend::Backend;
+use wayland_client::protocol::{wl_pointer, wl_surface::WlSurface};
+use wayland_client::Proxy;
+use crate::dbus::Served;
+use crate::keys::Combo;
+use crate::render::Rect;
+use crate::style::{Chrome, Style};
+use crate::window::{Host, Options, Update, Widget};
+
+/// Pixels per wheel step, as compositors usually report them.
+const WHEEL_STEP: f64 = 15.0;
+/// How often running animations are stepped during `wait_ms`.
+const FRAME: Duration = Duration::from_millis(16);
+/// How long a `serve_until` step waits before failing the script.
+const SERVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Script {
+    #[serde(default)]
+    clipboard: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum Step {
+    Configure { width: u32, height: u32, #[serde(default = "one")] scale: f32 },
+    Key(String),
+    Type(String),
+    Motion([f64; 2]),
+    Click([f64; 2]),
+    RightClick([f64; 2]),
+    Scroll([f64; 3]),
+    WaitMs(u64),
+    Render(PathBuf),
+    ServeUntil(Until),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Until {
+    Shown,
+    Hidden,
+    Exit,
+}
+
+fn one() -> f32 { 1.0 }
+
+/// A [`crate::keys::Spawn`] that prints `replay: run: CMD ARGS...` to
+/// stderr instead of starting anything.
+pub fn print_run(cmd: &str, args: &[String]) {
+    let mut line = format!("replay: run: {cmd}");
+    for arg in args { line.push(' '); line.push_str(arg); }
+    eprintln!("{line}");
+}
+
+/// The widget plus what the window would be keeping for it.
+struct Replay<W: Widget> {
+    widget: W,
+    opts: Options,
+    clipboard: String,
+    scale: f32,
+    size: (u32, u32),
+    chrome: Chrome,
+    content: Pixmap,
+    /// Pointer events need a surface; this one belongs to no connection.
+    surface: WlSurface,
+    shown: bool,
+    exited: bool,
+}
+
+/// The frame around a logical `width`x`height` panel and the widget's pixmap
+/// inside it, plus the surface size in pixels.
+fn layout<W: Widget>(widget: &W, style: &Style, width: u32, height: u32, scale: f32) -> Result<(Chrome, Pixmap, (u32, u32)), String> {
+    let m = 2 * style.margin();
+    let (pw, ph) = (((width + m) as f32 * scale).round() as u32, ((height + m) as f32 * scale).round() as u32);
+    let chrome = Chrome::new(style, widget.backdrop(), pw, ph, scale);
+    let c = chrome.content;
+    let content = Pixmap::new(c.w, c.h).ok_or_else(|| format!("invalid size {}x{}", c.w, c.h))?;
+    Ok((chrome, content, (pw, ph)))
+}
+
+impl<W: Widget> Replay<W> {
+    fn configure(&mut self, width: u32, height: u32, scale: f32) -> Result<(), String> {
+        (self.chrome, self.content, self.size) = layout(&self.widget, &self.opts.style, width, height, scale)?;
+        self.scale = scale;
+        self.configured();
+        Ok(())
+    }
+
+    /// Tell the widget its new size and draw, as the window does on configure.
+    fn configured(&mut self) {
+        let c = self.chrome.content;
+        self.widget.resize(c.w, c.h, self.scale);
+        self.widget.draw(&mut self.content);
+    }
+
+    fn take_clip(&mut self) {
+        if let Some(clip) = self.widget.take_clip() {
+            match clip.as_text() {
+                Some(text) => eprintln!("replay: copy: {text}"),
+                None => eprintln!("replay: copy: {} bytes of {}", clip.data.len(), clip.mimes.join(", ")),
+            }
+        }
+    }
+
+    fn key(&mut self, event: &KeyEvent, modifiers: &Modifiers) {
+        let update = self.widget.key(event, modifiers);
+        self.apply(update);
+    }
+
+    /// Send `kind` at surface position `[x, y]`; off the content, as in the
+    /// window, it is dropped.
+    fn pointer(&mut self, [x, y]: [f64; 2], kind: PointerEventKind) {
+        if self.exited { return; }
+        let c = self.chrome.content;
+        let (x, y) = (x * self.scale as f64 - c.x as f64, y * self.scale as f64 - c.y as f64);
+        if x < 0.0 || y < 0.0 || x >= c.w as f64 || y >= c.h as f64 { return; }
+        let event = PointerEvent { surface: self.surface.clone(), position: (x, y), kind };
+        let update = self.widget.pointer(&event);
+        self.apply(update);
+    }
+
+    fn click(&mut self, at: [f64; 2], button: u32) {
+        self.pointer(at, PointerEventKind::Motion { time: 0 });
+        self.pointer(at, PointerEventKind::Press { time: 0, button, serial: 0 });
+        self.pointer(at, PointerEventKind::Release { time: 0, button, serial: 0 });
+    }
+
+    /// Step running animations to now, as a frame callback would.
+    fn frame(&mut self) {
+        if self.widget.animating() {
+            let update = self.widget.animate(Instant::now());
+            self.apply(update);
+        }
+    }
+
+    fn wait(&mut self, ms: u64) {
+        let end = Instant::now() + Duration::from_millis(ms);
+        while !self.exited && Instant::now() < end {
+            std::thread::sleep(FRAME.min(end.saturating_duration_since(Instant::now())));
+            self.frame();
+        }
+    }
+
+    /// Run D-Bus calls as they come until `until` holds.
+    fn serve(&mut self, event_loop: &mut EventLoop<'static, Self>, until: Until) -> Result<(), String> {
+        let end = Instant::now() + SERVE_TIMEOUT;
+        loop {
+            let (done, what) = match until {
+                Until::Shown => (self.shown, "shown"),
+                Until::Hidden => (!self.shown, "hidden"),
+                Until::Exit => (false, "exited"),
+            };
+            if done || self.exited { return Ok(()); }
+            if Instant::now() >= end { return Err(format!("not {what} after {}s", SERVE_TIMEOUT.as_secs())); }
+            event_loop.dispatch(Some(FRAME), self).map_err(|e| e.to_string())?;
+            self.frame();
+        }
+    }
+
+    fn render(&mut self, path: &Path) -> Result<(), String> {
+        let (w, h) = self.size;
+        let mut frame = Pixmap::new(w, h).ok_or_else(|| format!("invalid size {w}x{h}"))?;
+        self.widget.draw(&mut self.content);
+        self.chrome.compose(&mut frame, &self.content, Rect { x: 0, y: 0, w, h });
+        frame.save_png(path).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    fn step(&mut self, step: &Step, event_loop: &mut EventLoop<'static, Self>) -> Result<(), String> {
+        match step {
+            Step::Configure { width, height, scale } => self.configure(*width, *height, *scale)?,
+            Step::Key(combo) => {
+                let (event, modifiers) = combo.parse::<Combo>()?.event();
+                self.key(&event, &modifiers);
+            }
+            Step::Type(text) => for c in text.chars() {
+                if self.exited { break; }
+                let event = KeyEvent { time: 0, raw_code: 0, keysym: Keysym::from_char(c), utf8: Some(c.into()) };
+                self.key(&event, &Modifiers::default());
+            },
+            Step::Motion(at) => self.pointer(*at, PointerEventKind::Motion { time: 0 }),
+            Step::Click(at) => self.click(*at, BTN_LEFT),
+            Step::RightClick(at) => self.click(*at, BTN_RIGHT),
+            &Step::Scroll([x, y, steps]) => {
+                let vertical = AxisScroll {
+                    absolute: steps * WHEEL_STEP,
+                    discrete: steps as i32,
+                    value120: (steps * 120.0) as i32,
+                    ..Default::default()
+                };
+                let kind = PointerEventKind::Axis {
+                    time: 0, horizontal: AxisScroll::default(), vertical, source: Some(wl_pointer::AxisSource::Wheel),
+                };
+                self.pointer([x, y], kind);
+            }
+            Step::WaitMs(ms) => self.wait(*ms),
+            Step::Render(path) => self.render(path)?,
+            Step::ServeUntil(until) => self.serve(event_loop, *until)?,
+        }
+        Ok(())
+    }
+}
+
+impl<W: Widget> Host for Replay<W> {
+    type Widget = W;
+    fn widget(&mut self) -> &mut W { &mut self.widget }
+    fn opts(&mut self) -> &mut Options { &mut self.opts }
+
+    fn apply(&mut self, update: Update) {
+        self.take_clip();
+        match update {
+            Update::None => {}
+            Update::Redraw => self.widget.draw(&mut self.content),
+            Update::Exit => self.dismiss(),
+            Update::Paste => {
+                let update = self.widget.paste(&self.clipboard.clone());
+                self.apply(update);
+            }
+        }
+    }
+
+    fn shown(&self) -> bool { self.shown }
+
+    /// Lay out again at `opts`' size, as the window's new surface would be.
+    fn show(&mut self) {
+        if self.shown { return; }
+        self.shown = true;
+        if let Err(e) = self.configure(self.opts.width, self.opts.height, self.scale) {
+            eprintln!("replay: show: {e}");
+        }
+    }
+
+    fn hide(&mut self) {
+        if !self.shown { return; }
+        self.shown = false;
+        self.widget.hidden();
+    }
+
+    fn dismiss(&mut self) {
+        if self.opts.resident { self.hide() } else { self.exited = true }
+    }
+
+    fn quit(&mut self) { self.exited = true; }
+}
+
+/// Run the script at `path` against `widget`, first configured at `opts`'
+/// logical size times `scale`, serving `served`'s calls if given.
+pub fn run<W: Widget>(widget: W, opts: Options, scale: f32, path: &Path, served: Option<Served<W>>) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let script: Script = toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+    let (ours, _) = std::os::unix::net::UnixStream::pair().map_err(|e| e.to_string())?;
+    let backend = Backend::connect(ours).map_err(|e| e.to_string())?;
+    let (chrome, content, size) = layout(&widget, &opts.style, opts.width, opts.height, scale)?;
+    let surface = WlSurface::inert(backend.downgrade());
+    let mut event_loop = EventLoop::try_new().map_err(|e| e.to_string())?;
+    if let Some(served) = served {
+        served.attach(&event_loop.handle()).map_err(|e| format!("D-Bus: {e}"))?;
+    }
+    let shown = !opts.resident;
+    let mut replay = Replay { widget, opts, clipboard: script.clipboard, scale, size, chrome, content, surface, shown, exited: false };
+    replay.configured();
+    for (i, step) in script.steps.iter().enumerate() {
+        if replay.exited { break; }
+        replay.step(step, &mut event_loop).map_err(|e| format!("{} step {}: {e}", path.display(), i + 1))?;
+    }
+    Ok(())
+}
+
+/// Helpers for the widgets' end-to-end tests, which run their binary on a
+/// script in a scratch home so no real config or state gets involved.
+#[cfg(feature = "testing")]
+pub mod testing {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Command, Output, Stdio};
+    use std::time::{Duration, Instant};
+    use zbus::blocking::connection::Builder;
+    use zbus::blocking::fdo::DBusProxy;
+    use zbus::blocking::{proxy, Connection, Proxy};
+    use zbus::proxy::CacheProperties;
+
+    /// An empty home for one test, with the XDG directories inside it.
+    pub fn scratch(test: &str) -> PathBuf {
+        let home = std::env::temp_dir().join(test);
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(home.join(".config/widgets")).unwrap();
+        home
+    }
+
+    /// Any font file under `dir`: the default fonts are unlikely to be installed.
+    fn system_font(dir: &Path) -> Option<PathBuf> {
+        let mut entries: Vec<_> = std::fs::read_dir(dir).ok()?.flatten().map(|e| e.path()).collect();
+        entries.sort();
+        entries.iter().find(|p| p.extension().is_some_and(|e| e == "ttf" || e == "otf")).cloned()
+            .or_else(|| entries.iter().filter(|p| p.is_dir()).find_map(|p| system_font(p)))
+    }
+
+    /// A font for the widget to draw with; panics if the system has none,
+    /// rather than letting the test pass without running.
+    pub fn font() -> PathBuf {
+        system_font(Path::new("/usr/share/fonts"))
+            .expect("no fonts under /usr/share/fonts; these tests need one installed")
+    }
+
+    /// The widget at `exe` on `script` with `args`, `config` (after a
+    /// `font` line) as its config file and its output piped.
+    fn command(exe: &str, home: &Path, config: &str, script: &str, args: &[&str]) -> Command {
+        let name = Path::new(exe).file_name().unwrap().to_string_lossy().into_owned();
+        let config = format!("font = {:?}\n{config}", font().display().to_string());
+        std::fs::write(home.join(format!(".config/widgets/{name}.toml")), config).unwrap();
+        let script_path = home.join("script.toml");
+        std::fs::write(&script_path, script).unwrap();
+        let mut command = Command::new(exe);
+        command.args(args).arg("--replay").arg(&script_path)
+            .env("HOME", home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_STATE_HOME", home.join(".local/state"))
+            .env("XDG_CACHE_HOME", home.join(".cache"))
+            .stdout(Stdio::piped()).stderr(Stdio::piped());
+        command
+    }
+
+    /// Run the widget at `exe` on `script` with `args` and `stdin` piped in,
+    /// `config` (after a `font` line) as its config file.
+    pub fn replay(exe: &str, home: &Path, config: &str, script: &str, args: &[&str], stdin: &str) -> Output {
+        let mut child = command(exe, home, config, script, args).stdin(Stdio::piped()).spawn().unwrap();
+        child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{exe} failed: {}", String::from_utf8_lossy(&output.stderr));
+        output
+    }
+
+    /// Start the widget at `exe` as [`replay`] does, serving its interface
+    /// on `bus` (its script wants `serve_until` steps), and leave it running.
+    pub fn serve(exe: &str, home: &Path, config: &str, script: &str, args: &[&str], bus: &Bus) -> Child {
+        command(exe, home, config, script, args)
+            .arg("--dbus")
+            .env("DBUS_SESSION_BUS_ADDRESS", &bus.address)
+            .stdin(Stdio::null())
+            .spawn().unwrap()
+    }
+
+    /// A `dbus-daemon` of the test's own, stopped when dropped.
+    pub struct Bus {
+        daemon: Child,
+        pub address: String,
+    }
+
+    impl Bus {
+        /// Start one; panics if there is no `dbus-daemon` to run.
+        pub fn start() -> Bus {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped()).stderr(Stdio::null())
+                .spawn().expect("dbus-daemon; these tests need one installed");
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address).unwrap();
+            Bus { daemon, address: address.trim().to_string() }
+        }
+
+        pub fn connect(&self) -> Connection {
+            Builder::address(self.address.as_str()).unwrap().build().unwrap()
+        }
+
+        /// `iface` as served by `widget`, once it has taken the name;
+        /// panics if the widget exits first or takes too long.
+        pub fn proxy(&self, iface: &str, widget: &mut Child) -> Proxy<'static> {
+            let connection = self.connect();
+            let bus = DBusProxy::new(&connection).unwrap();
+            let end = Instant::now() + Duration::from_secs(10);
+            while !bus.name_has_owner(iface.try_into().unwrap()).unwrap() {
+                if let Some(status) = widget.try_wait().unwrap() {
+                    let mut stderr = String::new();
+                    widget.stderr.take().unwrap().read_to_string(&mut stderr).unwrap();
+                    panic!("{iface} exited with {status}: {stderr}");
+                }
+                assert!(Instant::now() < end, "{iface} never took its name");
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            // The widgets don't signal property changes, so nothing may be cached
+            proxy::Builder::new(&connection)
+                .destination(iface.to_string()).unwrap()
+                .path(crate::dbus::path(iface)).unwrap()
+                .interface(iface.to_string()).unwrap()
+                .cache_properties(CacheProperties::No)
+                .build().unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            self.daemon.kill().ok();
+            self.daemon.wait().ok();
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! The `[style]` table every widget takes, and the panel it describes.
+//!
+//! The window paints the panel (drop shadow, flat or gradient background,
+//! border, rounded corners) as [`Chrome`] around and behind the widget.
+//! Widgets draw on a transparent pixmap inset by the border and padding,
+//! which is composited over the panel and clipped to its shape, so their
+//! layout never has to account for any of it. Sizes are in logical pixels.
+
+use serde::{Deserialize, Serialize};
+use tiny_skia::{
+    Color, FillRule, GradientStop, LinearGradient, Mask, Paint, Path, PathBuilder, Pixmap, Point, Shader, SpreadMode,
+    Stroke, Transform,
+};
+use crate::color::Hex;
+use crate::render::{faded, fill_rect_alpha, Rect};
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Style {
+    pub corner_radius: f32,
+    pub border_width: f32,
+    /// Unset: the color file's border color. Without an alpha, the color
+    /// file's border alpha.
+    pub border_color: Option<Hex>,
+    /// Between the border and the content.
+    pub padding: f32,
+    /// Drop shadow size; the surface grows by this much on every side.
+    pub shadow: f32,
+    /// Its alpha, if any, scales `shadow_opacity`.
+    pub shadow_color: Hex,
+    pub shadow_opacity: f32,
+    /// End color of a linear gradient from the background color; unset: flat.
+    /// Without an alpha, the background's.
+    pub gradient: Option<Hex>,
+    /// Gradient direction in degrees, as in CSS: 180 runs top to bottom.
+    pub gradient_angle: f32,
+    /// Corner radius of selection and hover highlights.
+    pub selection_radius: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            corner_radius: 0.0, border_width: 2.0, border_color: None, padding: 0.0,
+            shadow: 0.0, shadow_color: Hex([0, 0, 0], None), shadow_opacity: 0.5,
+            gradient: None, gradient_angle: 180.0, selection_radius: 0.0,
+        }
+    }
+}
+
+/// Config doc for the `style` table.
+pub const STYLE_DOC: &str = "Panel look, sizes in logical pixels and colors #rrggbb or #rrggbbaa: corner_radius,
+border_width, border_color (unset: the color file's border), padding, shadow (size, 0 for none),
+shadow_color, shadow_opacity, gradient (end color of the background; unset: flat), gradient_angle
+(degrees as in CSS, 180 = top to bottom) and selection_radius (highlight corners)";
+
+impl Style {
+    /// Room for the shadow on each side of the panel, in logical pixels.
+    pub fn margin(&self) -> u32 { self.shadow.max(0.0).ceil() as u32 }
+
+    /// Space between the panel's edge and the content, in logical pixels.
+    pub fn inset(&self) -> f32 { self.border_width.max(0.0) + self.padding.max(0.0) }
+}
+
+/// What the panel is painted with, from the widget's palette, as RGBA. The
+/// border fades with the background.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Backdrop {
+    pub background: [u8; 4],
+    pub border: [u8; 4],
+}
+
+impl Default for Backdrop {
+    fn default() -> Self {
+        Self { background: [0x1a, 0x1a, 0x2e, 0xff], border: [0x4a, 0x4a, 0x6e, 0xff] }
+    }
+}
+
+/// A rectangle with corners of radius `r`, clamped to fit.
+pub fn rounded_rect(x: f32, y: f32, w: f32, h: f32, r: f32) -> Option<Path> {
+    let r = r.min(w / 2.0).min(h / 2.0).max(0.0);
+    if r == 0.0 { return Some(PathBuilder::from_rect(tiny_skia::Rect::from_xywh(x, y, w, h)?)); }
+    // Cubic approximation of a quarter circle
+    let k = r * 0.552_284_8;
+    let (x1, y1) = (x + w, y + h);
+    let mut pb = PathBuilder::new();
+    pb.move_to(x + r, y);
+    pb.line_to(x1 - r, y);
+    pb.cubic_to(x1 - r + k, y, x1, y + r - k, x1, y + r);
+    pb.line_to(x1, y1 - r);
+    pb.cubic_to(x1, y1 - r + k, x1 - r + k, y1, x1 - r, y1);
+    pb.line_to(x + r, y1);
+    pb.cubic_to(x + r - k, y1, x, y1 - r + k, x, y1 - r);
+    pb.line_to(x, y + r);
+    pb.cubic_to(x, y + r - k, x + r - k, y, x + r, y);
+    pb.close();
+    pb.finish()
+}
+
+/// Blend `c` at opacity `a` over `r` with rounded corners (selection pills).
+pub fn fill_rounded(pixmap: &mut Pixmap, r: Rect, radius: f32, c: [u8; 3], a: u8) {
+    if radius <= 0.0 {
+        let (pw, ph) = (pixmap.width(), pixmap.height());
+        return fill_rect_alpha(pixmap.data_mut(), pw, ph, r.x, r.y, r.w, r.h, c, a);
+    }
+    let Some(path) = rounded_rect(r.x as f32, r.y as f32, r.w as f32, r.h as f32, radius) else { return };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(c[0], c[1], c[2], a);
+    pixmap.fill_path(&path, &paint, FillRule::Winding, Transform::identity(), None);
+}
+
+/// Outline the inside of `r` with a `width`-wide line at opacity `a`
+/// (focus rings).
+pub fn stroke_rounded(pixmap: &mut Pixmap, r: Rect, radius: f32, width: f32, c: [u8; 3], a: u8) {
+    let (x, y, w, h) = (r.x as f32 + width / 2.0, r.y as f32 + width / 2.0, r.w as f32 - width, r.h as f32 - width);
+    let Some(path) = rounded_rect(x, y, w, h, radius - width / 2.0) else { return };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(c[0], c[1], c[2], a);
+    pixmap.stroke_path(&path, &paint, &Stroke { width, ..Stroke::default() }, Transform::identity(), None);
+}
+
+/// The panel at one size and scale: everything around the widget that
+/// stays the same from frame to frame.
+pub struct Chrome {
+    /// What it was built for, to tell when it is stale.
+    built_for: (Style, Backdrop, u32, u32, f32),
+    /// Shadow and background, under the content.
+    under: Pixmap,
+    /// Border, over the content.
+    over: Pixmap,
+    /// Where content shows: inside the border.
+    clip: Mask,
+    /// The widget's pixmap within the frame, in buffer pixels.
+    pub content: Rect,
+}
+
+impl Chrome {
+    /// Chrome for a `width`x`height` pixel surface, shadow margin included.
+    pub fn new(style: &Style, backdrop: Backdrop, width: u32, height: u32, scale: f32) -> Self {
+        let px = |v: f32| (v.max(0.0) * scale).round();
+        let m = (style.margin() as f32 * scale).round();
+        let (w, h) = (width as f32 - 2.0 * m, height as f32 - 2.0 * m);
+        let (bw, radius) = (px(style.border_width), px(style.corner_radius));
+        let inset = m + bw + px(style.padding);
+        let content = Rect {
+            x: inset as u32, y: inset as u32,
+            w: (width as f32 - 2.0 * inset).max(1.0) as u32, h: (height as f32 - 2.0 * inset).max(1.0) as u32,
+        };
+        let color = |c: [u8; 4]| Color::from_rgba8(c[0], c[1], c[2], c[3]);
+
+        let mut under = Pixmap::new(width, height).unwrap();
+        if style.shadow > 0.0 {
+            let [r, g, b, a] = style.shadow_color.or_alpha(0xff);
+            draw_shadow(&mut under, m, w, h, radius, px(style.shadow), [r, g, b], style.shadow_opacity * a as f32 / 255.0);
+        }
+        let background = color(backdrop.background);
+        let shader = match style.gradient {
+            Some(end) => {
+                let (from, to) = gradient_line(m, m, w, h, style.gradient_angle);
+                let end = color(end.or_alpha(backdrop.background[3]));
+                let stops = vec![GradientStop::new(0.0, background), GradientStop::new(1.0, end)];
+                LinearGradient::new(from, to, stops, SpreadMode::Pad, Transform::identity())
+                    .unwrap_or(Shader::SolidColor(background))
+            }
+            None => Shader::SolidColor(background),
+        };
+        if let Some(path) = rounded_rect(m, m, w, h, radius) {
+            let paint = Paint { shader, ..Paint::default() };
+            under.fill_path(&path, &paint, FillRule::Winding, Transform::identity(), None);
+        }
+
+        // The stroke is centered on its path, so the path runs half a border in
+        let mut over = Pixmap::new(width, height).unwrap();
+        if bw > 0.0 && let Some(path) = rounded_rect(m + bw / 2.0, m + bw / 2.0, w - bw, h - bw, radius - bw / 2.0) {
+            let mut paint = Paint::default();
+            let border = style.border_color.map_or(backdrop.border, |c| c.or_alpha(backdrop.border[3]));
+            paint.set_color(color(faded(border, backdrop.background[3])));
+            over.stroke_path(&path, &paint, &Stroke { width: bw, ..Stroke::default() }, Transform::identity(), None);
+        }
+        let mut clip = Mask::new(width, height).unwrap();
+        if let Some(path) = rounded_rect(m + bw, m + bw, w - 2.0 * bw, h - 2.0 * bw, (radius - bw).max(0.0)) {
+            clip.fill_path(&path, FillRule::Winding, true, Transform::identity());
+        }
+        Self { built_for: (style.clone(), backdrop, width, height, scale), under, over, clip, content }
+    }
+
+    pub fn is_for(&self, style: &Style, backdrop: Backdrop, width: u32, height: u32, scale: f32) -> bool {
+        let (s, b, w, h, sc) = &self.built_for;
+        s == style && *b == backdrop && (*w, *h, *sc) == (width, height, scale)
+    }
+
+    /// Composite the widget's `content` over the panel into `frame`, within
+    /// `r` (frame coordinates).
+    pub fn compose(&self, frame: &mut Pixmap, content: &Pixmap, r: Rect) {
+        let (fw, fh) = (frame.width(), frame.height());
+        let c = self.content;
+        let (under, over, clip, src, cw) = (self.under.data(), self.over.data(), self.clip.data(), content.data(), content.width());
+        let out = frame.data_mut();
+        for y in r.y..r.y.saturating_add(r.h).min(fh) {
+            for x in r.x..r.x.saturating_add(r.w).min(fw) {
+                let i = (y * fw + x) as usize;
+                let mut px = [under[i * 4], under[i * 4 + 1], under[i * 4 + 2], under[i * 4 + 3]];
+                if c.contains(x, y) && x - c.x < cw && y - c.y < content.height() {
+                    let j = ((y - c.y) * cw + (x - c.x)) as usize * 4;
+                    let k = clip[i] as u32;
+                    let s = [0, 1, 2, 3].map(|n| (src[j + n] as u32 * k / 255) as u8);
+                    px = blend(s, px);
+                }
+                px = blend([over[i * 4], over[i * 4 + 1], over[i * 4 + 2], over[i * 4 + 3]], px);
+                out[i * 4..i * 4 + 4].copy_from_slice(&px);
+            }
+        }
+    }
+}
+
+/// Premultiplied `s` over `d`.
+fn blend(s: [u8; 4], d: [u8; 4]) -> [u8; 4] {
+    let inv = 255 - s[3] as u32;
+    [0, 1, 2, 3].map(|n| (s[n] as u32 + d[n] as u32 * inv / 255) as u8)
+}
+
+/// Endpoints of a CSS-style linear gradient across the rect.
+fn gradient_line(x: f32, y: f32, w: f32, h: f32, angle: f32) -> (Point, Point) {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let half = (w * sin.abs() + h * cos.abs()) / 2.0;
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    // 0 degrees points up
+    let (dx, dy) = (sin * half, -cos * half);
+    (Point::from_xy(cx - dx, cy - dy), Point::from_xy(cx + dx, cy + dy))
+}
+
+/// A soft shadow around the `w`x`h` panel at (`m`, `m`), fading out over
+/// `size` pixels. Only drawn outside the panel, so translucent backgrounds
+/// don't darken.
+fn draw_shadow(pixmap: &mut Pixmap, m: f32, w: f32, h: f32, radius: f32, size: f32, c: [u8; 3], opacity: f32) {
+    let (pw, ph) = (pixmap.width(), pixmap.height());
+    let radius = radius.min(w / 2.0).min(h / 2.0);
+    let (cx, cy) = (m + w / 2.0, m + h / 2.0);
+    let (hx, hy) = (w / 2.0 - radius, h / 2.0 - radius);
+    let data = pixmap.data_mut();
+    for y in 0..ph {
+        for x in 0..pw {
+            // Signed distance to the rounded rect
+            let (qx, qy) = ((x as f32 + 0.5 - cx).abs() - hx, (y as f32 + 0.5 - cy).abs() - hy);
+            let d = qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius;
+            if d <= 0.0 || d >= size { continue; }
+            let t = 1.0 - d / size;
+            let a = opacity.clamp(0.0, 1.0) * t * t;
+            let i = (y * pw + x) as usize * 4;
+            data[i..i + 4].copy_from_slice(&[c[0], c[1], c[2], 255].map(|v| (v as f32 * a).round() as u8));
+        }
+    }
+}
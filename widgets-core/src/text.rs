@@ -0,0 +1,150 @@
+use std::collections::BTreeSet;
+use std::sync::{Mutex, OnceLock};
+use cosmic_text::{fontdb, Attrs, Buffer, Fallback, FontSystem, Metrics, PlatformFallback, Shaping, SwashCache, SwashContent, Weight};
+use tiny_skia::Pixmap;
+use unicode_script::Script;
+use crate::config::expand_path;
+use crate::render::{blit_color, blit_mask};
+use crate::trace;
+
+pub const LINE_HEIGHT: f32 = 1.2;
+
+// --- Font lookup ---
+
+/// Whether a font setting names a file rather than a family.
+pub fn is_font_file(spec: &str) -> bool {
+    spec.contains('/') || spec.starts_with('~')
+}
+
+/// A font database holding the system's fonts (`/usr/share/fonts`,
+/// `~/.local/share/fonts`, ...), for family lookup and fallback. The fonts
+/// are scanned once; config reloads get a copy of the same database.
+pub fn font_db() -> fontdb::Database {
+    static SYSTEM: OnceLock<fontdb::Database> = OnceLock::new();
+    SYSTEM.get_or_init(|| trace::span("font_db", || {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        db
+    })).clone()
+}
+
+/// Resolve a font setting, loading it into `db` if it's a file, and return
+/// its family name. Family names match case-insensitively.
+pub fn load_font(db: &mut fontdb::Database, spec: &str) -> Option<String> {
+    if !is_font_file(spec) {
+        return db.faces().flat_map(|f| &f.families).find(|(name, _)| name.eq_ignore_ascii_case(spec))
+            .map(|(name, _)| name.clone());
+    }
+    let data = std::fs::read(expand_path(spec)).ok()?;
+    let before = db.len();
+    db.load_font_data(data);
+    Some(db.faces().nth(before)?.families[0].0.clone())
+}
+
+/// The text locale from `LC_ALL`, `LC_CTYPE` or `LANG` as a BCP 47 tag
+/// (`ja_JP.UTF-8` becomes `ja-JP`); `en-US` if none is set.
+pub fn locale() -> String {
+    ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+        .map(|v| v.split(['.', '@']).next().unwrap_or_default().replace('_', "-"))
+        .filter(|l| !l.is_empty() && l != "C" && l != "POSIX")
+        .unwrap_or_else(|| "en-US".into())
+}
+
+/// `name` with a `'static` lifetime, as cosmic-text wants for fallback
+/// families. Each distinct name is leaked once, however often it's asked for.
+fn intern(name: String) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES.lock().unwrap();
+    if let Some(&interned) = names.get(name.as_str()) { return interned; }
+    let interned: &'static str = Box::leak(name.into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// The configured fallback families, tried before the platform's own list.
+struct Fallbacks {
+    common: Vec<&'static str>,
+}
+
+impl Fallback for Fallbacks {
+    fn common_fallback(&self) -> &[&'static str] { &self.common }
+    fn forbidden_fallback(&self) -> &[&'static str] { PlatformFallback.forbidden_fallback() }
+    fn script_fallback(&self, script: Script, locale: &str) -> &[&'static str] {
+        // The platform lists pick CJK fonts by bare language except for Chinese
+        let lang = if locale.starts_with("zh") { locale } else { locale.split('-').next().unwrap_or(locale) };
+        PlatformFallback.script_fallback(script, lang)
+    }
+}
+
+/// Font system plus glyph cache, shared by everything a widget draws.
+pub struct Fonts {
+    system: FontSystem,
+    swash: SwashCache,
+}
+
+fn make_attrs(family: &str, weight: Weight) -> Attrs<'_> {
+    Attrs::new().weight(weight).family(cosmic_text::Family::Name(family))
+}
+
+impl Fonts {
+    /// Glyphs missing from the requested family come from `fallback` (files
+    /// or family names), then the platform's usual fallbacks, then any font
+    /// in `db`.
+    pub fn new(mut db: fontdb::Database, fallback: &[String]) -> Self {
+        let mut common = Vec::new();
+        for spec in fallback {
+            match load_font(&mut db, spec) {
+                Some(family) => common.push(intern(family)),
+                None => eprintln!("widgets: fallback font {spec} not found"),
+            }
+        }
+        common.extend(PlatformFallback.common_fallback());
+        Self {
+            system: FontSystem::new_with_locale_and_db_and_fallback(locale(), db, Fallbacks { common }),
+            swash: SwashCache::new(),
+        }
+    }
+
+    fn shape(&mut self, text: &str, font_size: f32, family: &str, weight: Weight) -> Buffer {
+        let mut buf = Buffer::new(&mut self.system, Metrics::new(font_size, font_size * LINE_HEIGHT));
+        buf.set_size(&mut self.system, None, None);
+        buf.set_text(&mut self.system, text, &make_attrs(family, weight), Shaping::Advanced, None);
+        buf.shape_until_scroll(&mut self.system, false);
+        buf
+    }
+
+    pub fn measure(&mut self, text: &str, font_size: f32, family: &str, weight: Weight) -> f32 {
+        self.shape(text, font_size, family, weight).layout_runs().next().map_or(0.0, |r| r.line_w)
+    }
+
+    /// Draw `text` with the top of its line box at `y`; glyphs starting past
+    /// `x + max_w` are clipped.
+    pub fn draw(
+        &mut self, pixmap: &mut Pixmap, text: &str, x: f32, y: f32, font_size: f32, max_w: f32,
+        color: [u8; 4], family: &str, weight: Weight,
+    ) {
+        let buf = self.shape(text, font_size, family, weight);
+        let pw = pixmap.width() as i32;
+        let ph = pixmap.height() as i32;
+        let x_max = (x + max_w) as i32;
+        for run in buf.layout_runs() {
+            for glyph in run.glyphs.iter() {
+                let physical = glyph.physical((x, y + run.line_y), 1.0);
+                if physical.x >= x_max { break; }
+                if let Some(image) = self.swash.get_image_uncached(&mut self.system, physical.cache_key) {
+                    let x0 = physical.x + image.placement.left;
+                    let y0 = physical.y - image.placement.top;
+                    let w = image.placement.width as i32;
+                    let h = image.placement.height as i32;
+                    match image.content {
+                        SwashContent::Mask => blit_mask(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data, &color),
+                        SwashContent::Color => blit_color(pixmap.data_mut(), pw, ph, x0, y0, w, h, &image.data),
+                        SwashContent::SubpixelMask => {}
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Startup tracing: `--trace-startup` (or `WIDGETS_TRACE_STARTUP=1`) times
+//! each startup phase and prints them to stderr as one JSON line once the
+//! first frame is committed, the headless frame is written, or a daemon is
+//! ready. Phases that run many times (icon lookups, thumbnails) are summed.
+//!
+//! ```text
+//! {"widget":"grimoire","total_ms":38.21,"phases":[{"name":"config","start_ms":0.02,"ms":0.31,"count":1},...]}
+//! ```
+//!
+//! `start_ms` is when a phase first began, relative to [`start`]; events such
+//! as `first_configure` have no duration.
+
+use std::sync::Mutex;
+use std::time::Instant;
+use crate::cli::Parsed;
+
+struct Phase {
+    name: &'static str,
+    start: f64,
+    ms: f64,
+    count: u32,
+}
+
+struct Trace {
+    widget: &'static str,
+    start: Instant,
+    phases: Vec<Phase>,
+}
+
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+fn ms(since: Instant, at: Instant) -> f64 { at.duration_since(since).as_secs_f64() * 1000.0 }
+
+/// Start tracing if asked to on the command line or in the environment.
+pub fn start(widget: &'static str, opts: &Parsed) {
+    let env = std::env::var("WIDGETS_TRACE_STARTUP").is_ok_and(|v| !v.is_empty() && v != "0");
+    if !env && !opts.iter().any(|(n, _)| *n == "--trace-startup") { return; }
+    *TRACE.lock().unwrap() = Some(Trace { widget, start: Instant::now(), phases: Vec::new() });
+}
+
+fn record(name: &'static str, from: Instant, to: Instant) {
+    let mut trace = TRACE.lock().unwrap();
+    let Some(t) = trace.as_mut() else { return };
+    let (start, duration) = (ms(t.start, from), ms(from, to));
+    match t.phases.iter_mut().find(|p| p.name == name) {
+        Some(p) => { p.ms += duration; p.count += 1; }
+        None => t.phases.push(Phase { name, start, ms: duration, count: 1 }),
+    }
+}
+
+fn tracing() -> bool { TRACE.lock().unwrap().is_some() }
+
+/// Run `f` as (part of) phase `name`.
+pub fn span<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    if !tracing() { return f(); }
+    let from = Instant::now();
+    let out = f();
+    record(name, from, Instant::now());
+    out
+}
+
+/// Record phase `name` as running from `from` until now, for phases that
+/// don't fit in a closure.
+pub fn since(name: &'static str, from: Instant) { record(name, from, Instant::now()); }
+
+/// Note that `name` happened now.
+pub fn event(name: &'static str) {
+    let now = Instant::now();
+    record(name, now, now);
+}
+
+/// Print the trace and stop tracing; later phases are not recorded.
+pub fn finish() {
+    let Some(t) = TRACE.lock().unwrap().take() else { return };
+    let phases: Vec<String> = t.phases.iter().map(|p| {
+        format!("{{\"name\":\"{}\",\"start_ms\":{:.2},\"ms\":{:.2},\"count\":{}}}", p.name, p.start, p.ms, p.count)
+    }).collect();
+    eprintln!("{{\"widget\":\"{}\",\"total_ms\":{:.2},\"phases\":[{}]}}", t.widget, ms(t.start, Instant::now()), phases.join(","));
+}
@@ -0,0 +1,83 @@
+//! Reloading on file changes.
+//!
+//! walrs and most editors replace files by writing a temporary and renaming
+//! it over the old one, which drops any watch on the file itself, so the
+//! parent directories are watched instead and events filtered by name. A
+//! file that doesn't exist yet is picked up once it is created, as long as
+//! its directory exists; a missing directory is only retried after the next
+//! reload.
+
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::AsFd;
+use std::path::PathBuf;
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+use smithay_client_toolkit::reexports::calloop::generic::Generic;
+use smithay_client_toolkit::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+use crate::color::palette_files;
+use crate::config::config_path;
+
+struct Watched {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+impl Watched {
+    fn set_files(&mut self, files: Vec<PathBuf>) {
+        for file in &files {
+            let Some(dir) = file.parent() else { continue };
+            if self.dirs.values().any(|d| d == dir) { continue; }
+            let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE;
+            match self.inotify.watches().add(dir, mask) {
+                Ok(wd) => { self.dirs.insert(wd, dir.to_path_buf()); }
+                // No config directory at all is the common case, not an error
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => eprintln!("widgets: cannot watch {}: {e}", dir.display()),
+            }
+        }
+        self.files = files;
+    }
+
+    /// Drain pending events; true if any of them touched a watched file.
+    fn changed(&mut self) -> bool {
+        let mut buf = [0u8; 4096];
+        let mut hit = false;
+        while let Ok(events) = self.inotify.read_events(&mut buf) {
+            for event in events {
+                let (Some(dir), Some(name)) = (self.dirs.get(&event.wd), event.name) else { continue };
+                let path = dir.join(name);
+                hit |= self.files.contains(&path);
+            }
+        }
+        hit
+    }
+}
+
+/// A widget's config file plus the color file it points at and the base
+/// palettes under that.
+pub fn config_files(name: &str, color_file: Option<&str>) -> Vec<PathBuf> {
+    std::iter::once(config_path(name)).chain(palette_files(color_file)).collect()
+}
+
+/// Call `reload` whenever one of `files` is written or replaced. It returns
+/// the files to watch from then on, since a reloaded config may point at a
+/// different color file.
+pub fn watch<D: 'static>(
+    loop_handle: &LoopHandle<'static, D>, files: Vec<PathBuf>, mut reload: impl FnMut(&mut D) -> Vec<PathBuf> + 'static,
+) -> io::Result<()> {
+    let inotify = Inotify::init()?;
+    // The event source only needs the fd for readiness; reads go through `watched`
+    let fd = inotify.as_fd().try_clone_to_owned()?;
+    let mut watched = Watched { inotify, dirs: HashMap::new(), files: Vec::new() };
+    watched.set_files(files);
+    let source = Generic::new(fd, Interest::READ, Mode::Level);
+    loop_handle.insert_source(source, move |_, _, data| {
+        if watched.changed() {
+            let files = reload(data);
+            watched.set_files(files);
+        }
+        Ok(PostAction::Continue)
+    }).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,1275 @@
+//! Layer-shell window scaffold.
+//!
+//! [`Window`] owns the Wayland state and implements every sctk handler once,
+//! forwarding input to a [`Widget`] and presenting whatever it draws.
+//!
+//! Widgets always work in buffer pixels: the window picks the scale
+//! (`wp_fractional_scale_v1` + `wp_viewporter` when available, integer
+//! `wl_surface` buffer scale otherwise), hands it to [`Widget::resize`] and
+//! scales pointer positions before forwarding them.
+//!
+//! Frames are retained: the window keeps the last pixmap and shm buffer and
+//! lets the widget repaint just the regions that changed
+//! ([`Widget::draw_dirty`]), copying and damaging only those.
+//!
+//! Drawing is paced by `wl_surface.frame`: after a commit, further redraws
+//! wait for the frame callback, and running animations are stepped from it.
+//! A hidden surface gets no callbacks, so it neither draws nor animates.
+//!
+//! Pasting goes through `wl_data_device`: a widget asks with
+//! [`Update::Paste`] and gets the clipboard's text in [`Widget::paste`] once
+//! the owning client has written it. Copying is [`Widget::take_clip`]; see
+//! [`crate::clipboard`].
+//!
+//! The panel around the widget (shadow, background, border, rounded corners;
+//! see [`crate::style`]) is the window's: widgets get a pixmap for the
+//! content inside it, and pointer, touch and caret positions are translated
+//! to and from that pixmap.
+//!
+//! Surfaces open and close with the configured [`Transition`]: the window
+//! composites the widget's retained frame with an opacity, offset or scale
+//! each frame callback. Closing is deferred until the transition has played,
+//! whether it comes from [`Update::Exit`], IPC or SIGTERM/SIGINT.
+//!
+//! Touch is turned into the pointer events widgets already handle: a tap
+//! clicks, dragging scrolls in steps like a wheel (content follows the
+//! finger), a long press right-clicks and a horizontal swipe dismisses the
+//! widget as Escape would. Only the first finger down is followed.
+//!
+//! Input methods reach widgets with a text field (those that report
+//! [`Widget::text_cursor`]) through `zwp_text_input_v3`: each `done` event
+//! arrives as one [`TextInput`] in [`Widget::text_input`], and the caret
+//! rectangle is sent back after every frame so the candidate popup follows it.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+use smithay_client_toolkit as sctk;
+use sctk::compositor::{CompositorHandler, CompositorState, Region};
+use sctk::data_device_manager::data_device::{DataDevice, DataDeviceHandler};
+use sctk::data_device_manager::data_offer::{DataOfferHandler, DragOffer};
+use sctk::data_device_manager::data_source::DataSourceHandler;
+use sctk::data_device_manager::{DataDeviceManagerState, WritePipe};
+use sctk::output::{OutputHandler, OutputInfo, OutputState};
+use sctk::reexports::calloop::timer::{TimeoutAction, Timer};
+use sctk::reexports::calloop::generic::Generic;
+use sctk::reexports::calloop::{EventLoop, Interest, LoopHandle, Mode, PostAction, RegistrationToken};
+use sctk::reexports::calloop_wayland_source::WaylandSource;
+use sctk::registry::{ProvidesRegistryState, RegistryState};
+use sctk::registry_handlers;
+use sctk::seat::keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers};
+use sctk::seat::pointer::cursor_shape::CursorShapeManager;
+use sctk::seat::pointer::{AxisScroll, PointerEvent, PointerEventKind, PointerHandler, BTN_LEFT, BTN_RIGHT};
+use sctk::seat::touch::TouchHandler;
+use sctk::seat::{Capability, SeatHandler, SeatState};
+use sctk::reexports::protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::{Shape, WpCursorShapeDeviceV1};
+use sctk::reexports::protocols::wp::fractional_scale::v1::client::{
+    wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+    wp_fractional_scale_v1::{self, WpFractionalScaleV1},
+};
+use sctk::reexports::protocols::wp::text_input::zv3::client::{
+    zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+    zwp_text_input_v3::{self, ContentHint, ContentPurpose, ZwpTextInputV3},
+};
+use sctk::reexports::protocols::wp::viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter};
+use sctk::reexports::protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1}, zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::ZwlrDataControlOfferV1, zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+use sctk::shell::wlr_layer::{
+    Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+    LayerSurfaceConfigure,
+};
+use sctk::shell::WaylandSurface;
+use sctk::shm::slot::{Buffer, SlotPool};
+use sctk::shm::{Shm, ShmHandler};
+use sctk::{
+    delegate_compositor, delegate_data_device, delegate_keyboard, delegate_layer, delegate_output,
+    delegate_pointer, delegate_registry, delegate_seat, delegate_shm, delegate_touch,
+};
+use serde::{Deserialize, Serialize};
+use tiny_skia::{FilterQuality, Pixmap, PixmapPaint, Transform};
+use wayland_client::globals::registry_queue_init;
+use wayland_client::protocol::{
+    wl_data_device::WlDataDevice, wl_data_device_manager::DndAction, wl_data_source::WlDataSource,
+    wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface, wl_touch,
+};
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy, QueueHandle};
+use crate::anim::Tween;
+use crate::clipboard::{self, Clip, Clipboard, Source, TEXT_MIMES};
+use crate::output;
+use crate::render::{copy_rect_to_argb, copy_to_argb, Rect};
+use crate::style::{Backdrop, Chrome, Style};
+use crate::trace;
+
+/// What the window should do after the widget handled an event.
+#[derive(PartialEq, Clone, Copy)]
+pub enum Update {
+    None,
+    Redraw,
+    Exit,
+    /// Read the clipboard and pass its text to [`Widget::paste`].
+    Paste,
+}
+
+/// Widget-specific state and behaviour driven by a [`Window`].
+pub trait Widget: 'static {
+    /// Render a full frame; the pixmap matches the surface size.
+    fn draw(&mut self, pixmap: &mut Pixmap);
+    /// Repaint only what changed since the last frame into the retained
+    /// `pixmap` and return the rects touched (empty: nothing to present).
+    /// `None` asks for a full [`Widget::draw`].
+    fn draw_dirty(&mut self, _pixmap: &mut Pixmap) -> Option<Vec<Rect>> { None }
+    /// New buffer size in pixels; `scale` maps logical (surface) units to pixels.
+    fn resize(&mut self, _width: u32, _height: u32, _scale: f32) {}
+    fn key(&mut self, _event: &KeyEvent, _modifiers: &Modifiers) -> Update { Update::None }
+    /// Keyboard focus moved to another surface.
+    fn unfocused(&mut self) -> Update { Update::None }
+    fn pointer(&mut self, _event: &PointerEvent) -> Update { Update::None }
+    /// Colors of the panel painted around and behind the widget.
+    fn backdrop(&self) -> Backdrop { Backdrop::default() }
+    /// Cursor for the pointer at `x, y` (buffer pixels); asked after every
+    /// pointer frame.
+    fn cursor(&self, _x: f64, _y: f64) -> Shape { Shape::Default }
+    /// Whether an animation is running; [`Widget::animate`] is then called
+    /// once per frame callback.
+    fn animating(&self) -> bool { false }
+    /// Advance animations to `now`.
+    fn animate(&mut self, _now: Instant) -> Update { Update::None }
+    /// The surface entered an output.
+    fn output_entered(&mut self, _layer: &LayerSurface, _info: &OutputInfo) {}
+    /// A resident window was hidden; drop per-show state.
+    fn hidden(&mut self) {}
+    /// Clipboard text asked for with [`Update::Paste`].
+    fn paste(&mut self, _text: &str) -> Update { Update::None }
+    /// Where the text caret was last drawn, in buffer pixels. Widgets that
+    /// return `Some` get input method events.
+    fn text_cursor(&self) -> Option<Rect> { None }
+    /// An input method update; see [`TextInput`].
+    fn text_input(&mut self, _event: &TextInput) -> Update { Update::None }
+    /// Something to put on the clipboard, asked for after each event the
+    /// widget handles (and before its [`Update`] is applied).
+    fn take_clip(&mut self) -> Option<Clip> { None }
+}
+
+/// What remote control (`msg`, D-Bus) works on: a widget and the window
+/// showing it. A [`Window`] is one; [`crate::replay`] stands in for one
+/// without a compositor.
+pub trait Host {
+    type Widget: Widget;
+    fn widget(&mut self) -> &mut Self::Widget;
+    fn opts(&mut self) -> &mut Options;
+    /// See [`Window::apply`].
+    fn apply(&mut self, update: Update);
+    fn shown(&self) -> bool;
+    fn show(&mut self);
+    fn hide(&mut self);
+    /// See [`Window::dismiss`].
+    fn dismiss(&mut self);
+    fn quit(&mut self);
+}
+
+/// One `zwp_text_input_v3.done`, to be applied in field order: delete around
+/// the caret, insert `commit`, then show `preedit` (replacing any previous one).
+#[derive(Default, Clone, Debug)]
+pub struct TextInput {
+    /// Composition in progress, with the caret's byte offset into it (`None` hides the caret).
+    pub preedit: Option<(String, Option<usize>)>,
+    pub commit: Option<String>,
+    /// Bytes to delete before and after the caret.
+    pub delete: (usize, usize),
+}
+
+// --- Placement ---
+
+/// A `zwlr_layer_shell_v1` layer, by config name.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerName { Background, Bottom, Top, Overlay }
+
+impl From<LayerName> for Layer {
+    fn from(l: LayerName) -> Self {
+        match l {
+            LayerName::Background => Layer::Background,
+            LayerName::Bottom => Layer::Bottom,
+            LayerName::Top => Layer::Top,
+            LayerName::Overlay => Layer::Overlay,
+        }
+    }
+}
+
+/// How the surface appears and disappears; slide and scale fade as well.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition { None, Fade, Slide, Scale }
+
+/// Edges a surface is anchored to, written as e.g. `"top right"`;
+/// `"center"` anchors to none.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Edges(pub Anchor);
+
+impl TryFrom<String> for Edges {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, String> {
+        let mut anchor = Anchor::empty();
+        for word in s.split(|c: char| c.is_whitespace() || c == '-' || c == ',').filter(|w| !w.is_empty()) {
+            anchor |= match word {
+                "top" => Anchor::TOP,
+                "bottom" => Anchor::BOTTOM,
+                "left" => Anchor::LEFT,
+                "right" => Anchor::RIGHT,
+                "center" | "none" => Anchor::empty(),
+                _ => return Err(format!("unknown edge `{word}`, expected top, bottom, left, right or center")),
+            };
+        }
+        Ok(Edges(anchor))
+    }
+}
+
+impl From<Edges> for String {
+    fn from(e: Edges) -> Self {
+        let names = [(Anchor::TOP, "top"), (Anchor::BOTTOM, "bottom"), (Anchor::LEFT, "left"), (Anchor::RIGHT, "right")];
+        let words: Vec<_> = names.iter().filter(|(a, _)| e.0.contains(*a)).map(|(_, n)| *n).collect();
+        if words.is_empty() { "center".into() } else { words.join(" ") }
+    }
+}
+
+/// Where the layer surface sits; flattened into every widget's config.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Placement {
+    pub anchor: Edges,
+    pub margin_top: i32,
+    pub margin_right: i32,
+    pub margin_bottom: i32,
+    pub margin_left: i32,
+    pub layer: LayerName,
+    pub exclusive_zone: i32,
+    pub transition: Transition,
+    pub transition_ms: u32,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            anchor: Edges(Anchor::empty()), margin_top: 0, margin_right: 0, margin_bottom: 0, margin_left: 0,
+            layer: LayerName::Overlay, exclusive_zone: 0, transition: Transition::Fade, transition_ms: 150,
+        }
+    }
+}
+
+/// Config docs for [`Placement`]'s keys (see [`crate::config::Documented::FLATTENED`]).
+pub const PLACEMENT_KEYS: &[(&str, &str)] = &[
+    ("anchor", "Edges to attach to, e.g. \"top right\"; \"center\" floats in the middle"),
+    ("margin_top", "Gap to the top edge in logical pixels (when anchored to it)"),
+    ("margin_right", "Gap to the right edge in logical pixels (when anchored to it)"),
+    ("margin_bottom", "Gap to the bottom edge in logical pixels (when anchored to it)"),
+    ("margin_left", "Gap to the left edge in logical pixels (when anchored to it)"),
+    ("layer", "background, bottom, top or overlay"),
+    ("exclusive_zone", "Space to reserve along the anchored edge; 0 reserves none, -1 also ignores other panels"),
+    ("transition", "Open/close animation: none, fade, slide (in from the anchored edge) or scale"),
+    ("transition_ms", "Length of the open/close animation in milliseconds"),
+];
+
+/// How to create the layer surface.
+pub struct Options {
+    pub namespace: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub placement: Placement,
+    pub style: Style,
+    pub keyboard: KeyboardInteractivity,
+    /// Output spec (see [`crate::output`]); `None` lets the compositor choose.
+    pub output: Option<String>,
+    /// Start hidden and hide instead of exiting on [`Update::Exit`]
+    /// (`--daemon`); see [`Window::show`].
+    pub resident: bool,
+}
+
+pub struct Window<W: Widget> {
+    conn: Connection,
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState,
+    compositor: CompositorState,
+    layer_shell: LayerShell,
+    shm: Shm,
+    /// The surface while shown.
+    pub layer: Option<LayerSurface>,
+    pub opts: Options,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    pointer: Option<wl_pointer::WlPointer>,
+    touch: Option<wl_touch::WlTouch>,
+    touch_point: Option<TouchPoint>,
+    cursor_shape_manager: CursorShapeManager,
+    /// The pointer's shape device, with its enter serial and the shape last set.
+    cursor: Option<(WpCursorShapeDeviceV1, u32, Shape)>,
+    data_device_manager: Option<DataDeviceManagerState>,
+    data_device: Option<DataDevice>,
+    clipboard: Clipboard,
+    /// Serial of the last key press, click or tap; `wl_data_device` copies
+    /// must name the input that caused them.
+    input_serial: Option<u32>,
+    text_input_manager: Option<ZwpTextInputManagerV3>,
+    text_input: Option<ZwpTextInputV3>,
+    /// Events since the last `done`.
+    text_input_pending: TextInput,
+    /// Caret rectangle last sent, in surface coordinates; `None` while the text input is disabled.
+    text_cursor: Option<Rect>,
+    pool: SlotPool,
+    buffer: Option<Buffer>,
+    /// The widget's last frame, inside the panel; `None` forces a full redraw.
+    pixmap: Option<Pixmap>,
+    /// Last presented frame, panel included.
+    frame: Option<Pixmap>,
+    chrome: Option<Chrome>,
+    loop_handle: LoopHandle<'static, Self>,
+    qh: QueueHandle<Self>,
+    /// A frame callback is outstanding; draws wait for it.
+    frame_pending: bool,
+    /// A draw was requested while `frame_pending`.
+    dirty: bool,
+    /// Logical surface size.
+    pub width: u32,
+    pub height: u32,
+    pub scale: f64,
+    /// Fractional scaling needs both globals; without them we fall back to integer buffer scale.
+    scalers: Option<(WpFractionalScaleManagerV1, WpViewporter)>,
+    fractional_scale: Option<WpFractionalScaleV1>,
+    viewport: Option<WpViewport>,
+    pub exit: bool,
+    configured: bool,
+    /// Open or close transition in progress.
+    transition: Option<Transitioning>,
+    modifiers: Modifiers,
+    pub widget: W,
+}
+
+impl<W: Widget> Window<W> {
+    pub fn new(event_loop: &EventLoop<'static, Self>, opts: Options, widget: W) -> Self {
+        let bind_start = Instant::now();
+        let conn = Connection::connect_to_env().unwrap();
+        let (globals, mut event_queue) = registry_queue_init::<Self>(&conn).unwrap();
+        let qh = event_queue.handle();
+
+        let compositor = CompositorState::bind(&globals, &qh).unwrap();
+        let layer_shell = LayerShell::bind(&globals, &qh).unwrap();
+        let shm = Shm::bind(&globals, &qh).unwrap();
+        let cursor_shape_manager = CursorShapeManager::bind(&globals, &qh).unwrap();
+        let data_device_manager = DataDeviceManagerState::bind(&globals, &qh).ok();
+        let data_control_manager = globals.bind::<ZwlrDataControlManagerV1, _, _>(&qh, 1..=1, ()).ok();
+        let text_input_manager = globals.bind::<ZwpTextInputManagerV3, _, _>(&qh, 1..=1, ()).ok();
+        let fractional = globals.bind::<WpFractionalScaleManagerV1, _, _>(&qh, 1..=1, ()).ok();
+        let viewporter = globals.bind::<WpViewporter, _, _>(&qh, 1..=1, ()).ok();
+        trace::since("wayland_bind", bind_start);
+
+        let pool = SlotPool::new((opts.width * opts.height * 4) as usize, &shm).unwrap();
+
+        let mut win = Self {
+            conn,
+            registry_state: RegistryState::new(&globals),
+            seat_state: SeatState::new(&globals, &qh),
+            output_state: OutputState::new(&globals, &qh),
+            compositor,
+            layer_shell,
+            shm,
+            layer: None,
+            keyboard: None,
+            pointer: None,
+            touch: None,
+            touch_point: None,
+            cursor_shape_manager,
+            cursor: None,
+            data_device_manager,
+            data_device: None,
+            clipboard: Clipboard::new(data_control_manager),
+            input_serial: None,
+            text_input_manager,
+            text_input: None,
+            text_input_pending: TextInput::default(),
+            text_cursor: None,
+            pool,
+            buffer: None,
+            pixmap: None,
+            frame: None,
+            chrome: None,
+            loop_handle: event_loop.handle(),
+            qh,
+            frame_pending: false,
+            dirty: false,
+            width: opts.width,
+            height: opts.height,
+            scale: 1.0,
+            scalers: fractional.zip(viewporter),
+            fractional_scale: None,
+            viewport: None,
+            exit: false,
+            configured: false,
+            transition: None,
+            modifiers: Modifiers::default(),
+            widget,
+            opts,
+        };
+        // Picking an output needs their names and positions, which take a
+        // roundtrip to bind the outputs and another for their info
+        if win.opts.output.is_some() && !win.opts.resident {
+            for _ in 0..2 {
+                if let Err(e) = event_queue.roundtrip(&mut win) { eprintln!("widgets: listing outputs: {e}"); }
+            }
+        }
+        WaylandSource::new(win.conn.clone(), event_queue).insert(event_loop.handle()).unwrap();
+        if let Err(e) = watch_terminate(&win.loop_handle) { eprintln!("widgets: cannot handle SIGTERM: {e}"); }
+        if win.opts.resident {
+            // Nothing to draw until asked; the daemon is ready
+            trace::event("ready");
+            trace::finish();
+        } else {
+            win.show();
+        }
+        win
+    }
+
+    /// Whether the surface is mapped; a hidden resident window's isn't.
+    pub fn shown(&self) -> bool { self.layer.is_some() }
+
+    /// Map the layer surface (no-op if already shown) on `opts.output`.
+    pub fn show(&mut self) {
+        if self.layer.is_some() { return; }
+        let qh = &self.qh;
+        let output = self.opts.output.as_deref().and_then(|spec| output::pick(&self.output_state, spec));
+        let surface = self.compositor.create_surface(qh);
+        let layer = self.layer_shell.create_layer_surface(qh, surface, self.opts.placement.layer.into(), Some(self.opts.namespace), output.as_ref());
+        (self.width, self.height) = self.surface_size();
+        layer.set_size(self.width, self.height);
+        self.place(&layer);
+        layer.set_keyboard_interactivity(self.opts.keyboard);
+        if let Some((fractional, viewporter)) = &self.scalers {
+            self.fractional_scale = Some(fractional.get_fractional_scale(layer.wl_surface(), qh, ()));
+            self.viewport = Some(viewporter.get_viewport(layer.wl_surface(), qh, ()));
+        }
+        layer.wl_surface().commit();
+        self.layer = Some(layer);
+    }
+
+    /// Logical surface size: the panel plus room for its shadow.
+    fn surface_size(&self) -> (u32, u32) {
+        let m = 2 * self.opts.style.margin();
+        (self.opts.width + m, self.opts.height + m)
+    }
+
+    /// Only the panel takes input, not its shadow.
+    fn set_input_region(&self) {
+        let Some(layer) = &self.layer else { return };
+        let m = self.opts.style.margin() as i32;
+        if m == 0 { return layer.wl_surface().set_input_region(None); }
+        let Ok(region) = Region::new(&self.compositor) else { return };
+        region.add(m, m, self.width as i32 - 2 * m, self.height as i32 - 2 * m);
+        layer.wl_surface().set_input_region(Some(region.wl_region()));
+    }
+
+    fn place(&self, layer: &LayerSurface) {
+        let p = &self.opts.placement;
+        layer.set_anchor(p.anchor.0);
+        layer.set_margin(p.margin_top, p.margin_right, p.margin_bottom, p.margin_left);
+        layer.set_exclusive_zone(p.exclusive_zone);
+    }
+
+    /// Unmap the surface; the widget keeps its state for the next [`Window::show`].
+    pub fn hide(&mut self) {
+        let Some(layer) = self.layer.take() else { return };
+        if let Some(f) = self.fractional_scale.take() { f.destroy(); }
+        if let Some(v) = self.viewport.take() { v.destroy(); }
+        drop(layer);
+        self.configured = false;
+        self.transition = None;
+        self.frame_pending = false;
+        self.dirty = false;
+        self.pixmap = None;
+        self.frame = None;
+        self.widget.hidden();
+    }
+
+    /// Pick up a reloaded config: move and resize the surface to `opts` and
+    /// repaint everything, re-running [`Widget::resize`] so layout is recomputed.
+    pub fn reload(&mut self) {
+        let Some(layer) = &self.layer else { return };
+        self.place(layer);
+        layer.set_layer(self.opts.placement.layer.into());
+        layer.set_keyboard_interactivity(self.opts.keyboard);
+        let (width, height) = self.surface_size();
+        if (width, height) != (self.width, self.height) {
+            // The configure that follows resizes and redraws
+            layer.set_size(width, height);
+            layer.wl_surface().commit();
+            return;
+        }
+        if !self.configured { return; }
+        self.set_input_region();
+        self.relayout();
+        self.draw();
+    }
+
+    /// Dispatch until something sets `exit`. A copy still on the clipboard
+    /// then is served from a forked child, and the parent exits here.
+    pub fn run(&mut self, event_loop: &mut EventLoop<'static, Self>) {
+        while !self.exit {
+            event_loop.dispatch(None, self).unwrap();
+        }
+        if !self.clipboard.holding() { return; }
+        self.hide();
+        self.conn.flush().ok();
+        io::stdout().flush().ok();
+        match unsafe { libc::fork() } {
+            0 => clipboard::detach(self.opts.namespace, self.conn.backend().poll_fd().as_raw_fd()),
+            -1 => eprintln!("widgets: cannot fork to keep the copy: {}; serving it until it's replaced", io::Error::last_os_error()),
+            _ => std::process::exit(0),
+        }
+        self.exit = false;
+        while !self.exit && self.clipboard.holding() {
+            event_loop.dispatch(None, self).unwrap();
+        }
+        std::process::exit(0);
+    }
+
+    /// Put `clip` on the clipboard.
+    pub fn copy(&mut self, clip: Clip) {
+        let source = match (&self.data_device_manager, &self.data_device, self.input_serial, &self.clipboard) {
+            (Some(manager), Some(device), Some(serial), _) => {
+                let source = manager.create_copy_paste_source(&self.qh, &clip.mimes);
+                source.set_selection(device, serial);
+                Source::Device(source)
+            }
+            (.., Clipboard { manager: Some(manager), device: Some(device), .. }) => {
+                let source = manager.create_data_source(&self.qh, ());
+                for mime in &clip.mimes { source.offer(mime.clone()); }
+                device.set_selection(Some(&source));
+                Source::Control(source)
+            }
+            _ => return eprintln!("widgets: cannot copy: no input to copy on and no zwlr_data_control_manager_v1"),
+        };
+        self.clipboard.own(source, clip);
+        self.conn.flush().ok();
+    }
+
+    fn take_clip(&mut self) {
+        if let Some(clip) = self.widget.take_clip() { self.copy(clip); }
+    }
+
+    pub fn apply(&mut self, update: Update) {
+        self.take_clip();
+        match update {
+            Update::None => {}
+            Update::Redraw => self.draw(),
+            Update::Exit => self.dismiss(),
+            Update::Paste => self.paste(),
+        }
+    }
+
+    /// Start reading the clipboard as text; the widget gets it when the
+    /// source client has finished writing.
+    fn paste(&mut self) {
+        let Some(offer) = self.data_device.as_ref().and_then(|d| d.data().selection_offer()) else { return };
+        let mime = offer.with_mime_types(|types| {
+            TEXT_MIMES.iter().find(|m| types.iter().any(|t| t == *m)).map(|m| m.to_string())
+        });
+        let Some(pipe) = mime.and_then(|m| offer.receive(m).ok()) else { return };
+        let mut data = Vec::new();
+        let source = self.loop_handle.insert_source(pipe, move |_, file, win| {
+            let mut buf = [0; 4096];
+            match (&**file).read(&mut buf) {
+                Ok(0) => {
+                    let update = win.widget.paste(&String::from_utf8_lossy(&data));
+                    win.apply(update);
+                    PostAction::Remove
+                }
+                Ok(n) => { data.extend_from_slice(&buf[..n]); PostAction::Continue }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => PostAction::Continue,
+                Err(_) => PostAction::Remove,
+            }
+        });
+        if let Err(e) = source { eprintln!("widgets: cannot read clipboard: {e}"); }
+    }
+
+    /// What [`Update::Exit`] does: play the close transition, then hide
+    /// (resident) or exit.
+    pub fn dismiss(&mut self) {
+        self.close(if self.opts.resident { Closing::Hide } else { Closing::Exit });
+    }
+
+    /// Close and exit, even when resident. Asking again while the close
+    /// transition plays exits at once.
+    pub fn quit(&mut self) {
+        if self.closing() == Some(Closing::Exit) { self.exit = true; }
+        self.close(Closing::Exit);
+    }
+
+    fn closing(&self) -> Option<Closing> {
+        self.transition.as_ref().and_then(|t| t.closing)
+    }
+
+    fn close(&mut self, then: Closing) {
+        let p = &self.opts.placement;
+        if let Some(t) = self.transition.as_mut().filter(|t| t.closing.is_some()) {
+            if then == Closing::Exit { t.closing = Some(then); }
+            return;
+        }
+        if !self.configured || p.transition == Transition::None || p.transition_ms == 0 {
+            return self.closed(then);
+        }
+        // Reverse an unfinished open from where it got to
+        let from = self.transition.as_ref().map_or(1.0, |t| t.tween.value(Instant::now()));
+        let duration = Duration::from_millis(p.transition_ms as u64).mul_f32(from);
+        self.transition = Some(Transitioning { tween: Tween::new(from, 0.0, duration), closing: Some(then) });
+        self.draw();
+    }
+
+    fn closed(&mut self, then: Closing) {
+        self.transition = None;
+        match then {
+            Closing::Hide => self.hide(),
+            Closing::Exit => self.exit = true,
+        }
+    }
+
+    /// Buffer size in pixels for the current logical size and scale.
+    pub fn buffer_size(&self) -> (u32, u32) {
+        ((self.width as f64 * self.scale).round() as u32, (self.height as f64 * self.scale).round() as u32)
+    }
+
+    fn set_scale(&mut self, scale: f64) {
+        if scale == self.scale { return; }
+        self.scale = scale;
+        if !self.configured { return; }
+        self.relayout();
+        self.draw();
+    }
+
+    /// Rebuild the panel and hand the widget its new content size.
+    fn relayout(&mut self) {
+        self.chrome = None;
+        self.update_chrome();
+    }
+
+    /// Rebuild the panel if the size, scale, style or the widget's colors
+    /// changed since it was drawn, forcing a full redraw.
+    fn update_chrome(&mut self) {
+        let (width, height) = self.buffer_size();
+        let (backdrop, scale) = (self.widget.backdrop(), self.scale as f32);
+        if self.chrome.as_ref().is_some_and(|c| c.is_for(&self.opts.style, backdrop, width, height, scale)) { return; }
+        let chrome = Chrome::new(&self.opts.style, backdrop, width, height, scale);
+        let content = chrome.content;
+        self.chrome = Some(chrome);
+        self.widget.resize(content.w, content.h, scale);
+        self.pixmap = None;
+        self.frame = None;
+    }
+
+    /// A surface position (logical) in the widget's pixmap, if it is on it.
+    fn to_content(&self, (x, y): (f64, f64)) -> Option<(f64, f64)> {
+        let c = self.chrome.as_ref()?.content;
+        let (x, y) = (x * self.scale - c.x as f64, y * self.scale - c.y as f64);
+        (x >= 0.0 && y >= 0.0 && x < c.w as f64 && y < c.h as f64).then_some((x, y))
+    }
+
+    /// `event` in the widget's coordinates. Off the content, motion becomes a
+    /// leave and buttons and scrolling are dropped.
+    fn content_event(&self, event: &PointerEvent) -> Option<PointerEvent> {
+        let mut event = event.clone();
+        match (self.to_content(event.position), &event.kind) {
+            (Some(position), _) => event.position = position,
+            (None, PointerEventKind::Enter { .. } | PointerEventKind::Leave { .. }) => event.position = (-1.0, -1.0),
+            (None, PointerEventKind::Motion { .. }) => {
+                event.position = (-1.0, -1.0);
+                event.kind = PointerEventKind::Leave { serial: 0 };
+            }
+            (None, _) => return None,
+        }
+        Some(event)
+    }
+
+    pub fn draw(&mut self) {
+        if !self.configured { return; }
+        if self.frame_pending { self.dirty = true; return; }
+        self.update_chrome();
+        let (width, height) = self.buffer_size();
+        let chrome = self.chrome.as_ref().unwrap();
+        let c = chrome.content;
+        let (pixmap, damage) = match self.pixmap.as_mut() {
+            Some(pixmap) => {
+                let damage = self.widget.draw_dirty(pixmap);
+                (pixmap, damage)
+            }
+            None => (self.pixmap.insert(Pixmap::new(c.w, c.h).unwrap()), None),
+        };
+        let full = Rect { x: 0, y: 0, w: width, h: height };
+        let damage = match damage {
+            Some(rects) if self.frame.is_some() => rects.into_iter().map(|r| Rect { x: r.x + c.x, y: r.y + c.y, ..r }).collect(),
+            Some(_) => vec![full],
+            None => {
+                trace::span("draw", || self.widget.draw(pixmap));
+                vec![full]
+            }
+        };
+        let frame = self.frame.get_or_insert_with(|| Pixmap::new(width, height).unwrap());
+        for &r in &damage { chrome.compose(frame, pixmap, r); }
+        // Mid-transition every frame differs everywhere
+        let composed = self.transition.as_ref().map(|t| {
+            compose(frame, self.opts.placement.transition, self.opts.placement.anchor.0, t.tween.value(Instant::now()))
+        });
+        let (pixmap, damage) = match &composed {
+            Some(composed) => (composed, vec![full]),
+            None => (&*frame, damage),
+        };
+        if damage.is_empty() { return; }
+
+        // Reuse the last buffer when the compositor has released it; it still
+        // holds the previous frame, so only the damaged rects need copying
+        let stride = width as i32 * 4;
+        let reusable = self.buffer.as_ref()
+            .is_some_and(|b| b.height() == height as i32 && b.stride() == stride);
+        match self.buffer.as_ref().filter(|_| reusable).and_then(|b| b.canvas(&mut self.pool)) {
+            Some(canvas) => {
+                for &r in &damage { copy_rect_to_argb(canvas, pixmap.data(), width, height, r); }
+            }
+            None => {
+                let (buffer, canvas) = self.pool
+                    .create_buffer(width as i32, height as i32, stride, wl_shm::Format::Argb8888)
+                    .unwrap();
+                copy_to_argb(canvas, pixmap.data());
+                self.buffer = Some(buffer);
+            }
+        }
+
+        if let Some(viewport) = &self.viewport {
+            viewport.set_destination(self.width as i32, self.height as i32);
+        }
+        let surface = self.layer.as_ref().unwrap().wl_surface();
+        self.buffer.as_ref().unwrap().attach_to(surface).unwrap();
+        for r in &damage {
+            surface.damage_buffer(r.x as i32, r.y as i32, r.w as i32, r.h as i32);
+        }
+        surface.frame(&self.qh, surface.clone());
+        self.frame_pending = true;
+        surface.commit();
+        trace::event("first_commit");
+        trace::finish();
+        self.send_text_cursor();
+    }
+
+    /// Tell the input method where the caret is, if it moved.
+    fn send_text_cursor(&mut self) {
+        let (Some(text_input), Some(sent)) = (&self.text_input, self.text_cursor) else { return };
+        let (Some(r), Some(chrome)) = (self.widget.text_cursor(), &self.chrome) else { return };
+        let r = Rect { x: r.x + chrome.content.x, y: r.y + chrome.content.y, ..r };
+        let s = self.scale;
+        let logical = |v: u32| (v as f64 / s).round() as u32;
+        let r = Rect { x: logical(r.x), y: logical(r.y), w: logical(r.w).max(1), h: logical(r.h).max(1) };
+        if r == sent { return; }
+        text_input.set_cursor_rectangle(r.x as i32, r.y as i32, r.w as i32, r.h as i32);
+        text_input.commit();
+        self.text_cursor = Some(r);
+    }
+
+    fn key(&mut self, event: &KeyEvent) {
+        if self.closing().is_some() { return; }
+        let update = self.widget.key(event, &self.modifiers);
+        self.apply(update);
+    }
+}
+
+// --- Transitions ---
+
+#[derive(PartialEq, Clone, Copy)]
+enum Closing { Hide, Exit }
+
+struct Transitioning {
+    /// 0 is fully closed, 1 fully open.
+    tween: Tween,
+    /// Set while closing: what to do once closed.
+    closing: Option<Closing>,
+}
+
+/// `frame` as it looks `t` of the way open.
+fn compose(frame: &Pixmap, kind: Transition, anchor: Anchor, t: f32) -> Pixmap {
+    let (w, h) = (frame.width() as f32, frame.height() as f32);
+    let transform = match kind {
+        Transition::Slide => {
+            let edge = |a, b| anchor.contains(a) && !anchor.contains(b);
+            let (dx, dy) = if edge(Anchor::TOP, Anchor::BOTTOM) { (0.0, -h) }
+                else if edge(Anchor::BOTTOM, Anchor::TOP) { (0.0, h) }
+                else if edge(Anchor::LEFT, Anchor::RIGHT) { (-w, 0.0) }
+                else if edge(Anchor::RIGHT, Anchor::LEFT) { (w, 0.0) }
+                // Floating: rise a little from below
+                else { (0.0, h / 8.0) };
+            Transform::from_translate(dx * (1.0 - t), dy * (1.0 - t))
+        }
+        Transition::Scale => {
+            let k = 0.9 + 0.1 * t;
+            Transform::from_scale(k, k).post_translate(w * (1.0 - k) / 2.0, h * (1.0 - k) / 2.0)
+        }
+        Transition::None | Transition::Fade => Transform::identity(),
+    };
+    let mut out = Pixmap::new(frame.width(), frame.height()).unwrap();
+    let paint = PixmapPaint { opacity: t.clamp(0.0, 1.0), quality: FilterQuality::Bilinear, ..PixmapPaint::default() };
+    out.draw_pixmap(0, 0, frame.as_ref(), &paint, transform, None);
+    out
+}
+
+/// Write end of the pipe that turns SIGTERM/SIGINT into an event loop wakeup.
+static TERM_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_terminate(_: libc::c_int) {
+    let fd = TERM_PIPE.load(Ordering::Relaxed);
+    unsafe { libc::write(fd, [0u8].as_ptr().cast(), 1); }
+}
+
+/// Quit through [`Window::quit`] on SIGTERM or SIGINT, so the close
+/// transition plays and the widget's shutdown code runs.
+fn watch_terminate<W: Widget>(loop_handle: &LoopHandle<'static, Window<W>>) -> io::Result<()> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let read = File::from(unsafe { OwnedFd::from_raw_fd(fds[0]) });
+    TERM_PIPE.store(fds[1], Ordering::Relaxed);
+    unsafe {
+        let mut sa: libc::sigaction = std::mem::zeroed();
+        sa.sa_sigaction = on_terminate as *const () as usize;
+        sa.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut sa.sa_mask);
+        libc::sigaction(libc::SIGTERM, &sa, std::ptr::null_mut());
+        libc::sigaction(libc::SIGINT, &sa, std::ptr::null_mut());
+    }
+    loop_handle.insert_source(Generic::new(read, Interest::READ, Mode::Level), |_, file, win| {
+        let mut buf = [0; 16];
+        while (&**file).read(&mut buf).is_ok_and(|n| n > 0) {}
+        win.quit();
+        Ok(PostAction::Continue)
+    }).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(())
+}
+
+// --- Touch ---
+
+/// Movement (logical px) before a touch stops being a tap or long press.
+const TOUCH_SLOP: f64 = 10.0;
+/// Drag distance (logical px) per scroll step.
+const TOUCH_SCROLL_STEP: f64 = 40.0;
+/// Horizontal travel (logical px) that dismisses.
+const SWIPE_DISTANCE: f64 = 80.0;
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+#[derive(PartialEq, Clone, Copy)]
+enum Gesture { Pending, Scroll, Swipe, Held }
+
+/// The finger being followed; positions are logical.
+struct TouchPoint {
+    id: i32,
+    surface: wl_surface::WlSurface,
+    start: (f64, f64),
+    position: (f64, f64),
+    /// Where the last scroll step was taken.
+    scrolled_at: f64,
+    gesture: Gesture,
+    long_press: Option<RegistrationToken>,
+}
+
+impl<W: Widget> Window<W> {
+    /// Hand the widget a pointer event at the touch point.
+    fn touch_pointer(&mut self, kind: PointerEventKind) {
+        let Some(t) = &self.touch_point else { return };
+        let event = PointerEvent { surface: t.surface.clone(), position: t.position, kind };
+        let Some(event) = self.content_event(&event) else { return };
+        let update = self.widget.pointer(&event);
+        self.apply(update);
+    }
+
+    fn touch_click(&mut self, button: u32, time: u32) {
+        self.touch_pointer(PointerEventKind::Press { time, button, serial: 0 });
+        self.touch_pointer(PointerEventKind::Release { time, button, serial: 0 });
+    }
+
+    fn cancel_long_press(&mut self) {
+        if let Some(token) = self.touch_point.as_mut().and_then(|t| t.long_press.take()) {
+            self.loop_handle.remove(token);
+        }
+    }
+
+    /// The finger is lifted or the compositor took the touch: clear hover.
+    fn touch_end(&mut self) {
+        self.cancel_long_press();
+        self.touch_pointer(PointerEventKind::Leave { serial: 0 });
+        self.touch_point = None;
+    }
+}
+
+impl<W: Widget> Host for Window<W> {
+    type Widget = W;
+    fn widget(&mut self) -> &mut W { &mut self.widget }
+    fn opts(&mut self) -> &mut Options { &mut self.opts }
+    fn apply(&mut self, update: Update) { self.apply(update) }
+    fn shown(&self) -> bool { self.shown() }
+    fn show(&mut self) { self.show() }
+    fn hide(&mut self) { self.hide() }
+    fn dismiss(&mut self) { self.dismiss() }
+    fn quit(&mut self) { self.quit() }
+}
+
+// --- Wayland handler boilerplate ---
+
+impl<W: Widget> CompositorHandler for Window<W> {
+    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, surface: &wl_surface::WlSurface, factor: i32) {
+        if self.fractional_scale.is_some() { return; }
+        surface.set_buffer_scale(factor);
+        self.set_scale(factor as f64);
+    }
+    fn transform_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: wl_output::Transform) {}
+    fn frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, surface: &wl_surface::WlSurface, _: u32) {
+        self.frame_pending = false;
+        let redraw = std::mem::take(&mut self.dirty) || self.transition.is_some();
+        if let Some(t) = self.transition.as_ref().filter(|t| t.tween.done(Instant::now())) {
+            let closing = t.closing;
+            self.transition = None;
+            if let Some(then) = closing { return self.closed(then); }
+            // The retained pixmap is fine, but the buffer still holds a composed frame
+            self.pixmap = None;
+        }
+        let mut update = if redraw { Update::Redraw } else { Update::None };
+        if self.widget.animating() {
+            match self.widget.animate(Instant::now()) {
+                Update::None => {}
+                u => update = u,
+            }
+        }
+        self.apply(update);
+        // Nothing was committed (or damaged) but the animation isn't over yet
+        if !self.frame_pending && (self.widget.animating() || self.transition.is_some()) {
+            surface.frame(qh, surface.clone());
+            self.frame_pending = true;
+            surface.commit();
+        }
+    }
+    fn surface_enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, output: &wl_output::WlOutput) {
+        if let Some(info) = self.output_state.info(output) && let Some(layer) = &self.layer {
+            self.widget.output_entered(layer, &info);
+        }
+    }
+    fn surface_leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: &wl_output::WlOutput) {}
+}
+
+impl<W: Widget> OutputHandler for Window<W> {
+    fn output_state(&mut self) -> &mut OutputState { &mut self.output_state }
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl<W: Widget> SeatHandler for Window<W> {
+    fn seat_state(&mut self) -> &mut SeatState { &mut self.seat_state }
+    fn new_seat(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat) {
+        if self.clipboard.device.is_none() && let Some(manager) = &self.clipboard.manager {
+            self.clipboard.device = Some(manager.get_data_device(&seat, qh, ()));
+        }
+    }
+    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, capability: Capability) {
+        if capability == Capability::Keyboard && self.data_device.is_none() && let Some(manager) = &self.data_device_manager {
+            self.data_device = Some(manager.get_data_device(qh, &seat));
+        }
+        if capability == Capability::Keyboard && self.text_input.is_none() && let Some(manager) = &self.text_input_manager {
+            self.text_input = Some(manager.get_text_input(&seat, qh, ()));
+        }
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = Some(self.seat_state.get_keyboard_with_repeat(
+                qh, &seat, None,
+                self.loop_handle.clone(),
+                Box::new(|state, _wl_kbd, event| {
+                    state.key(&event);
+                }),
+            ).unwrap());
+        }
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            self.pointer = Some(self.seat_state.get_pointer(qh, &seat).unwrap());
+        }
+        if capability == Capability::Touch && self.touch.is_none() {
+            self.touch = Some(self.seat_state.get_touch(qh, &seat).unwrap());
+        }
+    }
+    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat, _: Capability) {}
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl<W: Widget> KeyboardHandler for Window<W> {
+    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32, _: &[u32], _: &[Keysym]) {}
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {
+        self.input_serial = None;
+        let update = self.widget.unfocused();
+        self.apply(update);
+    }
+    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, serial: u32, event: KeyEvent) {
+        self.input_serial = Some(serial);
+        self.key(&event);
+    }
+    fn repeat_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
+        self.key(&event);
+    }
+    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
+    fn update_modifiers(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, modifiers: Modifiers, _: RawModifiers, _: u32) { self.modifiers = modifiers; }
+}
+
+impl<W: Widget> PointerHandler for Window<W> {
+    fn pointer_frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, pointer: &wl_pointer::WlPointer, events: &[PointerEvent]) {
+        if self.closing().is_some() { return; }
+        let mut redraw = false;
+        let mut at = None;
+        for event in events {
+            if let PointerEventKind::Enter { serial } = event.kind {
+                let device = match self.cursor.take() {
+                    Some((device, ..)) => device,
+                    None => self.cursor_shape_manager.get_shape_device(pointer, qh),
+                };
+                device.set_shape(serial, Shape::Default);
+                self.cursor = Some((device, serial, Shape::Default));
+            }
+            at = self.to_content(event.position).filter(|_| !matches!(event.kind, PointerEventKind::Leave { .. }));
+            if let PointerEventKind::Press { serial, .. } = event.kind { self.input_serial = Some(serial); }
+            let Some(event) = self.content_event(event) else { continue };
+            let update = self.widget.pointer(&event);
+            self.take_clip();
+            match update {
+                Update::None => {}
+                Update::Redraw => redraw = true,
+                Update::Exit => { self.dismiss(); return; }
+                Update::Paste => self.paste(),
+            }
+        }
+        if let Some((device, serial, shape)) = &mut self.cursor {
+            let wanted = at.map_or(Shape::Default, |(x, y)| self.widget.cursor(x, y));
+            if wanted != *shape {
+                device.set_shape(*serial, wanted);
+                *shape = wanted;
+            }
+        }
+        if redraw { self.draw(); }
+    }
+}
+
+impl<W: Widget> TouchHandler for Window<W> {
+    fn down(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_touch::WlTouch, serial: u32, time: u32, surface: wl_surface::WlSurface, id: i32, position: (f64, f64)) {
+        if self.touch_point.is_some() || self.closing().is_some() { return; }
+        self.input_serial = Some(serial);
+        let timer = Timer::from_duration(LONG_PRESS);
+        let long_press = self.loop_handle.insert_source(timer, move |_, _, win| {
+            if let Some(t) = win.touch_point.as_mut().filter(|t| t.gesture == Gesture::Pending) {
+                t.gesture = Gesture::Held;
+                t.long_press = None;
+                win.touch_click(BTN_RIGHT, 0);
+            }
+            TimeoutAction::Drop
+        }).ok();
+        self.touch_point = Some(TouchPoint { id, surface, start: position, position, scrolled_at: position.1, gesture: Gesture::Pending, long_press });
+        self.touch_pointer(PointerEventKind::Motion { time });
+    }
+    fn up(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_touch::WlTouch, _: u32, time: u32, id: i32) {
+        let Some(t) = self.touch_point.as_ref().filter(|t| t.id == id) else { return };
+        let dx = t.position.0 - t.start.0;
+        match t.gesture {
+            Gesture::Pending => self.touch_click(BTN_LEFT, time),
+            Gesture::Swipe if dx.abs() >= SWIPE_DISTANCE => {
+                self.touch_point = None;
+                self.dismiss();
+                return;
+            }
+            _ => {}
+        }
+        self.touch_end();
+    }
+    fn motion(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_touch::WlTouch, time: u32, id: i32, position: (f64, f64)) {
+        let Some(t) = self.touch_point.as_mut().filter(|t| t.id == id) else { return };
+        t.position = position;
+        let (dx, dy) = (position.0 - t.start.0, position.1 - t.start.1);
+        if t.gesture == Gesture::Pending && dx.hypot(dy) > TOUCH_SLOP {
+            t.gesture = if dx.abs() > dy.abs() { Gesture::Swipe } else { Gesture::Scroll };
+            self.cancel_long_press();
+        }
+        let Some(t) = self.touch_point.as_mut().filter(|t| t.gesture == Gesture::Scroll) else { return };
+        // Dragging up scrolls down, so the content follows the finger
+        let mut steps = Vec::new();
+        while (t.scrolled_at - position.1).abs() >= TOUCH_SCROLL_STEP {
+            let dir = (t.scrolled_at - position.1).signum();
+            t.scrolled_at -= dir * TOUCH_SCROLL_STEP;
+            steps.push(dir);
+        }
+        for dir in steps {
+            let vertical = AxisScroll { absolute: dir * TOUCH_SCROLL_STEP * self.scale, ..Default::default() };
+            self.touch_pointer(PointerEventKind::Axis { time, horizontal: AxisScroll::default(), vertical, source: None });
+        }
+    }
+    fn shape(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_touch::WlTouch, _: i32, _: f64, _: f64) {}
+    fn orientation(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_touch::WlTouch, _: i32, _: f64) {}
+    fn cancel(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_touch::WlTouch) { self.touch_end(); }
+}
+
+// Only the selection is used; drag-and-drop offers and sources are ignored.
+impl<W: Widget> DataDeviceHandler for Window<W> {
+    fn enter(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataDevice, _: f64, _: f64, _: &wl_surface::WlSurface) {}
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataDevice) {}
+    fn motion(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataDevice, _: f64, _: f64) {}
+    fn selection(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataDevice) {}
+    fn drop_performed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataDevice) {}
+}
+
+impl<W: Widget> DataOfferHandler for Window<W> {
+    fn source_actions(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &mut DragOffer, _: DndAction) {}
+    fn selected_action(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &mut DragOffer, _: DndAction) {}
+}
+
+impl<W: Widget> DataSourceHandler for Window<W> {
+    fn accept_mime(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataSource, _: Option<String>) {}
+    fn send_request(&mut self, _: &Connection, _: &QueueHandle<Self>, source: &WlDataSource, _: String, pipe: WritePipe) {
+        if let Some(data) = self.clipboard.offered_by(&source.id()) { clipboard::serve(&self.loop_handle, pipe, data); }
+    }
+    fn cancelled(&mut self, _: &Connection, _: &QueueHandle<Self>, source: &WlDataSource) { self.clipboard.cancelled(&source.id()); }
+    fn dnd_dropped(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataSource) {}
+    fn dnd_finished(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataSource) {}
+    fn action(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataSource, _: DndAction) {}
+}
+
+impl<W: Widget> ShmHandler for Window<W> {
+    fn shm_state(&mut self) -> &mut Shm { &mut self.shm }
+}
+
+impl<W: Widget> LayerShellHandler for Window<W> {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
+        // Gone already: no close transition
+        self.configured = false;
+        self.apply(Update::Exit);
+    }
+    fn configure(&mut self, _: &Connection, _: &QueueHandle<Self>, layer: &LayerSurface, configure: LayerSurfaceConfigure, _: u32) {
+        // Late configure for a surface hidden since
+        if self.layer.as_ref() != Some(layer) { return; }
+        if configure.new_size.0 > 0 { self.width = configure.new_size.0; }
+        if configure.new_size.1 > 0 { self.height = configure.new_size.1; }
+        if !self.configured { trace::event("first_configure"); }
+        let p = &self.opts.placement;
+        if !self.configured && p.transition != Transition::None && p.transition_ms > 0 {
+            let duration = Duration::from_millis(p.transition_ms as u64);
+            self.transition = Some(Transitioning { tween: Tween::new(0.0, 1.0, duration), closing: None });
+        }
+        self.configured = true;
+        self.set_input_region();
+        self.relayout();
+        self.draw();
+    }
+}
+
+impl<W: Widget> ProvidesRegistryState for Window<W> {
+    fn registry(&mut self) -> &mut RegistryState { &mut self.registry_state }
+    registry_handlers![OutputState, SeatState];
+}
+
+impl<W: Widget> Dispatch<WpFractionalScaleV1, ()> for Window<W> {
+    fn event(state: &mut Self, _: &WpFractionalScaleV1, event: wp_fractional_scale_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let wp_fractional_scale_v1::Event::PreferredScale { scale } = event {
+            // Sent in 120ths of a unit
+            state.set_scale(scale as f64 / 120.0);
+        }
+    }
+}
+
+impl<W: Widget> Dispatch<ZwpTextInputV3, ()> for Window<W> {
+    fn event(state: &mut Self, text_input: &ZwpTextInputV3, event: zwp_text_input_v3::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        use zwp_text_input_v3::Event;
+        match event {
+            Event::Enter { .. } if state.widget.text_cursor().is_some() => {
+                text_input.enable();
+                text_input.set_content_type(ContentHint::None, ContentPurpose::Normal);
+                text_input.commit();
+                // Anything differs from this, so the next frame sends the real caret
+                state.text_cursor = Some(Rect { x: 0, y: 0, w: 0, h: 0 });
+                state.send_text_cursor();
+            }
+            Event::Leave { .. } if state.text_cursor.take().is_some() => {
+                text_input.disable();
+                text_input.commit();
+                // Drop a composition left on screen
+                let update = state.widget.text_input(&TextInput::default());
+                state.apply(update);
+            }
+            Event::PreeditString { text, cursor_begin, .. } => {
+                state.text_input_pending.preedit = text.map(|t| {
+                    let cursor = usize::try_from(cursor_begin).ok().filter(|&c| t.is_char_boundary(c));
+                    (t, cursor)
+                });
+            }
+            Event::CommitString { text } => state.text_input_pending.commit = text,
+            Event::DeleteSurroundingText { before_length, after_length } => {
+                state.text_input_pending.delete = (before_length as usize, after_length as usize);
+            }
+            Event::Done { .. } => {
+                let event = std::mem::take(&mut state.text_input_pending);
+                let update = state.widget.text_input(&event);
+                state.apply(update);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<W: Widget> Dispatch<ZwlrDataControlDeviceV1, ()> for Window<W> {
+    fn event(state: &mut Self, device: &ZwlrDataControlDeviceV1, event: zwlr_data_control_device_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        use zwlr_data_control_device_v1::Event;
+        match event {
+            Event::Selection { id } => {
+                if let Some(old) = std::mem::replace(&mut state.clipboard.offer, id) { old.destroy(); }
+            }
+            Event::PrimarySelection { id } => {
+                if let Some(old) = std::mem::replace(&mut state.clipboard.primary_offer, id) { old.destroy(); }
+            }
+            Event::Finished => {
+                device.destroy();
+                state.clipboard.device = None;
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(Window<W>, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl<W: Widget> Dispatch<ZwlrDataControlSourceV1, ()> for Window<W> {
+    fn event(state: &mut Self, source: &ZwlrDataControlSourceV1, event: zwlr_data_control_source_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        use zwlr_data_control_source_v1::Event;
+        match event {
+            Event::Send { fd, .. } => {
+                if let Some(data) = state.clipboard.offered_by(&source.id()) { clipboard::serve(&state.loop_handle, fd.into(), data); }
+            }
+            Event::Cancelled => state.clipboard.cancelled(&source.id()),
+            _ => {}
+        }
+    }
+}
+
+macro_rules! ignore_events {
+    ($($iface:ty),*) => {$(
+        impl<W: Widget> Dispatch<$iface, ()> for Window<W> {
+            fn event(_: &mut Self, _: &$iface, _: <$iface as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+        }
+    )*};
+}
+ignore_events!(WpFractionalScaleManagerV1, WpViewporter, WpViewport, ZwpTextInputManagerV3, ZwlrDataControlManagerV1, ZwlrDataControlOfferV1);
+
+delegate_compositor!(@<W: Widget> Window<W>);
+delegate_output!(@<W: Widget> Window<W>);
+delegate_seat!(@<W: Widget> Window<W>);
+delegate_keyboard!(@<W: Widget> Window<W>);
+delegate_pointer!(@<W: Widget> Window<W>);
+delegate_touch!(@<W: Widget> Window<W>);
+delegate_shm!(@<W: Widget> Window<W>);
+delegate_data_device!(@<W: Widget> Window<W>);
+delegate_layer!(@<W: Widget> Window<W>);
+delegate_registry!(@<W: Widget> Window<W>);
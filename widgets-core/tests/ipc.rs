@@ -0,0 +1,53 @@
+//! The `--daemon` socket: a client still sending its stdin doesn't hold up
+//! the event loop or anyone else's request.
+
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+use smithay_client_toolkit::reexports::calloop::EventLoop;
+use widgets_core::ipc::{self, Request};
+
+#[test]
+fn slow_clients_dont_block() {
+    let dir = std::env::temp_dir().join("widgets-ipc-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    // SAFETY: the only test in this binary, set before anything reads it
+    unsafe { std::env::set_var("XDG_RUNTIME_DIR", &dir) };
+    let mut event_loop: EventLoop<Vec<Request>> = EventLoop::try_new().unwrap();
+    ipc::serve(&event_loop.handle(), "test", |req, got: &mut Vec<Request>| got.push(req)).unwrap();
+    let mut got = Vec::new();
+    let dispatch = |event_loop: &mut EventLoop<Vec<Request>>, got: &mut Vec<Request>| {
+        for _ in 0..5 {
+            event_loop.dispatch(Some(Duration::from_millis(20)), got).unwrap();
+        }
+    };
+
+    // Half a request, then nothing for now
+    let mut slow = UnixStream::connect(ipc::socket_path("test")).unwrap();
+    slow.write_all(b"show\0--dmenu\nfirst\n").unwrap();
+    dispatch(&mut event_loop, &mut got);
+    assert!(got.is_empty());
+
+    let mut quick = UnixStream::connect(ipc::socket_path("test")).unwrap();
+    quick.write_all(b"hide\n").unwrap();
+    quick.shutdown(Shutdown::Write).unwrap();
+    dispatch(&mut event_loop, &mut got);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].command, "hide");
+
+    slow.write_all(b"second\n").unwrap();
+    slow.shutdown(Shutdown::Write).unwrap();
+    dispatch(&mut event_loop, &mut got);
+    assert_eq!(got.len(), 2);
+    assert_eq!((got[1].command.as_str(), &got[1].args[..]), ("show", &["--dmenu".to_string()][..]));
+    assert_eq!(got[1].stdin, "first\nsecond\n");
+
+    // Replies go back on the stream, which the widget writes blocking
+    writeln!(got[1].stream, "picked").unwrap();
+    got.clear();
+    let mut reply = String::new();
+    slow.read_to_string(&mut reply).unwrap();
+    assert_eq!(reply, "picked\n");
+    std::fs::remove_dir_all(&dir).ok();
+}